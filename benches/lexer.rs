@@ -0,0 +1,24 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use nonsense::Program;
+use std::hint::black_box;
+
+fn input(size: usize) -> String {
+    let query = ".users(org: 2) u where { age >= 18 and not u.name = \"Ada\" } { u.name, count(id) as total } group by u.name order by total desc limit 10\n";
+    query.repeat(size / query.len() + 1)
+}
+
+fn lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for size in [64 * 1024, 4 * 1024 * 1024] {
+        let input = input(size);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.sample_size(10);
+        group.bench_function(format!("{}KB", size / 1024), |b| {
+            b.iter(|| Program::parse(black_box(&input)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lexer);
+criterion_main!(benches);
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nonsense::cst::Cst;
+use nonsense::{format, parse_lossy, tokenize, try_compile, Context};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = try_compile(data, &mut Context::default());
+    let (program, _) = parse_lossy(data);
+    let _ = program.diagnose(&mut Context::default());
+    if let Ok(input) = std::str::from_utf8(data) {
+        tokenize(input);
+        assert_eq!(Cst::parse(input).to_string(), input);
+        let _ = format::format(input);
+    }
+});
@@ -0,0 +1,36 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+#[proc_macro]
+pub fn nonsense(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input.into()).into()
+}
+
+fn expand(input: TokenStream) -> TokenStream {
+    let query = match syn::parse2::<LitStr>(input) {
+        Ok(query) => query,
+        Err(error) => return error.to_compile_error(),
+    };
+    match nonsense::compile(&query.value(), &mut nonsense::Context::default()) {
+        Ok(sql) => quote!(#sql),
+        Err(error) => syn::Error::new(query.span(), error).to_compile_error(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(
+            expand(quote!(".users { name, id }")).to_string(),
+            "\"SELECT name, id FROM users;\""
+        );
+        assert!(expand(quote!(".users {"))
+            .to_string()
+            .ends_with("compile_error ! { \"PARSE ERROR: expected RBRACK, got EOF\" }"));
+        assert!(expand(quote!(users)).to_string().contains("compile_error"));
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{DotStatement, PairStatement, Stmt, WildcardStatement};
+use std::ops::{Index, IndexMut, Range};
+
+/// A node of an arena
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeId(u32);
+
+/// Sibling nodes allocated next to each other, like the selections of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Nodes {
+    start: u32,
+    len: u32,
+}
+impl Nodes {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (self.start..self.start + self.len).map(NodeId)
+    }
+    fn range(&self) -> Range<usize> {
+        self.start as usize..(self.start + self.len) as usize
+    }
+}
+
+/// Every node below the statements of a program. Children are ids into it rather than boxes, so
+/// parsing only pushes onto one vector and the whole tree is dropped at once
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arena {
+    nodes: Vec<Stmt>,
+}
+impl Arena {
+    pub fn alloc(&mut self, node: Stmt) -> NodeId {
+        self.nodes.push(node);
+        NodeId(self.nodes.len() as u32 - 1)
+    }
+    pub fn alloc_all(&mut self, nodes: impl IntoIterator<Item = Stmt>) -> Nodes {
+        let start = self.nodes.len() as u32;
+        self.nodes.extend(nodes);
+        Nodes {
+            start,
+            len: self.nodes.len() as u32 - start,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    // Moves a node out to be folded, a childless placeholder holds its place until it's put back
+    pub(crate) fn take(&mut self, id: NodeId) -> Stmt {
+        let placeholder = Stmt::Wildcard(WildcardStatement { exclude: vec![] });
+        std::mem::replace(&mut self[id], placeholder)
+    }
+    /// Copies a statement and every node below it into another arena
+    pub fn copy(&self, statement: &Stmt, to: &mut Arena) -> Stmt {
+        let mut copy = statement.clone();
+        children(&mut copy, &mut |child| match child {
+            Child::Node(id) => {
+                let node = self.copy(&self[*id], to);
+                *id = to.alloc(node);
+            }
+            Child::Nodes(nodes) => {
+                let copied: Vec<Stmt> = self[*nodes]
+                    .iter()
+                    .map(|node| self.copy(node, to))
+                    .collect();
+                *nodes = to.alloc_all(copied);
+            }
+        });
+        copy
+    }
+    /// Replaces the nodes in `range` with the nodes of another arena. `statements` point into the
+    /// other arena and `later` past the range, their ids are moved to where the nodes end up
+    pub(crate) fn splice(
+        &mut self,
+        range: Range<usize>,
+        other: Arena,
+        statements: &mut [Stmt],
+        later: &mut [Stmt],
+    ) {
+        let start = range.start as u32;
+        let end = range.end as u32;
+        let (removed, added) = (end - start, other.nodes.len() as u32);
+        let mut nodes = other.nodes;
+        for node in nodes.iter_mut().chain(statements.iter_mut()) {
+            renumber(node, &|id| id + start);
+        }
+        let moved = |id: u32| if id >= end { id + added - removed } else { id };
+        for node in self.nodes[range.end..].iter_mut().chain(later.iter_mut()) {
+            renumber(node, &moved);
+        }
+        self.nodes.splice(range, nodes);
+    }
+    /// Copies a statement and every node below it within the arena, so the copy can be folded on
+    /// its own. Folds change nodes in place, so a node is never shared by two parents
+    pub fn duplicate(&mut self, statement: &Stmt) -> Stmt {
+        let mut copy = statement.clone();
+        children(&mut copy, &mut |child| match child {
+            Child::Node(id) => {
+                let node = self[*id].clone();
+                let node = self.duplicate(&node);
+                *id = self.alloc(node);
+            }
+            Child::Nodes(nodes) => {
+                let originals = self[*nodes].to_vec();
+                let copied: Vec<Stmt> = originals.iter().map(|node| self.duplicate(node)).collect();
+                *nodes = self.alloc_all(copied);
+            }
+        });
+        copy
+    }
+}
+impl Index<NodeId> for Arena {
+    type Output = Stmt;
+    fn index(&self, id: NodeId) -> &Stmt {
+        &self.nodes[id.0 as usize]
+    }
+}
+impl IndexMut<NodeId> for Arena {
+    fn index_mut(&mut self, id: NodeId) -> &mut Stmt {
+        &mut self.nodes[id.0 as usize]
+    }
+}
+impl Index<Nodes> for Arena {
+    type Output = [Stmt];
+    fn index(&self, nodes: Nodes) -> &[Stmt] {
+        &self.nodes[nodes.range()]
+    }
+}
+
+/// One past the last node below a statement, None when it holds no nodes. Nodes are allocated
+/// after their children, so the last one is among the ids the statement holds itself
+pub(crate) fn end(statement: &Stmt) -> Option<usize> {
+    let mut end = None;
+    children(&mut statement.clone(), &mut |child| {
+        let last = match child {
+            Child::Node(id) => id.0 as usize + 1,
+            Child::Nodes(nodes) if nodes.is_empty() => return,
+            Child::Nodes(nodes) => nodes.range().end,
+        };
+        end = end.max(Some(last));
+    });
+    end
+}
+
+fn renumber(statement: &mut Stmt, map: &dyn Fn(u32) -> u32) {
+    children(statement, &mut |child| match child {
+        Child::Node(id) => id.0 = map(id.0),
+        Child::Nodes(nodes) => nodes.start = map(nodes.start),
+    });
+}
+
+enum Child<'a> {
+    Node(&'a mut NodeId),
+    Nodes(&'a mut Nodes),
+}
+
+// Every id a statement holds, including those of the queries it nests by value
+fn children(statement: &mut Stmt, each: &mut dyn FnMut(Child)) {
+    match statement {
+        Stmt::Dot(dot) => dot_children(dot, each),
+        Stmt::Insert(insert) => pair_children(&mut insert.values, each),
+        Stmt::Update(update) => {
+            pair_children(&mut update.filters, each);
+            pair_children(&mut update.values, each);
+        }
+        Stmt::Delete(delete) => {
+            pair_children(&mut delete.filters, each);
+            if let Some(condition) = &mut delete.condition {
+                each(Child::Node(condition));
+            }
+        }
+        Stmt::Block(block) => each(Child::Nodes(&mut block.properties)),
+        Stmt::Prefix(prefix) => each(Child::Node(&mut prefix.right)),
+        Stmt::Infix(infix) => {
+            each(Child::Node(&mut infix.left));
+            each(Child::Node(&mut infix.right));
+        }
+        Stmt::IsNull(expression) => each(Child::Node(&mut expression.operand)),
+        Stmt::Call(call) => each(Child::Nodes(&mut call.arguments)),
+        Stmt::Alias(alias) => each(Child::Node(&mut alias.selection)),
+        Stmt::Join(join) => each(Child::Nodes(&mut join.block.properties)),
+        Stmt::Subquery(subquery) => dot_children(&mut subquery.query, each),
+        Stmt::Union(union) => {
+            each(Child::Node(&mut union.left));
+            dot_children(&mut union.right, each);
+        }
+        Stmt::With(with) => {
+            for table in &mut with.tables {
+                children(&mut table.query, each);
+            }
+            each(Child::Node(&mut with.statement));
+        }
+        Stmt::Case(case) => {
+            for (condition, result) in &mut case.branches {
+                each(Child::Node(condition));
+                each(Child::Node(result));
+            }
+            if let Some(otherwise) = &mut case.otherwise {
+                each(Child::Node(otherwise));
+            }
+        }
+        Stmt::In(expression) => {
+            each(Child::Node(&mut expression.operand));
+            each(Child::Nodes(&mut expression.values));
+        }
+        Stmt::Between(expression) => {
+            each(Child::Node(&mut expression.operand));
+            each(Child::Node(&mut expression.low));
+            each(Child::Node(&mut expression.high));
+        }
+        Stmt::Like(expression) => {
+            each(Child::Node(&mut expression.operand));
+            each(Child::Node(&mut expression.pattern));
+        }
+        Stmt::Identifier(_)
+        | Stmt::Literal(_)
+        | Stmt::Spread(_)
+        | Stmt::Wildcard(_)
+        | Stmt::Variable(_)
+        | Stmt::Env(_)
+        | Stmt::Temporal(_) => {}
+    }
+}
+
+fn dot_children(dot: &mut DotStatement, each: &mut dyn FnMut(Child)) {
+    pair_children(&mut dot.filters, each);
+    for join in &mut dot.joins {
+        if let Some(condition) = &mut join.condition {
+            each(Child::Node(condition));
+        }
+    }
+    if let Some(condition) = &mut dot.condition {
+        each(Child::Node(condition));
+    }
+    each(Child::Nodes(&mut dot.block.properties));
+}
+
+fn pair_children(pairs: &mut [PairStatement], each: &mut dyn FnMut(Child)) {
+    for pair in pairs {
+        each(Child::Node(&mut pair.value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Program, Statement};
+
+    #[test]
+    fn test_arena() {
+        let program =
+            Program::new(".users where { age > 18 and name = \"Ada\" } { id, name }").unwrap();
+        let Stmt::Dot(dot) = &program.statements()[0] else {
+            panic!("expected a select");
+        };
+        let properties = &program.arena()[dot.block.properties];
+        assert_eq!(properties.len(), 2);
+        assert_eq!(dot.block.properties.ids().count(), 2);
+        let mut copied = Arena::default();
+        let copy = program.arena().copy(&program.statements()[0], &mut copied);
+        assert_eq!(copied.len(), program.arena().len());
+        assert_eq!(
+            Program::from_arena(copied, vec![copy]).run_with(&mut Context::default()),
+            program.run_with(&mut Context::default())
+        );
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut program = Program::new(".users where { age > 18 } { id }").unwrap();
+        let statement = program.statements()[0].clone();
+        let before = program.arena().len();
+        let copy = program.arena.duplicate(&statement);
+        assert_eq!(program.arena().len(), before * 2);
+        assert_ne!(copy, statement);
+        let arena = program.arena();
+        assert_eq!(copy.ast(arena), statement.ast(arena));
+    }
+}
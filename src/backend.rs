@@ -0,0 +1,118 @@
+use crate::{elasticsearch, mongodb, Context, Program, Result};
+
+/// A code generator turning a parsed program into the text of some query language
+pub trait Backend {
+    fn name(&self) -> &str;
+    fn compile(&self, program: &Program, context: &mut Context) -> Result<String>;
+}
+
+pub struct Sql;
+impl Backend for Sql {
+    fn name(&self) -> &str {
+        "sql"
+    }
+    fn compile(&self, program: &Program, context: &mut Context) -> Result<String> {
+        program.run_with(context)
+    }
+}
+
+pub struct MongoDb;
+impl Backend for MongoDb {
+    fn name(&self) -> &str {
+        "mongodb"
+    }
+    fn compile(&self, program: &Program, context: &mut Context) -> Result<String> {
+        mongodb::compile(program, context)
+    }
+}
+
+pub struct Elasticsearch;
+impl Backend for Elasticsearch {
+    fn name(&self) -> &str {
+        "elasticsearch"
+    }
+    fn compile(&self, program: &Program, context: &mut Context) -> Result<String> {
+        elasticsearch::compile(program, context)
+    }
+}
+
+/// The backends a target name can pick from, starting with the built in ones
+pub struct Registry {
+    backends: Vec<Box<dyn Backend>>,
+}
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            backends: vec![Box::new(Sql), Box::new(MongoDb), Box::new(Elasticsearch)],
+        }
+    }
+}
+impl Registry {
+    /// Adds a backend, replacing any registered under the same name
+    pub fn register(&mut self, backend: impl Backend + 'static) {
+        self.backends
+            .retain(|existing| existing.name() != backend.name());
+        self.backends.push(Box::new(backend));
+    }
+    pub fn get(&self, name: &str) -> Option<&dyn Backend> {
+        self.backends
+            .iter()
+            .find(|backend| backend.name() == name)
+            .map(Box::as_ref)
+    }
+    pub fn names(&self) -> Vec<&str> {
+        self.backends.iter().map(|backend| backend.name()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Stmt;
+
+    struct Tables;
+    impl Backend for Tables {
+        fn name(&self) -> &str {
+            "tables"
+        }
+        fn compile(&self, program: &Program, _context: &mut Context) -> Result<String> {
+            let tables = program
+                .statements()
+                .iter()
+                .map(|statement| match statement {
+                    Stmt::Dot(dot) => dot.ident.literal.to_string(),
+                    _ => String::new(),
+                });
+            Ok(tables.collect::<Vec<String>>().join("\n"))
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = Registry::default();
+        registry.register(Tables);
+        assert_eq!(
+            registry.names(),
+            ["sql", "mongodb", "elasticsearch", "tables"]
+        );
+        let program = Program::new(".users { name } .posts { title }").unwrap();
+        let mut context = Context::default();
+        assert_eq!(
+            registry
+                .get("tables")
+                .unwrap()
+                .compile(&program, &mut context)
+                .unwrap(),
+            "users\nposts"
+        );
+        assert_eq!(
+            registry
+                .get("sql")
+                .unwrap()
+                .compile(&program, &mut context)
+                .unwrap(),
+            "SELECT name FROM users; SELECT title FROM posts;"
+        );
+        assert!(registry.get("cypher").is_none());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::cache::{self, Cache};
+use crate::{failure, render, worst, write, BuildArgs, Failure};
+use nonsense::compile;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+impl BuildArgs {
+    fn out_dir(&self) -> &Path {
+        self.out_dir.as_deref().unwrap_or(Path::new("build"))
+    }
+}
+
+pub fn run(args: BuildArgs) -> Result<(), Failure> {
+    if !args.path.is_dir() {
+        return Err(Failure::from(format!(
+            "EXEC ERROR: {} is not a directory",
+            args.path.display()
+        )));
+    }
+    let mut files = Vec::new();
+    collect(&args.path, Some(args.out_dir()), &mut files);
+    let cache = (!args.no_cache).then(|| Cache::new(&args.out_dir().join(".cache"), &args.context));
+    files.sort();
+    let jobs = args
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, usize::from)
+        .min(files.len());
+    // Workers take the next file in turn, the results are reported in file order
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<String, Failure>)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = files.get(index) else {
+                            break;
+                        };
+                        results.push((index, build(&args, cache.as_ref(), path)));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    let mut exits = vec![];
+    for (_, result) in results {
+        match result {
+            Ok(line) => eprintln!("{}", line),
+            Err(failure) => {
+                eprintln!("{}", failure.message);
+                exits.push(failure.exit);
+            }
+        }
+    }
+    let failed = exits.len();
+    match &cache {
+        Some(cache) => eprintln!(
+            "{} compiled, {} failed, {} from the cache",
+            files.len() - failed,
+            failed,
+            cache.hits()
+        ),
+        None => eprintln!("{} compiled, {} failed", files.len() - failed, failed),
+    }
+    if failed > 0 {
+        return Err(Failure {
+            exit: worst(exits),
+            message: format!("{} of {} files failed", failed, files.len()),
+        });
+    }
+    Ok(())
+}
+
+fn build(args: &BuildArgs, cache: Option<&Cache>, path: &Path) -> Result<String, Failure> {
+    let input = std::fs::read_to_string(path)
+        .map_err(|error| format!("EXEC ERROR: Failed to read {}: {}", path.display(), error))?;
+    let (output, cached) = cache::compile(cache, &input, || {
+        let mut context = args.context.context()?;
+        let sql = compile(&input, &mut context)
+            .map_err(|error| failure(&path.display().to_string(), &input, error, &mut context))?;
+        Ok::<_, Failure>(render(&sql, &context))
+    })?;
+    let target = target(&args.path, path, args.out_dir());
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|error| {
+            format!(
+                "EXEC ERROR: Failed to create {}: {}",
+                parent.display(),
+                error
+            )
+        })?;
+    }
+    write(&target, &output)?;
+    let hit = if cached { " (cached)" } else { "" };
+    Ok(format!("{} -> {}{}", path.display(), target.display(), hit))
+}
+
+fn target(root: &Path, path: &Path, out_dir: &Path) -> PathBuf {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    out_dir.join(relative).with_extension("sql")
+}
+
+pub fn collect(path: &Path, out_dir: Option<&Path>, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if hidden || Some(path.as_path()) == out_dir {
+            continue;
+        }
+        if path.is_dir() {
+            collect(&path, out_dir, files);
+        } else if path.extension().is_some_and(|extension| extension == "ns") {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn test_target() {
+        assert_eq!(
+            target(
+                Path::new("src/queries"),
+                Path::new("src/queries/users/active.ns"),
+                Path::new("build")
+            ),
+            PathBuf::from("build/users/active.sql")
+        );
+    }
+
+    #[test]
+    fn test_run_parallel() {
+        let directory = std::env::temp_dir().join(format!("nonsense-build-{}", std::process::id()));
+        let queries = directory.join("queries");
+        std::fs::create_dir_all(&queries).unwrap();
+        for index in 0..20 {
+            let query = match index {
+                7 => ".broken {".to_string(),
+                index => format!(".table{} {{ id }}", index),
+            };
+            std::fs::write(queries.join(format!("{}.ns", index)), query).unwrap();
+        }
+        let out_dir = directory.join("build");
+        let argv = ["nonsense".into(), "build".into(), queries.into_os_string()];
+        let Some(crate::Command::Build(mut args)) = crate::Cli::parse_from(argv).command else {
+            unreachable!();
+        };
+        args.out_dir = Some(out_dir.clone());
+        args.jobs = NonZeroUsize::new(4);
+        let failure = run(args).unwrap_err();
+        assert_eq!(failure.exit, crate::Exit::Syntax);
+        assert_eq!(failure.message, "1 of 20 files failed");
+        assert_eq!(
+            std::fs::read_to_string(out_dir.join("19.sql")).unwrap(),
+            "SELECT id FROM table19;\n"
+        );
+        assert!(!out_dir.join("7.sql").exists());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use crate::ContextArgs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Compiled output on disk, keyed by a hash of the source, the options and the files they name
+pub struct Cache {
+    directory: PathBuf,
+    options: u64,
+    hits: AtomicUsize,
+    writes: AtomicUsize,
+}
+impl Cache {
+    pub fn new(directory: &Path, args: &ContextArgs) -> Cache {
+        let mut hash = Fnv::default();
+        hash.write(env!("CARGO_PKG_VERSION"));
+        hash.write(&format!("{:?}", args));
+        // The schema and variables files can change without the options naming them changing
+        for path in [&args.schema, &args.vars].into_iter().flatten() {
+            hash.write(&std::fs::read_to_string(path).unwrap_or_default());
+        }
+        Cache {
+            directory: directory.to_path_buf(),
+            options: hash.0,
+            hits: AtomicUsize::new(0),
+            writes: AtomicUsize::new(0),
+        }
+    }
+    fn key(&self, input: &str) -> Option<String> {
+        // Values read from the environment aren't part of the source, so those files always compile
+        if input.contains("${") || input.contains("env(") {
+            return None;
+        }
+        let mut hash = Fnv(self.options);
+        hash.write(input);
+        Some(format!("{:016x}", hash.0))
+    }
+    fn get(&self, key: &str) -> Option<String> {
+        let output = std::fs::read_to_string(self.directory.join(key)).ok()?;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(output)
+    }
+    /// Best effort, a cache that can't be written only means compiling again next time
+    fn set(&self, key: &str, output: &str) {
+        let index = self.writes.fetch_add(1, Ordering::Relaxed);
+        let temporary = self
+            .directory
+            .join(format!("{}.{}.{}", key, std::process::id(), index));
+        let written = std::fs::create_dir_all(&self.directory)
+            .and_then(|_| std::fs::write(&temporary, output))
+            .and_then(|_| std::fs::rename(&temporary, self.directory.join(key)));
+        if written.is_err() {
+            let _ = std::fs::remove_file(temporary);
+        }
+    }
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+/// The output of unchanged sources comes from the cache, and whether it did
+pub fn compile<E>(
+    cache: Option<&Cache>,
+    input: &str,
+    compile: impl FnOnce() -> Result<String, E>,
+) -> Result<(String, bool), E> {
+    let key = cache.and_then(|cache| cache.key(input));
+    if let Some(output) = cache
+        .zip(key.as_deref())
+        .and_then(|(cache, key)| cache.get(key))
+    {
+        return Ok((output, true));
+    }
+    let output = compile()?;
+    if let Some((cache, key)) = cache.zip(key.as_deref()) {
+        cache.set(key, &output);
+    }
+    Ok((output, false))
+}
+
+// FNV-1a, unlike the standard hasher it is the same across Rust versions
+struct Fnv(u64);
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+}
+impl Fnv {
+    fn write(&mut self, text: &str) {
+        for byte in text.bytes().chain([0]) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_cache() {
+        let directory = std::env::temp_dir().join(format!("nonsense-cache-{}", std::process::id()));
+        let args = |flags: &[&str]| {
+            let argv = ["nonsense", "repl"].iter().chain(flags);
+            let Some(crate::Command::Repl(args)) = crate::Cli::parse_from(argv).command else {
+                unreachable!();
+            };
+            args
+        };
+        let cache = Cache::new(&directory, &args(&[]));
+        let key = cache.key(".users { name }").unwrap();
+        assert_eq!(cache.get(&key), None);
+        cache.set(&key, "SELECT name FROM users;\n");
+        assert_eq!(cache.get(&key).unwrap(), "SELECT name FROM users;\n");
+        assert_eq!(cache.hits(), 1);
+        assert_ne!(cache.key(".users { id }").unwrap(), key);
+        let mysql = Cache::new(&directory, &args(&["--dialect", "mysql"]));
+        assert_ne!(mysql.key(".users { name }").unwrap(), key);
+        assert_eq!(cache.key(".users where { id = ${USER_ID} } { name }"), None);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{ContextArgs, KeywordCasing, LintArgs};
+use clap::ValueEnum;
+use nonsense::{dialect, lint};
+use std::path::{Path, PathBuf};
+
+pub const FILE: &str = "nonsense.toml";
+
+/// Defaults from the nearest nonsense.toml, flags given on the command line win
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub dialect: Option<String>,
+    pub schema: Option<PathBuf>,
+    pub keyword_case: Option<KeywordCasing>,
+    pub out_dir: Option<PathBuf>,
+    pub lints: Vec<(String, bool)>,
+}
+impl Config {
+    /// Walks up from the input, or the working directory when reading stdin
+    pub fn discover(input: Option<&Path>) -> Result<Option<Config>, String> {
+        let current = std::env::current_dir().map_err(|error| {
+            format!(
+                "EXEC ERROR: Failed to read the working directory: {}",
+                error
+            )
+        })?;
+        let start = match input {
+            Some(path) if path.is_dir() => current.join(path),
+            Some(path) => current
+                .join(path)
+                .parent()
+                .map_or(current.clone(), Path::to_path_buf),
+            None => current.clone(),
+        };
+        let Some(path) = start
+            .ancestors()
+            .map(|directory| directory.join(FILE))
+            .find(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+        let input = std::fs::read_to_string(&path)
+            .map_err(|error| format!("EXEC ERROR: Failed to read {}: {}", path.display(), error))?;
+        // Paths in the file are relative to it, kept short when it sits below the working directory
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let base = directory.strip_prefix(&current).unwrap_or(directory);
+        Config::parse(&input, base)
+            .map(Some)
+            .map_err(|error| format!("EXEC ERROR: Invalid config {}: {}", path.display(), error))
+    }
+    fn parse(input: &str, base: &Path) -> Result<Config, String> {
+        let table: toml::Table = input.parse().map_err(|error| format!("{}", error))?;
+        let mut config = Config::default();
+        for (key, value) in &table {
+            let text = || {
+                value
+                    .as_str()
+                    .ok_or_else(|| format!("expected {} to be a string", key))
+            };
+            match key.as_str() {
+                "dialect" => {
+                    let name = text()?;
+                    if !dialect::NAMES.contains(&name) {
+                        return Err(format!(
+                            "expected dialect to be one of {}",
+                            dialect::NAMES.join(", ")
+                        ));
+                    }
+                    config.dialect = Some(name.to_string());
+                }
+                "schema" => config.schema = Some(base.join(text()?)),
+                "out-dir" => config.out_dir = Some(base.join(text()?)),
+                "keyword-case" => {
+                    let casing = KeywordCasing::from_str(text()?, false)
+                        .map_err(|_| "expected keyword-case to be upper or lower".to_string())?;
+                    config.keyword_case = Some(casing);
+                }
+                "lints" => {
+                    let levels = value
+                        .as_table()
+                        .ok_or_else(|| "expected lints to be a table".to_string())?;
+                    for (name, level) in levels {
+                        if !lint::NAMES.contains(&name.as_str()) {
+                            return Err(format!("unknown lint {}", name));
+                        }
+                        let denied = match level.as_str() {
+                            Some("deny") => true,
+                            Some("allow") => false,
+                            Some("warn") => continue,
+                            _ => {
+                                return Err(format!(
+                                    "expected lints.{} to be allow, warn or deny",
+                                    name
+                                ))
+                            }
+                        };
+                        config.lints.push((name.clone(), denied));
+                    }
+                }
+                key => return Err(format!("unknown key {}", key)),
+            }
+        }
+        Ok(config)
+    }
+    pub fn apply(&self, args: &mut ContextArgs) {
+        if args.dialect.is_none() {
+            args.dialect.clone_from(&self.dialect);
+        }
+        if args.schema.is_none() {
+            args.schema.clone_from(&self.schema);
+        }
+        if args.keyword_case.is_none() {
+            args.keyword_case = self.keyword_case;
+        }
+    }
+    pub fn apply_lints(&self, args: &mut LintArgs) {
+        self.apply(&mut args.context);
+        for (name, denied) in &self.lints {
+            if args.allow.contains(name) || args.deny.contains(name) {
+                continue;
+            }
+            match denied {
+                true => args.deny.push(name.clone()),
+                false => args.allow.push(name.clone()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = Config::parse(
+            "dialect = \"mysql\"\nschema = \"schema.toml\"\nkeyword-case = \"lower\"\nout-dir = \"sql\"\n\n[lints]\nduplicate-column = \"deny\"\nempty-block = \"allow\"\n",
+            Path::new("project"),
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                dialect: Some("mysql".to_string()),
+                schema: Some(PathBuf::from("project/schema.toml")),
+                keyword_case: Some(KeywordCasing::Lower),
+                out_dir: Some(PathBuf::from("project/sql")),
+                lints: vec![
+                    ("duplicate-column".to_string(), true),
+                    ("empty-block".to_string(), false)
+                ],
+            }
+        );
+        assert_eq!(
+            Config::parse("dialect = \"oracle\"", Path::new("")).unwrap_err(),
+            "expected dialect to be one of postgres, mysql, sqlite, mssql"
+        );
+        assert_eq!(
+            Config::parse("pretty = true", Path::new("")).unwrap_err(),
+            "unknown key pretty"
+        );
+    }
+}
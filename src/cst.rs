@@ -0,0 +1,336 @@
+use crate::{tokenize, Span, TokenKind};
+use std::fmt;
+
+/// Whitespace and comments, kept so a tree prints back to the exact source it came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia {
+    Whitespace(String),
+    Comment(String),
+}
+impl Trivia {
+    fn text(&self) -> &str {
+        match self {
+            Trivia::Whitespace(text) | Trivia::Comment(text) => text,
+        }
+    }
+}
+
+/// A token with the trivia before it and the trivia after it up to the end of its line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+/// Tokens between braces or parentheses, the close is missing when the input ends first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub open: Token,
+    pub children: Vec<Node>,
+    pub close: Option<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Token(Token),
+    Group(Group),
+}
+
+/// A concrete syntax tree, which unlike the `Program` keeps every character of the source.
+/// Input that doesn't parse still makes a tree, so tools can rewrite files that have errors
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cst {
+    pub nodes: Vec<Node>,
+    /// Trivia after the last token
+    pub end: Vec<Trivia>,
+}
+impl Cst {
+    pub fn parse(input: &str) -> Cst {
+        let mut tree = Builder::default();
+        let mut position = 0;
+        let mut tokens = tokenize(input).into_iter();
+        loop {
+            let token = tokens.next();
+            let start = token
+                .as_ref()
+                .map_or(input.len(), |token| token.span.start)
+                .max(position);
+            let gap = &input[position..start];
+            if gap.trim().is_empty() && !gap.is_empty() {
+                tree.pending.push(Trivia::Whitespace(gap.to_string()));
+            } else if !gap.is_empty() {
+                // Characters the lexer skipped over after an error
+                let span = Span {
+                    start: position,
+                    end: start,
+                };
+                tree.push(TokenKind::Illegal, gap, span);
+            }
+            let Some(token) = token else {
+                break;
+            };
+            position = token.span.end.max(start);
+            let text = &input[start..position];
+            match token.kind {
+                TokenKind::Comment => tree.pending.push(Trivia::Comment(text.to_string())),
+                kind => tree.push(kind, text, token.span),
+            }
+        }
+        tree.finish()
+    }
+    /// Every token in source order
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = vec![];
+        collect(&self.nodes, &mut tokens);
+        tokens
+    }
+    /// Renames every identifier called `from`, including the parts of qualified names like
+    /// `u.from`, and returns how many were changed. Strings and comments are left alone
+    pub fn rename(&mut self, from: &str, to: &str) -> usize {
+        rename(&mut self.nodes, from, to)
+    }
+}
+impl fmt::Display for Cst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in &self.nodes {
+            write!(f, "{}", node)?;
+        }
+        for trivia in &self.end {
+            f.write_str(trivia.text())?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Token(token) => write!(f, "{}", token),
+            Node::Group(group) => {
+                write!(f, "{}", group.open)?;
+                for child in &group.children {
+                    write!(f, "{}", child)?;
+                }
+                match &group.close {
+                    Some(close) => write!(f, "{}", close),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for trivia in &self.leading {
+            f.write_str(trivia.text())?;
+        }
+        f.write_str(&self.text)?;
+        for trivia in &self.trailing {
+            f.write_str(trivia.text())?;
+        }
+        Ok(())
+    }
+}
+
+fn closes(open: TokenKind, close: TokenKind) -> bool {
+    matches!(
+        (open, close),
+        (TokenKind::LBrace, TokenKind::RBrace) | (TokenKind::LParen, TokenKind::RParen)
+    )
+}
+
+#[derive(Default)]
+struct Builder {
+    nodes: Vec<Node>,
+    open: Vec<(Token, Vec<Node>)>,
+    pending: Vec<Trivia>,
+}
+impl Builder {
+    fn push(&mut self, kind: TokenKind, text: &str, span: Span) {
+        let leading = self.attach();
+        let token = Token {
+            kind,
+            text: text.to_string(),
+            span,
+            leading,
+            trailing: vec![],
+        };
+        let closing = self
+            .open
+            .last()
+            .is_some_and(|(open, _)| closes(open.kind, kind));
+        match kind {
+            TokenKind::LBrace | TokenKind::LParen => self.open.push((token, vec![])),
+            TokenKind::RBrace | TokenKind::RParen if closing => {
+                let (open, children) = self.open.pop().unwrap();
+                self.children().push(Node::Group(Group {
+                    open,
+                    children,
+                    close: Some(token),
+                }));
+            }
+            _ => self.children().push(Node::Token(token)),
+        }
+    }
+    fn finish(mut self) -> Cst {
+        let end = self.attach();
+        while let Some((open, children)) = self.open.pop() {
+            self.children().push(Node::Group(Group {
+                open,
+                children,
+                close: None,
+            }));
+        }
+        Cst {
+            nodes: self.nodes,
+            end,
+        }
+    }
+    fn children(&mut self) -> &mut Vec<Node> {
+        match self.open.last_mut() {
+            Some((_, children)) => children,
+            None => &mut self.nodes,
+        }
+    }
+    // Trivia up to the first line break trails the previous token, the rest leads the next one
+    fn attach(&mut self) -> Vec<Trivia> {
+        let mut trivia = std::mem::take(&mut self.pending).into_iter();
+        let previous = match self.open.last_mut() {
+            Some((open, children)) => children.last_mut().map(last).or(Some(open)),
+            None => self.nodes.last_mut().map(last),
+        };
+        let mut leading = vec![];
+        if let Some(previous) = previous {
+            for item in trivia.by_ref() {
+                if matches!(&item, Trivia::Whitespace(text) if text.contains('\n')) {
+                    leading.push(item);
+                    break;
+                }
+                previous.trailing.push(item);
+            }
+        }
+        leading.extend(trivia);
+        leading
+    }
+}
+
+fn last(node: &mut Node) -> &mut Token {
+    match node {
+        Node::Token(token) => token,
+        Node::Group(group) => match (&mut group.close, group.children.last_mut()) {
+            (Some(close), _) => close,
+            (None, Some(child)) => last(child),
+            (None, None) => &mut group.open,
+        },
+    }
+}
+
+fn collect<'a>(nodes: &'a [Node], tokens: &mut Vec<&'a Token>) {
+    for node in nodes {
+        match node {
+            Node::Token(token) => tokens.push(token),
+            Node::Group(group) => {
+                tokens.push(&group.open);
+                collect(&group.children, tokens);
+                tokens.extend(&group.close);
+            }
+        }
+    }
+}
+
+fn rename(nodes: &mut [Node], from: &str, to: &str) -> usize {
+    let mut renamed = 0;
+    for node in nodes {
+        match node {
+            Node::Token(token) if token.kind == TokenKind::Ident => {
+                let parts: Vec<&str> = token.text.split('.').collect();
+                let count = parts.iter().filter(|part| **part == from).count();
+                if count > 0 {
+                    let parts = parts
+                        .iter()
+                        .map(|part| if *part == from { to } else { part });
+                    token.text = parts.collect::<Vec<&str>>().join(".");
+                    renamed += count;
+                }
+            }
+            Node::Token(_) => {}
+            Node::Group(group) => renamed += rename(&mut group.children, from, to),
+        }
+    }
+    renamed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lossless() {
+        let inputs = [
+            "# active users\n.users where { active } {\n  name, // shown\n\n  /* later */ email\n}\n",
+            ".users {\n  \"unterminated",
+            "  .users(id: 1) { ?? name } )\n\n",
+            "",
+        ];
+        for input in inputs {
+            assert_eq!(Cst::parse(input).to_string(), input);
+        }
+        let input = ".users /*/ c */ { id }";
+        let cst = Cst::parse(input);
+        assert_eq!(cst.to_string(), input);
+        assert!(cst
+            .tokens()
+            .iter()
+            .all(|token| token.kind != TokenKind::Illegal));
+        assert_eq!(
+            cst.tokens()[1].trailing,
+            [
+                Trivia::Whitespace(" ".into()),
+                Trivia::Comment("/*/ c */".into()),
+                Trivia::Whitespace(" ".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trivia() {
+        let cst = Cst::parse("# users\n.users { name // shown\n}");
+        let tokens = cst.tokens();
+        assert_eq!(
+            tokens[0].leading,
+            [
+                Trivia::Comment("# users".into()),
+                Trivia::Whitespace("\n".into())
+            ]
+        );
+        let name = tokens.iter().find(|token| token.text == "name").unwrap();
+        assert_eq!(
+            name.trailing,
+            [
+                Trivia::Whitespace(" ".into()),
+                Trivia::Comment("// shown".into())
+            ]
+        );
+        let Node::Group(block) = &cst.nodes[2] else {
+            unreachable!();
+        };
+        assert_eq!(
+            block.close.as_ref().unwrap().leading,
+            [Trivia::Whitespace("\n".into())]
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut cst = Cst::parse(
+            ".users u { u.name, /* name */ email } order by name\n.posts { title: \"name\" }",
+        );
+        assert_eq!(cst.rename("name", "full_name"), 2);
+        assert_eq!(
+            cst.to_string(),
+            ".users u { u.full_name, /* name */ email } order by full_name\n.posts { title: \"name\" }"
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use crate::Placeholder;
+
+pub trait Dialect {
+    fn quote(&self, identifier: &str) -> String {
+        format!("\"{}\"", identifier.replace('"', "\"\""))
+    }
+    fn placeholder(&self) -> Placeholder {
+        Placeholder::Question
+    }
+    fn string(&self, value: &str) -> String {
+        string(value)
+    }
+    fn reserved(&self) -> &[&str] {
+        &[]
+    }
+    fn paginate(&self, limit: Option<&str>, offset: Option<&str>, _ordered: bool) -> Vec<String> {
+        limit_offset(limit, offset)
+    }
+    fn ilike(&self, operand: &str, pattern: &str) -> String {
+        format!("LOWER({}) LIKE LOWER({})", operand, pattern)
+    }
+    fn begin(&self) -> &str {
+        "BEGIN"
+    }
+    fn distinct_on(&self, _columns: &str) -> Option<String> {
+        None
+    }
+    /// A date, or a timestamp when it has a time of day, already validated
+    fn date(&self, value: &str, time: bool) -> String {
+        let kind = if time { "TIMESTAMP" } else { "DATE" };
+        format!("{} {}", kind, string(value))
+    }
+    /// Adds a signed amount of a singular unit like day to a date or timestamp
+    fn interval(&self, operand: &str, amount: i64, unit: &str) -> String {
+        let plural = if amount.abs() == 1 { "" } else { "s" };
+        let operator = if amount < 0 { "-" } else { "+" };
+        format!(
+            "{} {} INTERVAL '{} {}{}'",
+            operand,
+            operator,
+            amount.abs(),
+            unit,
+            plural
+        )
+    }
+    /// The clause at the end of a mutation that hands back the rows it changed
+    fn returning(&self, columns: &[String]) -> Option<String> {
+        Some(format!("RETURNING {}", columns.join(", ")))
+    }
+    /// Returned columns for dialects that list them before VALUES or WHERE instead
+    fn output(&self, _columns: &[String], _deleted: bool) -> Option<String> {
+        None
+    }
+    /// What an insert that sets no columns writes in place of its columns and values
+    fn default_values(&self) -> &str {
+        "DEFAULT VALUES"
+    }
+    /// The clause after an insert's values that updates the row it collides with
+    fn upsert(&self, conflict: &str, columns: &[String]) -> Option<String> {
+        let assignments: Vec<String> = columns
+            .iter()
+            .map(|column| format!("{} = EXCLUDED.{}", column, column))
+            .collect();
+        Some(format!(
+            "ON CONFLICT ({}) DO UPDATE SET {}",
+            conflict,
+            assignments.join(", ")
+        ))
+    }
+    /// A member of a union with its own ORDER BY, LIMIT or OFFSET, or `None` when the dialect
+    /// can't order one without limiting it
+    fn union_member(&self, query: &str, _limited: bool) -> Option<String> {
+        Some(format!("({})", query))
+    }
+    fn explain(&self, analyze: bool) -> Option<&str> {
+        Some(if analyze {
+            "EXPLAIN ANALYZE"
+        } else {
+            "EXPLAIN"
+        })
+    }
+    fn identifier(&self, identifier: &str) -> String {
+        identifier
+            .split('.')
+            .map(|part| {
+                if needs_quoting(part) || self.is_reserved(part) {
+                    self.quote(part)
+                } else {
+                    part.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+    fn is_reserved(&self, identifier: &str) -> bool {
+        let identifier = identifier.to_lowercase();
+        RESERVED.contains(&identifier.as_str()) || self.reserved().contains(&identifier.as_str())
+    }
+}
+
+const RESERVED: [&str; 52] = [
+    "all",
+    "alter",
+    "and",
+    "any",
+    "as",
+    "asc",
+    "between",
+    "by",
+    "case",
+    "check",
+    "column",
+    "constraint",
+    "create",
+    "cross",
+    "default",
+    "delete",
+    "desc",
+    "distinct",
+    "drop",
+    "else",
+    "end",
+    "exists",
+    "foreign",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "in",
+    "inner",
+    "insert",
+    "into",
+    "is",
+    "join",
+    "left",
+    "like",
+    "not",
+    "null",
+    "on",
+    "or",
+    "order",
+    "primary",
+    "references",
+    "right",
+    "select",
+    "set",
+    "table",
+    "then",
+    "union",
+    "update",
+    "values",
+    "where",
+];
+
+fn limit_offset(limit: Option<&str>, offset: Option<&str>) -> Vec<String> {
+    let mut clauses = vec![];
+    if let Some(limit) = limit {
+        clauses.push(format!("LIMIT {}", limit));
+    }
+    if let Some(offset) = offset {
+        clauses.push(format!("OFFSET {}", offset));
+    }
+    clauses
+}
+
+fn needs_quoting(identifier: &str) -> bool {
+    let mut characters = identifier.chars();
+    match characters.next() {
+        Some(first) if first.is_ascii_lowercase() || first == '_' => !characters.all(|character| {
+            character.is_ascii_lowercase() || character.is_ascii_digit() || character == '_'
+        }),
+        _ => true,
+    }
+}
+
+pub const NAMES: [&str; 4] = ["postgres", "mysql", "sqlite", "mssql"];
+
+pub fn from_name(name: &str) -> Option<Box<dyn Dialect>> {
+    match name {
+        "postgres" => Some(Box::new(Postgres)),
+        "mysql" => Some(Box::new(MySql)),
+        "sqlite" => Some(Box::new(Sqlite)),
+        "mssql" => Some(Box::new(MsSql)),
+        _ => None,
+    }
+}
+
+pub struct Postgres;
+impl Dialect for Postgres {
+    fn placeholder(&self) -> Placeholder {
+        Placeholder::Dollar
+    }
+    fn ilike(&self, operand: &str, pattern: &str) -> String {
+        format!("{} ILIKE {}", operand, pattern)
+    }
+    fn distinct_on(&self, columns: &str) -> Option<String> {
+        Some(format!("DISTINCT ON ({})", columns))
+    }
+    fn reserved(&self) -> &[&str] {
+        &[
+            "analyse",
+            "analyze",
+            "array",
+            "current_user",
+            "fetch",
+            "limit",
+            "offset",
+            "only",
+            "returning",
+            "user",
+            "window",
+            "with",
+        ]
+    }
+}
+
+pub struct MySql;
+impl Dialect for MySql {
+    fn reserved(&self) -> &[&str] {
+        &[
+            "change",
+            "databases",
+            "div",
+            "index",
+            "interval",
+            "key",
+            "keys",
+            "limit",
+            "lock",
+            "rank",
+            "read",
+            "rows",
+            "show",
+            "window",
+            "with",
+            "write",
+        ]
+    }
+    fn quote(&self, identifier: &str) -> String {
+        format!("`{}`", identifier.replace('`', "``"))
+    }
+    // Backslashes escape in MySQL strings unless NO_BACKSLASH_ESCAPES is set
+    fn string(&self, value: &str) -> String {
+        string(&value.replace('\\', "\\\\"))
+    }
+    fn returning(&self, _columns: &[String]) -> Option<String> {
+        None
+    }
+    fn default_values(&self) -> &str {
+        "() VALUES ()"
+    }
+    fn interval(&self, operand: &str, amount: i64, unit: &str) -> String {
+        let operator = if amount < 0 { "-" } else { "+" };
+        format!(
+            "{} {} INTERVAL {} {}",
+            operand,
+            operator,
+            amount.abs(),
+            unit.to_uppercase()
+        )
+    }
+    // Any unique key can collide, so the conflict columns aren't written
+    fn upsert(&self, _conflict: &str, columns: &[String]) -> Option<String> {
+        let assignments: Vec<String> = columns
+            .iter()
+            .map(|column| format!("{} = VALUES({})", column, column))
+            .collect();
+        Some(format!(
+            "ON DUPLICATE KEY UPDATE {}",
+            assignments.join(", ")
+        ))
+    }
+    fn paginate(&self, limit: Option<&str>, offset: Option<&str>, _ordered: bool) -> Vec<String> {
+        // MySQL has no OFFSET without a LIMIT, the documented workaround is the largest row count
+        let limit = limit.or(offset.map(|_| "18446744073709551615"));
+        limit_offset(limit, offset)
+    }
+}
+
+pub struct Sqlite;
+impl Dialect for Sqlite {
+    fn reserved(&self) -> &[&str] {
+        &[
+            "autoincrement",
+            "collate",
+            "glob",
+            "index",
+            "indexed",
+            "isnull",
+            "limit",
+            "notnull",
+            "offset",
+            "pragma",
+            "raise",
+            "regexp",
+            "vacuum",
+        ]
+    }
+    fn paginate(&self, limit: Option<&str>, offset: Option<&str>, _ordered: bool) -> Vec<String> {
+        // SQLite also requires a LIMIT before OFFSET, a negative one means no limit
+        let limit = limit.or(offset.map(|_| "-1"));
+        limit_offset(limit, offset)
+    }
+    fn explain(&self, analyze: bool) -> Option<&str> {
+        (!analyze).then_some("EXPLAIN QUERY PLAN")
+    }
+    // Compound members can't be parenthesized, a subquery can
+    fn union_member(&self, query: &str, _limited: bool) -> Option<String> {
+        Some(format!("SELECT * FROM ({})", query))
+    }
+    // Dates are text, the date functions both normalize them and do the arithmetic
+    fn date(&self, value: &str, time: bool) -> String {
+        let function = if time { "DATETIME" } else { "DATE" };
+        format!("{}({})", function, string(value))
+    }
+    fn interval(&self, operand: &str, amount: i64, unit: &str) -> String {
+        let (amount, unit) = match unit {
+            "week" => (amount * 7, "day"),
+            unit => (amount, unit),
+        };
+        format!("DATETIME({}, '{:+} {}s')", operand, amount, unit)
+    }
+}
+
+pub struct MsSql;
+impl Dialect for MsSql {
+    fn reserved(&self) -> &[&str] {
+        &[
+            "backup", "browse", "file", "identity", "key", "open", "percent", "plan", "proc",
+            "rowcount", "top", "tran", "user",
+        ]
+    }
+    fn quote(&self, identifier: &str) -> String {
+        format!("[{}]", identifier.replace(']', "]]"))
+    }
+    fn begin(&self) -> &str {
+        "BEGIN TRANSACTION"
+    }
+    fn explain(&self, _analyze: bool) -> Option<&str> {
+        // Plans are a session setting (SET SHOWPLAN_TEXT ON), not a statement prefix
+        None
+    }
+    fn upsert(&self, _conflict: &str, _columns: &[String]) -> Option<String> {
+        // Only MERGE can do this, which doesn't fit after VALUES
+        None
+    }
+    // ORDER BY is only allowed in a derived table next to OFFSET, which needs an alias
+    fn union_member(&self, query: &str, limited: bool) -> Option<String> {
+        limited.then(|| format!("SELECT * FROM ({}) AS member", query))
+    }
+    fn date(&self, value: &str, time: bool) -> String {
+        let kind = if time { "DATETIME2" } else { "DATE" };
+        format!("CAST({} AS {})", string(value), kind)
+    }
+    fn interval(&self, operand: &str, amount: i64, unit: &str) -> String {
+        format!("DATEADD({}, {}, {})", unit, amount, operand)
+    }
+    fn output(&self, columns: &[String], deleted: bool) -> Option<String> {
+        let table = if deleted { "DELETED" } else { "INSERTED" };
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|column| format!("{}.{}", table, column))
+            .collect();
+        Some(format!("OUTPUT {}", columns.join(", ")))
+    }
+    fn placeholder(&self) -> Placeholder {
+        Placeholder::At
+    }
+    fn paginate(&self, limit: Option<&str>, offset: Option<&str>, ordered: bool) -> Vec<String> {
+        if limit.is_none() && offset.is_none() {
+            return vec![];
+        }
+        // OFFSET ... FETCH is only valid after an ORDER BY
+        let mut clauses = vec![];
+        if !ordered {
+            clauses.push("ORDER BY (SELECT NULL)".to_string());
+        }
+        clauses.push(format!("OFFSET {} ROWS", offset.unwrap_or("0")));
+        if let Some(limit) = limit {
+            clauses.push(format!("FETCH NEXT {} ROWS ONLY", limit));
+        }
+        clauses
+    }
+}
+
+/// A standard SQL string literal, quotes inside are doubled
+pub fn string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(Postgres.identifier("user data"), "\"user data\"");
+        assert_eq!(MySql.identifier("user data"), "`user data`");
+        assert_eq!(Sqlite.identifier("user data"), "\"user data\"");
+        assert_eq!(MsSql.identifier("user data"), "[user data]");
+        assert_eq!(Postgres.identifier("u.name"), "u.name");
+        assert_eq!(Postgres.identifier("u.café"), "u.\"café\"");
+        assert_eq!(Postgres.identifier("u.order"), "u.\"order\"");
+        assert_eq!(Postgres.identifier("user"), "\"user\"");
+        assert_eq!(MySql.identifier("user"), "user");
+        assert_eq!(MsSql.identifier("Top"), "[Top]");
+        assert_eq!(Postgres.identifier("up.firstName"), "up.\"firstName\"");
+        assert_eq!(Postgres.identifier("user_2"), "user_2");
+    }
+
+    #[test]
+    fn test_string() {
+        assert_eq!(Postgres.string("O'Brien"), "'O''Brien'");
+        assert_eq!(
+            Sqlite.string("'; DROP TABLE users; --"),
+            "'''; DROP TABLE users; --'"
+        );
+        assert_eq!(MsSql.string("''"), "''''''");
+        assert_eq!(Postgres.string("C:\\temp\\"), "'C:\\temp\\'");
+        assert_eq!(MySql.string("\\'; --"), "'\\\\''; --'");
+        assert_eq!(MySql.string("\\"), "'\\\\'");
+    }
+
+    #[test]
+    fn test_paginate() {
+        assert_eq!(
+            Postgres.paginate(Some("10"), Some("20"), false),
+            ["LIMIT 10", "OFFSET 20"]
+        );
+        assert_eq!(
+            MySql.paginate(None, Some("20"), false),
+            ["LIMIT 18446744073709551615", "OFFSET 20"]
+        );
+        assert_eq!(
+            Sqlite.paginate(None, Some("20"), false),
+            ["LIMIT -1", "OFFSET 20"]
+        );
+        assert_eq!(
+            MsSql.paginate(Some("10"), None, true),
+            ["OFFSET 0 ROWS", "FETCH NEXT 10 ROWS ONLY"]
+        );
+        assert_eq!(
+            MsSql.paginate(Some("10"), Some("20"), false),
+            [
+                "ORDER BY (SELECT NULL)",
+                "OFFSET 20 ROWS",
+                "FETCH NEXT 10 ROWS ONLY"
+            ]
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use crate::arena::Arena;
+use crate::json::Json;
+use crate::{
+    Context, DeleteStatement, DotStatement, PairStatement, Program, Result, Stmt, TokenKind,
+    UpdateStatement, WildcardStatement,
+};
+
+pub fn compile(program: &Program, context: &mut Context) -> Result<String> {
+    let program = program.cased(context);
+    program.check(context)?;
+    let requests = program
+        .statements
+        .iter()
+        .zip(&program.spans)
+        .map(|(statement, span)| {
+            request(&program.arena, statement, context).map_err(|error| error.at(*span))
+        })
+        .collect::<Result<Vec<String>>>()?;
+    Ok(requests.join("\n\n"))
+}
+
+fn request(arena: &Arena, statement: &Stmt, context: &Context) -> Result<String> {
+    let (method, path, body) = match statement {
+        Stmt::Dot(dot) => (
+            "GET",
+            format!("{}/_search", dot.ident.literal),
+            search(arena, dot, context)?,
+        ),
+        Stmt::Insert(insert) if insert.conflict.is_some() || insert.returning.is_some() => {
+            return Err(error!(
+                Eval,
+                "on conflict and returning are not supported by the elasticsearch target"
+            ))
+        }
+        Stmt::Insert(insert) => (
+            "POST",
+            format!("{}/_doc", insert.ident.literal),
+            document(arena, &insert.values, context)?,
+        ),
+        Stmt::Update(UpdateStatement {
+            returning: Some(_), ..
+        })
+        | Stmt::Delete(DeleteStatement {
+            returning: Some(_), ..
+        }) => {
+            return Err(error!(
+                Eval,
+                "on conflict and returning are not supported by the elasticsearch target"
+            ))
+        }
+        Stmt::Update(update) => {
+            let assignments: Vec<String> = update
+                .values
+                .iter()
+                .map(|pair| format!("ctx._source.{0} = params.{0}", pair.ident.literal))
+                .collect();
+            let script = Json::object([
+                ("source", assignments.join("; ").into()),
+                ("params", document(arena, &update.values, context)?),
+            ]);
+            (
+                "POST",
+                format!("{}/_update_by_query", update.ident.literal),
+                Json::object([
+                    ("query", query(arena, &update.filters, None, context)?),
+                    ("script", script),
+                ]),
+            )
+        }
+        Stmt::Delete(delete) => {
+            if delete.is_unfiltered() && !context.allow_full_delete {
+                return Err(error!(
+                    Eval,
+                    "refusing to delete every row from {}, pass --allow-full-delete",
+                    delete.ident.literal
+                ));
+            }
+            (
+                "POST",
+                format!("{}/_delete_by_query", delete.ident.literal),
+                Json::object([(
+                    "query",
+                    query(
+                        arena,
+                        &delete.filters,
+                        delete.condition.map(|condition| &arena[condition]),
+                        context,
+                    )?,
+                )]),
+            )
+        }
+        statement => return Err(unsupported(statement)),
+    };
+    Ok(format!("{} /{}\n{:#}", method, path, body))
+}
+
+fn search(arena: &Arena, dot: &DotStatement, context: &Context) -> Result<Json> {
+    if dot.alias.is_some()
+        || !dot.joins.is_empty()
+        || dot.within.is_some()
+        || dot.distinct
+        || !dot.grouping.is_empty()
+    {
+        return Err(error!(
+            Eval,
+            "aliases, joins, in, distinct and group by are not supported by the elasticsearch target"
+        ));
+    }
+    let mut body = vec![(
+        "query".to_string(),
+        query(
+            arena,
+            &dot.filters,
+            dot.condition.map(|condition| &arena[condition]),
+            context,
+        )?,
+    )];
+    if let Some(source) = source(&arena[dot.block.properties])? {
+        body.push(("_source".to_string(), source));
+    }
+    if !dot.ordering.is_empty() {
+        let sort = dot.ordering.iter().map(|order| {
+            let direction = match order.direction {
+                Some(TokenKind::Desc) => "desc",
+                _ => "asc",
+            };
+            Json::Object(vec![(order.ident.literal.to_string(), direction.into())])
+        });
+        body.push(("sort".to_string(), Json::Array(sort.collect())));
+    }
+    if let Some(offset) = &dot.offset {
+        body.push(("from".to_string(), number(&offset.literal)));
+    }
+    if let Some(limit) = &dot.limit {
+        body.push(("size".to_string(), number(&limit.literal)));
+    }
+    Ok(Json::Object(body))
+}
+
+fn source(properties: &[Stmt]) -> Result<Option<Json>> {
+    let mut includes = vec![];
+    let mut excludes = vec![];
+    for property in properties {
+        match property {
+            Stmt::Identifier(identifier) => includes.push(identifier.literal.as_str().into()),
+            Stmt::Wildcard(WildcardStatement { exclude }) => {
+                excludes.extend(exclude.iter().map(|ident| ident.literal.as_str().into()));
+                includes.push("*".into());
+            }
+            property => return Err(unsupported(property)),
+        }
+    }
+    Ok(match (includes.as_slice(), excludes.is_empty()) {
+        ([], true) => None,
+        ([star], true) if *star == Json::from("*") => None,
+        (_, true) => Some(Json::Array(includes)),
+        (_, false) => Some(Json::object([
+            ("includes", Json::Array(includes)),
+            ("excludes", Json::Array(excludes)),
+        ])),
+    })
+}
+
+fn query(
+    arena: &Arena,
+    filters: &[PairStatement],
+    condition: Option<&Stmt>,
+    context: &Context,
+) -> Result<Json> {
+    let mut clauses = filters
+        .iter()
+        .map(|pair| {
+            Ok(term(
+                &pair.ident.literal,
+                value(&arena[pair.value], context)?,
+            ))
+        })
+        .collect::<Result<Vec<Json>>>()?;
+    if let Some(condition) = condition {
+        let condition = predicate(arena, condition, context)?;
+        match nested(&condition, "filter") {
+            Some(nested) => clauses.extend(nested.iter().cloned()),
+            None => clauses.push(condition),
+        }
+    }
+    Ok(match clauses.len() {
+        0 => Json::object([("match_all", Json::object([]))]),
+        1 => clauses.remove(0),
+        _ => boolean("filter", clauses),
+    })
+}
+
+fn predicate(arena: &Arena, statement: &Stmt, context: &Context) -> Result<Json> {
+    let column = |operand: &Stmt| match operand {
+        Stmt::Identifier(identifier) => Ok(identifier.literal.to_string()),
+        _ => Err(unsupported(statement)),
+    };
+    match statement {
+        Stmt::Identifier(identifier) => Ok(term(&identifier.literal, Json::Bool(true))),
+        Stmt::Prefix(prefix) if prefix.operator == TokenKind::Not => Ok(boolean(
+            "must_not",
+            vec![predicate(arena, &arena[prefix.right], context)?],
+        )),
+        Stmt::Infix(infix) if matches!(infix.operator, TokenKind::And | TokenKind::Or) => {
+            let occurrence = match infix.operator {
+                TokenKind::And => "filter",
+                _ => "should",
+            };
+            let mut clauses = vec![];
+            for side in [infix.left, infix.right] {
+                let clause = predicate(arena, &arena[side], context)?;
+                match nested(&clause, occurrence) {
+                    Some(nested) => clauses.extend(nested.iter().cloned()),
+                    None => clauses.push(clause),
+                }
+            }
+            Ok(boolean(occurrence, clauses))
+        }
+        Stmt::Infix(infix) => {
+            let (name, operator, operand) = match (&arena[infix.left], &arena[infix.right]) {
+                (Stmt::Identifier(name), operand) => (name, infix.operator, operand),
+                (operand, Stmt::Identifier(name)) => (name, flip(infix.operator), operand),
+                _ => return Err(unsupported(statement)),
+            };
+            let value = value(operand, context)?;
+            let bound = match operator {
+                TokenKind::Eq => return Ok(term(&name.literal, value)),
+                TokenKind::NotEq => {
+                    return Ok(boolean("must_not", vec![term(&name.literal, value)]))
+                }
+                TokenKind::Lt => "lt",
+                TokenKind::LtEq => "lte",
+                TokenKind::Gt => "gt",
+                TokenKind::GtEq => "gte",
+                _ => return Err(unsupported(statement)),
+            };
+            Ok(range(&name.literal, vec![(bound, value)]))
+        }
+        Stmt::IsNull(is_null) => {
+            let exists = Json::object([(
+                "exists",
+                Json::object([("field", column(&arena[is_null.operand])?.into())]),
+            )]);
+            Ok(match is_null.negated {
+                true => exists,
+                false => boolean("must_not", vec![exists]),
+            })
+        }
+        Stmt::In(in_expression) => {
+            let values = arena[in_expression.values]
+                .iter()
+                .map(|item| value(item, context))
+                .collect::<Result<Vec<Json>>>()?;
+            Ok(Json::object([(
+                "terms",
+                Json::Object(vec![(
+                    column(&arena[in_expression.operand])?,
+                    Json::Array(values),
+                )]),
+            )]))
+        }
+        Stmt::Between(between) => Ok(range(
+            &column(&arena[between.operand])?,
+            vec![
+                ("gte", value(&arena[between.low], context)?),
+                ("lte", value(&arena[between.high], context)?),
+            ],
+        )),
+        Stmt::Like(like) => {
+            let Json::String(pattern) = value(&arena[like.pattern], context)? else {
+                return Err(unsupported(statement));
+            };
+            let mut wildcard = vec![("value".to_string(), wildcard(&pattern).into())];
+            if like.insensitive {
+                wildcard.push(("case_insensitive".to_string(), true.into()));
+            }
+            Ok(Json::object([(
+                "wildcard",
+                Json::Object(vec![(
+                    column(&arena[like.operand])?,
+                    Json::Object(wildcard),
+                )]),
+            )]))
+        }
+        statement => Err(unsupported(statement)),
+    }
+}
+
+fn value(statement: &Stmt, context: &Context) -> Result<Json> {
+    match statement {
+        Stmt::Literal(literal) if literal.kind == TokenKind::Number => Ok(number(&literal.literal)),
+        Stmt::Literal(literal) => Ok(Json::String(literal.literal.clone())),
+        Stmt::Variable(variable) => match context.variables.get(&variable.name) {
+            Some(value @ (Json::Number(_) | Json::String(_))) => Ok(value.clone()),
+            Some(_) => Err(error!(
+                Eval,
+                "variable ${} must be a number or a string", variable.name
+            )),
+            None => Err(error!(Eval, "unbound variable ${}", variable.name)),
+        },
+        Stmt::Env(env) => Ok(Json::String(env.resolve(context)?)),
+        statement => Err(unsupported(statement)),
+    }
+}
+
+fn document(arena: &Arena, pairs: &[PairStatement], context: &Context) -> Result<Json> {
+    let fields = pairs
+        .iter()
+        .map(|pair| {
+            let value = value(&arena[pair.value], context)?;
+            Ok((pair.ident.literal.to_string(), value))
+        })
+        .collect::<Result<Vec<(String, Json)>>>()?;
+    Ok(Json::Object(fields))
+}
+
+fn term(name: &str, value: Json) -> Json {
+    Json::object([("term", Json::Object(vec![(name.to_string(), value)]))])
+}
+
+fn range(name: &str, bounds: Vec<(&str, Json)>) -> Json {
+    let bounds = bounds
+        .into_iter()
+        .map(|(bound, value)| (bound.to_string(), value));
+    Json::object([(
+        "range",
+        Json::Object(vec![(name.to_string(), Json::Object(bounds.collect()))]),
+    )])
+}
+
+fn boolean(occurrence: &str, clauses: Vec<Json>) -> Json {
+    let mut fields = vec![(occurrence.to_string(), Json::Array(clauses))];
+    if occurrence == "should" {
+        fields.push(("minimum_should_match".to_string(), number("1")));
+    }
+    Json::object([("bool", Json::Object(fields))])
+}
+
+// The clauses of a bool query with nothing but this occurrence, so chains of and/or stay flat
+fn nested<'a>(clause: &'a Json, occurrence: &str) -> Option<&'a Vec<Json>> {
+    let Some(Json::Object(fields)) = clause.get("bool") else {
+        return None;
+    };
+    let extra = if occurrence == "should" { 2 } else { 1 };
+    match fields.first() {
+        Some((key, Json::Array(clauses))) if key == occurrence && fields.len() == extra => {
+            Some(clauses)
+        }
+        _ => None,
+    }
+}
+
+fn flip(operator: TokenKind) -> TokenKind {
+    match operator {
+        TokenKind::Lt => TokenKind::Gt,
+        TokenKind::LtEq => TokenKind::GtEq,
+        TokenKind::Gt => TokenKind::Lt,
+        TokenKind::GtEq => TokenKind::LtEq,
+        operator => operator,
+    }
+}
+
+fn number(value: &str) -> Json {
+    Json::Number(value.to_string())
+}
+
+fn wildcard(pattern: &str) -> String {
+    let mut wildcard = String::new();
+    for character in pattern.chars() {
+        match character {
+            '%' => wildcard.push('*'),
+            '_' => wildcard.push('?'),
+            '*' | '?' | '\\' => {
+                wildcard.push('\\');
+                wildcard.push(character);
+            }
+            character => wildcard.push(character),
+        }
+    }
+    wildcard
+}
+
+fn unsupported(statement: &Stmt) -> crate::CompileError {
+    error!(
+        Eval,
+        "{} are not supported by the elasticsearch target",
+        statement.noun()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elasticsearch(input: &str) -> String {
+        compile(&Program::new(input).unwrap(), &mut Context::default()).unwrap()
+    }
+
+    #[test]
+    fn test_elasticsearch() {
+        let search = elasticsearch(
+            r#".users(org: 2) where { age >= 18 and (name like "a%" or deleted_at is null) } { name, email } order by name desc limit 10"#,
+        );
+        let expected = r#"GET /users/_search
+{"query":{"bool":{"filter":[{"term":{"org":2}},{"range":{"age":{"gte":18}}},{"bool":{"should":[{"wildcard":{"name":{"value":"a*"}}},{"bool":{"must_not":[{"exists":{"field":"deleted_at"}}]}}],"minimum_should_match":1}}]}},"_source":["name","email"],"sort":[{"name":"desc"}],"size":10}"#;
+        let (request, body) = search.split_once('\n').unwrap();
+        let (expected_request, expected_body) = expected.split_once('\n').unwrap();
+        assert_eq!(request, expected_request);
+        assert_eq!(
+            Json::parse(body).unwrap(),
+            Json::parse(expected_body).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_elasticsearch_mutations() {
+        assert_eq!(
+            elasticsearch("~users(id: 1) { name: \"Ada\" } -users(id: 1)"),
+            "POST /users/_update_by_query\n{\n  \"query\": {\n    \"term\": {\n      \"id\": 1\n    }\n  },\n  \"script\": {\n    \"source\": \"ctx._source.name = params.name\",\n    \"params\": {\n      \"name\": \"Ada\"\n    }\n  }\n}\n\nPOST /users/_delete_by_query\n{\n  \"query\": {\n    \"term\": {\n      \"id\": 1\n    }\n  }\n}"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use crate::{comment_length, Lexer, Program, Result, TokenKind};
+use std::vec::IntoIter;
+
+const WIDTH: usize = 80;
+
+pub fn format(input: &str) -> Result<String> {
+    let program = Program::new(input)?;
+    let mut pieces = pieces(input)?.into_iter();
+    let mut nodes = vec![];
+    while let Some(piece) = pieces.next() {
+        nodes.push(node(piece, &mut pieces));
+    }
+    let mut printer = Printer::default();
+    printer.top(&nodes);
+    let output = printer.output;
+    if Program::new(&output)?.ast() != program.ast() {
+        return Err(error!(
+            Parse,
+            "formatting would change the meaning of the query"
+        ));
+    }
+    Ok(output)
+}
+
+struct Piece<'a> {
+    kind: Option<TokenKind>,
+    text: &'a str,
+    trailing: bool,
+    blank: bool,
+}
+
+enum Node<'a> {
+    Token(TokenKind, &'a str, bool),
+    Comment(&'a str, bool, bool),
+    Group(TokenKind, Vec<Item<'a>>),
+}
+
+#[derive(Default)]
+struct Item<'a> {
+    leading: Vec<&'a str>,
+    nodes: Vec<Node<'a>>,
+    trailing: Vec<&'a str>,
+}
+
+fn pieces(input: &str) -> Result<Vec<Piece<'_>>> {
+    let mut lexer = Lexer::new(input);
+    let mut pieces = vec![];
+    let mut end = 0;
+    loop {
+        let token = lexer.next_token()?;
+        let span = lexer.span();
+        let newlines = comments(&input[end..span.start], end == 0, &mut pieces);
+        if token.kind == TokenKind::Eof {
+            return Ok(pieces);
+        }
+        pieces.push(Piece {
+            kind: Some(token.kind),
+            text: &input[span.start..span.end],
+            trailing: false,
+            blank: newlines > 1,
+        });
+        end = span.end;
+    }
+}
+
+fn comments<'a>(gap: &'a str, first: bool, pieces: &mut Vec<Piece<'a>>) -> usize {
+    let mut rest = gap;
+    let mut newlines = 0;
+    let mut first = first;
+    loop {
+        let trimmed = rest.trim_start();
+        newlines += rest[..rest.len() - trimmed.len()].matches('\n').count();
+        rest = trimmed;
+        let Some(end) = comment_length(rest) else {
+            return newlines;
+        };
+        pieces.push(Piece {
+            kind: None,
+            text: rest[..end].trim_end(),
+            trailing: !first && newlines == 0,
+            blank: newlines > 1,
+        });
+        rest = &rest[end..];
+        newlines = 0;
+        first = false;
+    }
+}
+
+fn node<'a>(piece: Piece<'a>, pieces: &mut IntoIter<Piece<'a>>) -> Node<'a> {
+    match piece.kind {
+        Some(TokenKind::LBrace) => Node::Group(TokenKind::LBrace, group(pieces, TokenKind::RBrace)),
+        Some(TokenKind::LParen) => Node::Group(TokenKind::LParen, group(pieces, TokenKind::RParen)),
+        Some(kind) => Node::Token(kind, piece.text, piece.blank),
+        None => Node::Comment(piece.text, piece.trailing, piece.blank),
+    }
+}
+
+fn group<'a>(pieces: &mut IntoIter<Piece<'a>>, close: TokenKind) -> Vec<Item<'a>> {
+    let mut items = vec![];
+    let mut item = Item::default();
+    while let Some(piece) = pieces.next() {
+        let empty = item.nodes.is_empty() && item.leading.is_empty();
+        match piece.kind {
+            Some(kind) if kind == close => break,
+            Some(TokenKind::Comma) => push(&mut items, std::mem::take(&mut item)),
+            None if piece.trailing && empty && !items.is_empty() => {
+                items.last_mut().unwrap().trailing.push(piece.text)
+            }
+            None if item.nodes.is_empty() => item.leading.push(piece.text),
+            _ => item.nodes.push(node(piece, pieces)),
+        }
+    }
+    push(&mut items, item);
+    items
+}
+
+// Comments after the last token of an item stay on its line, or move below it when on their own line
+fn push<'a>(items: &mut Vec<Item<'a>>, mut item: Item<'a>) {
+    let mut end = item.nodes.len();
+    while end > 0 && matches!(item.nodes[end - 1], Node::Comment(..)) {
+        end -= 1;
+    }
+    let mut after = Item::default();
+    for node in item.nodes.split_off(end) {
+        if let Node::Comment(text, trailing, _) = node {
+            if trailing && after.leading.is_empty() {
+                item.trailing.push(text);
+            } else {
+                after.leading.push(text);
+            }
+        }
+    }
+    if !item.nodes.is_empty() || !item.leading.is_empty() {
+        items.push(item);
+    }
+    if !after.leading.is_empty() {
+        items.push(after);
+    }
+}
+
+fn space(previous: &Node, next: &Node) -> bool {
+    // `distinct on (...)`, `on conflict (...)` and `.users delete (...)` read as clauses, not calls
+    let clause = matches!(
+        previous,
+        Node::Token(TokenKind::Ident, "on" | "conflict" | "delete", _)
+    );
+    // `date"2024-01-01"` reads as one literal, `- interval "7 days"` as arithmetic
+    let literal = matches!(
+        previous,
+        Node::Token(TokenKind::Ident, "date" | "timestamp", _)
+    );
+    let previous = match previous {
+        Node::Token(kind, ..) => Some(*kind),
+        _ => None,
+    };
+    match next {
+        Node::Token(TokenKind::Colon | TokenKind::Semicolon, ..) => false,
+        Node::Token(TokenKind::String, ..) if literal => false,
+        Node::Token(TokenKind::Ident, "interval", _) => true,
+        Node::Group(TokenKind::LParen, _) => {
+            clause
+                || !matches!(
+                    previous,
+                    Some(TokenKind::Ident | TokenKind::Directive | TokenKind::Over)
+                )
+        }
+        _ => !matches!(
+            previous,
+            Some(
+                TokenKind::Dot
+                    | TokenKind::Plus
+                    | TokenKind::Tilde
+                    | TokenKind::Minus
+                    | TokenKind::Spread
+                    | TokenKind::Bang
+            )
+        ),
+    }
+}
+
+fn starts_statement(previous: &Node, next: &Node) -> bool {
+    // Outside of blocks a `query name:` header is the only place a bare name or colon can be
+    let statement = matches!(
+        next,
+        Node::Token(
+            TokenKind::Dot
+                | TokenKind::Plus
+                | TokenKind::Tilde
+                | TokenKind::Minus
+                | TokenKind::Let
+                | TokenKind::Fragment,
+            ..
+        ) | Node::Token(TokenKind::Ident, "query", _)
+    );
+    statement
+        && !matches!(
+            previous,
+            Node::Token(
+                TokenKind::Pipe
+                    | TokenKind::Union
+                    | TokenKind::All
+                    | TokenKind::Eq
+                    | TokenKind::Colon,
+                ..
+            ) | Node::Token(TokenKind::Ident, "join" | "query", _)
+        )
+}
+
+fn is_line_comment(text: &str) -> bool {
+    !text.starts_with("/*")
+}
+
+fn flat(node: &Node) -> Option<String> {
+    match node {
+        Node::Token(_, text, _) => Some(text.to_string()),
+        Node::Comment(..) => None,
+        Node::Group(open, items) => {
+            let mut inner = vec![];
+            for item in items {
+                if !item.leading.is_empty() || !item.trailing.is_empty() {
+                    return None;
+                }
+                inner.push(flat_nodes(&item.nodes)?);
+            }
+            let inner = inner.join(", ");
+            Some(match open {
+                TokenKind::LParen => format!("({})", inner),
+                _ if inner.is_empty() => "{}".to_string(),
+                _ => format!("{{ {} }}", inner),
+            })
+        }
+    }
+}
+
+fn flat_nodes(nodes: &[Node]) -> Option<String> {
+    let mut output = String::new();
+    for (index, node) in nodes.iter().enumerate() {
+        if index > 0 && space(&nodes[index - 1], node) {
+            output.push(' ');
+        }
+        output.push_str(&flat(node)?);
+    }
+    Some(output)
+}
+
+#[derive(Default)]
+struct Printer {
+    output: String,
+    indent: usize,
+}
+impl Printer {
+    fn column(&self) -> usize {
+        let line = self.output.rsplit('\n').next().unwrap_or("");
+        line.chars().count()
+    }
+    fn newline(&mut self) {
+        let end = self.output.trim_end_matches(' ').len();
+        self.output.truncate(end);
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+        self.output.push_str(&"  ".repeat(self.indent));
+    }
+    fn blank(&mut self) {
+        self.newline();
+        if !self.output.trim().is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+    fn comment(&mut self, text: &str) {
+        self.output.push_str(text);
+        if is_line_comment(text) {
+            self.newline();
+        }
+    }
+    fn top(&mut self, nodes: &[Node]) {
+        for (index, node) in nodes.iter().enumerate() {
+            if index > 0 {
+                let previous = &nodes[index - 1];
+                let blank = matches!(node, Node::Token(.., true) | Node::Comment(_, _, true));
+                let own_line = matches!(node, Node::Comment(_, false, _));
+                if starts_statement(previous, node) || own_line {
+                    if blank {
+                        self.blank();
+                    } else {
+                        self.newline();
+                    }
+                } else if !self.output.ends_with('\n') && space(previous, node) {
+                    self.output.push(' ');
+                }
+            }
+            self.node(node);
+        }
+        self.newline();
+    }
+    fn nodes(&mut self, nodes: &[Node]) {
+        for (index, node) in nodes.iter().enumerate() {
+            let fresh = self
+                .output
+                .ends_with(&format!("\n{}", "  ".repeat(self.indent)));
+            if index > 0 && !fresh && space(&nodes[index - 1], node) {
+                self.output.push(' ');
+            }
+            self.node(node);
+        }
+    }
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Token(_, text, _) => self.output.push_str(text),
+            Node::Comment(text, ..) => self.comment(text),
+            Node::Group(open, items) => {
+                if let Some(flat) = flat(node) {
+                    // Breaking an empty group, like the parentheses of `now()`, only adds lines
+                    let empty = flat == "()" || flat == "{}";
+                    if empty || self.column() + flat.chars().count() <= WIDTH {
+                        self.output.push_str(&flat);
+                        return;
+                    }
+                }
+                let (open, close) = match open {
+                    TokenKind::LParen => ("(", ")"),
+                    _ => ("{", "}"),
+                };
+                self.output.push_str(open);
+                self.indent += 1;
+                let last = items.iter().rposition(|item| !item.nodes.is_empty());
+                for (index, item) in items.iter().enumerate() {
+                    self.newline();
+                    for comment in &item.leading {
+                        self.output.push_str(comment);
+                        self.newline();
+                    }
+                    self.nodes(&item.nodes);
+                    if !item.nodes.is_empty() && Some(index) != last {
+                        self.output.push(',');
+                    }
+                    for comment in &item.trailing {
+                        self.output.push(' ');
+                        self.comment(comment);
+                    }
+                }
+                self.indent -= 1;
+                self.newline();
+                self.output.push_str(close);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format(".users(id:1){name,email,}order by name").unwrap(),
+            ".users(id: 1) { name, email } order by name\n"
+        );
+        let input = "# active users\n.users where {active}{\n  id, // primary key\n  name, email, created_at, updated_at, last_login_at, avatar\n}\n\n\n-users(id:1)";
+        let expected = "# active users\n.users where { active } {\n  id, // primary key\n  name,\n  email,\n  created_at,\n  updated_at,\n  last_login_at,\n  avatar\n}\n\n-users(id: 1)\n";
+        assert_eq!(format(input).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_syntax() {
+        let input = "fragment person{name,email}\nlet actives=.users where{active}{id};\n.users @limit(10){...person,*,!ssn,case{age>=18=>\"adult\",_=>\"minor\"} as bracket,rank() over(order by id)}|.admins{name}";
+        let expected = "fragment person { name, email }\nlet actives = .users where { active } { id };\n.users @limit(10) {\n  ...person,\n  *,\n  !ssn,\n  case { age >= 18 => \"adult\", _ => \"minor\" } as bracket,\n  rank() over(order by id)\n} | .admins { name }\n";
+        assert_eq!(format(input).unwrap(), expected);
+        assert_eq!(
+            format(".events distinct on(user_id){id}\n+users{email:\"a@b.c\"}on conflict(email)update{email}").unwrap(),
+            ".events distinct on (user_id) { id }\n+users { email: \"a@b.c\" } on conflict (email) update { email }\n"
+        );
+        assert_eq!(
+            format(".users left join .orders on{orders.user_id=users.id}{users.name}").unwrap(),
+            ".users left join .orders on { orders.user_id = users.id } { users.name }\n"
+        );
+        assert_eq!(
+            format("query getUsers:.users{name} query getPosts :.posts{title}").unwrap(),
+            "query getUsers: .users { name }\nquery getPosts: .posts { title }\n"
+        );
+        assert_eq!(
+            format(".users where{born<date\"2000-01-31\"+interval\"1 week\"}{name}").unwrap(),
+            ".users where { born < date\"2000-01-31\" + interval \"1 week\" } { name }\n"
+        );
+        assert_eq!(
+            format(".users insert{name:\"Ada\"}\n.users delete(id:1)where{age<18}").unwrap(),
+            ".users insert { name: \"Ada\" }\n.users delete (id: 1) where { age < 18 }\n"
+        );
+        assert_eq!(
+            format(".users /*/ c */ { id }").unwrap(),
+            ".users /*/ c */ { id }\n"
+        );
+        let wide = format!(
+            ".users where {{ {} > now() - interval \"7 days\" and b > 1 }} {{ id }}",
+            "c".repeat(72)
+        );
+        assert!(format(&wide).unwrap().contains("> now() - interval"));
+    }
+}
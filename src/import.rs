@@ -0,0 +1,333 @@
+use crate::{format, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Word,
+    Number,
+    String,
+    Symbol,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: Kind,
+    text: String,
+}
+impl Token {
+    fn is(&self, keyword: &str) -> bool {
+        self.kind == Kind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+    fn is_symbol(&self, symbol: &str) -> bool {
+        self.kind == Kind::Symbol && self.text == symbol
+    }
+}
+
+const KEYWORDS: [&str; 13] = [
+    "and", "or", "not", "is", "null", "in", "between", "like", "ilike", "asc", "desc", "as",
+    "distinct",
+];
+
+const CLAUSES: [&str; 6] = ["from", "where", "group", "order", "limit", "offset"];
+
+pub fn import(sql: &str) -> Result<String> {
+    let tokens = tokenize(sql)?;
+    let mut statements = vec![];
+    for statement in tokens.split(|token| token.is_symbol(";")) {
+        if !statement.is_empty() {
+            statements.push(Select::new(statement).parse()?);
+        }
+    }
+    format::format(&statements.join("\n"))
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut characters = sql.char_indices().peekable();
+    while let Some((start, character)) = characters.next() {
+        let mut take_while = |test: fn(char) -> bool| {
+            let mut end = start + character.len_utf8();
+            while let Some((index, next)) = characters.peek() {
+                if !test(*next) {
+                    break;
+                }
+                end = index + next.len_utf8();
+                characters.next();
+            }
+            sql[start..end].to_string()
+        };
+        let token = match character {
+            _ if character.is_whitespace() => continue,
+            '-' if sql[start..].starts_with("--") => {
+                take_while(|next| next != '\n');
+                continue;
+            }
+            '\'' => {
+                let mut text = String::new();
+                loop {
+                    match characters.next() {
+                        Some((_, '\''))
+                            if characters.peek().is_some_and(|(_, next)| *next == '\'') =>
+                        {
+                            characters.next();
+                            text.push('\'');
+                        }
+                        Some((_, '\'')) => break,
+                        Some((_, next)) => text.push(next),
+                        None => return Err(error!(Lex, "Unterminated string")),
+                    }
+                }
+                Token {
+                    kind: Kind::String,
+                    text,
+                }
+            }
+            '"' => {
+                let text = take_while(|next| next != '"');
+                characters.next();
+                Token {
+                    kind: Kind::Word,
+                    text: text[1..].to_string(),
+                }
+            }
+            _ if character.is_ascii_digit() => Token {
+                kind: Kind::Number,
+                text: take_while(|next| next.is_ascii_digit() || next == '.'),
+            },
+            _ if character.is_alphabetic() || character == '_' => Token {
+                kind: Kind::Word,
+                text: take_while(|next| next.is_alphanumeric() || next == '_'),
+            },
+            '<' | '>' | '!' => Token {
+                kind: Kind::Symbol,
+                text: take_while(|next| next == '=' || next == '>'),
+            },
+            '(' | ')' | ',' | ';' | '*' | '.' | '=' => Token {
+                kind: Kind::Symbol,
+                text: character.to_string(),
+            },
+            _ => return Err(error!(Lex, "Unknown token {}", character)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Select<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+impl<'a> Select<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Self {
+            tokens,
+            position: 0,
+        }
+    }
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+    fn accept(&mut self, keyword: &str) -> bool {
+        let accepted = self.peek().is_some_and(|token| token.is(keyword));
+        if accepted {
+            self.position += 1;
+        }
+        accepted
+    }
+    fn expect(&mut self, keyword: &str) -> Result<()> {
+        match self.next() {
+            Some(token) if token.is(keyword) => Ok(()),
+            Some(token) => Err(error!(
+                Parse,
+                "cannot import {}, expected {}",
+                token.text,
+                keyword.to_uppercase()
+            )),
+            None => Err(error!(
+                Parse,
+                "cannot import, expected {}",
+                keyword.to_uppercase()
+            )),
+        }
+    }
+    // Everything up to the next clause keyword, outside of parentheses
+    fn clause(&mut self) -> &'a [Token] {
+        let start = self.position;
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token.text.as_str() {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ if depth == 0 && CLAUSES.iter().any(|clause| token.is(clause)) => break,
+                _ => {}
+            }
+            self.position += 1;
+        }
+        &self.tokens[start..self.position]
+    }
+    fn parse(mut self) -> Result<String> {
+        self.expect("select")?;
+        let distinct = self.accept("distinct");
+        let columns = self.clause();
+        self.expect("from")?;
+        let mut query = match self.next() {
+            Some(table) if table.kind == Kind::Word => format!(".{}", table.text),
+            _ => return Err(error!(Parse, "cannot import, expected a table after FROM")),
+        };
+        self.accept("as");
+        let alias = self.peek().filter(|token| {
+            token.kind == Kind::Word && !CLAUSES.iter().any(|clause| token.is(clause))
+        });
+        if alias.is_some() {
+            self.position += 1;
+        }
+        let condition = match self.accept("where") {
+            true => Some(self.clause()),
+            false => None,
+        };
+        let filters = condition.and_then(filters);
+        if let Some(filters) = &filters {
+            query = format!("{}({})", query, filters);
+        }
+        if let Some(alias) = alias {
+            query = format!("{} {}", query, alias.text);
+        }
+        if let (Some(condition), None) = (condition, &filters) {
+            query = format!("{} where {{ {} }}", query, expression(condition)?);
+        }
+        if distinct {
+            query.push_str(" distinct");
+        }
+        let columns = match columns {
+            [star] if star.is_symbol("*") => "*".to_string(),
+            columns => list(columns)?,
+        };
+        query = format!("{} {{ {} }}", query, columns);
+        while let Some(token) = self.next() {
+            let clause = token.text.to_lowercase();
+            if clause == "group" || clause == "order" {
+                self.expect("by")?;
+                query = format!("{} {} by {}", query, clause, list(self.clause())?);
+            } else if clause == "limit" || clause == "offset" {
+                query = format!("{} {} {}", query, clause, expression(self.clause())?);
+            } else {
+                return Err(error!(Parse, "cannot import {}", token.text));
+            }
+        }
+        Ok(query)
+    }
+}
+
+fn list(tokens: &[Token]) -> Result<String> {
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text.as_str() {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                items.push(expression(&tokens[start..index])?);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(expression(&tokens[start..])?);
+    Ok(items.join(", "))
+}
+
+// A conjunction of equalities reads better as filters
+fn filters(tokens: &[Token]) -> Option<String> {
+    let mut filters = vec![];
+    for comparison in tokens.split(|token| token.is("and")) {
+        let [column, equals, value] = comparison else {
+            return None;
+        };
+        let literal = matches!(value.kind, Kind::Number | Kind::String);
+        if column.kind != Kind::Word || !equals.is_symbol("=") || !literal {
+            return None;
+        }
+        filters.push(format!("{}: {}", column.text, literal_text(value)));
+    }
+    Some(filters.join(", "))
+}
+
+fn expression(tokens: &[Token]) -> Result<String> {
+    if tokens.is_empty() {
+        return Err(error!(Parse, "cannot import an empty expression"));
+    }
+    let keyword = |token: &Token| KEYWORDS.iter().any(|keyword| token.is(keyword));
+    let call = |index: usize| {
+        let word = &tokens[index];
+        let open = tokens
+            .get(index + 1)
+            .is_some_and(|next| next.is_symbol("("));
+        word.kind == Kind::Word && !keyword(word) && open
+    };
+    let mut output = String::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let text = match token.kind {
+            Kind::String => literal_text(token),
+            Kind::Word if keyword(token) || call(index) => token.text.to_lowercase(),
+            Kind::Symbol if token.text == "<>" => "!=".to_string(),
+            _ => token.text.clone(),
+        };
+        let tight = index == 0
+            || tokens[index - 1].is_symbol("(")
+            || tokens[index - 1].is_symbol(".")
+            || [")", ",", "."].iter().any(|symbol| token.is_symbol(symbol))
+            || call(index - 1);
+        if !tight {
+            output.push(' ');
+        }
+        output.push_str(&text);
+    }
+    Ok(output)
+}
+
+fn literal_text(token: &Token) -> String {
+    match token.kind {
+        Kind::String => format!(
+            "\"{}\"",
+            token.text.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+        _ => token.text.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, Context};
+
+    #[test]
+    fn test_import() {
+        assert_eq!(
+            import("SELECT name, id FROM users WHERE id = 1 AND name = 'O''Hara';").unwrap(),
+            ".users(id: 1, name: \"O'Hara\") { name, id }\n"
+        );
+        let sql = "SELECT DISTINCT u.name, COUNT(u.id) AS total FROM users u WHERE u.age >= 18 AND (u.active OR u.email IS NOT NULL) GROUP BY u.name ORDER BY total DESC LIMIT 10;";
+        let imported = import(sql).unwrap();
+        assert_eq!(
+            imported,
+            ".users u where { u.age >= 18 and (u.active or u.email is not null) } distinct {\n  u.name,\n  count(u.id) as total\n} group by u.name order by total desc limit 10\n"
+        );
+        assert_eq!(compile(&imported, &mut Context::default()).unwrap(), sql);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        assert_eq!(
+            import("UPDATE users SET name = 'Ada'")
+                .unwrap_err()
+                .to_string(),
+            "PARSE ERROR: cannot import UPDATE, expected SELECT"
+        );
+        assert!(import("SELECT * FROM users JOIN posts ON posts.user_id = users.id").is_err());
+    }
+}
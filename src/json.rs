@@ -0,0 +1,318 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+impl Json {
+    pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Self {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut reader = Reader {
+            characters: input.chars().collect(),
+            position: 0,
+        };
+        let value = reader.value()?;
+        reader.whitespace();
+        match reader.peek() {
+            None => Ok(value),
+            Some(character) => Err(reader.unexpected(character)),
+        }
+    }
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        let pretty = f.alternate();
+        let newline = |f: &mut fmt::Formatter, indent: usize| {
+            if pretty {
+                write!(f, "\n{}", "  ".repeat(indent))
+            } else {
+                Ok(())
+            }
+        };
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(items) if items.is_empty() => write!(f, "[]"),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    newline(f, indent + 1)?;
+                    item.write(f, indent + 1)?;
+                }
+                newline(f, indent)?;
+                write!(f, "]")
+            }
+            Json::Object(fields) if fields.is_empty() => write!(f, "{{}}"),
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    newline(f, indent + 1)?;
+                    write_string(f, key)?;
+                    write!(f, "{}", if pretty { ": " } else { ":" })?;
+                    value.write(f, indent + 1)?;
+                }
+                newline(f, indent)?;
+                write!(f, "}}")
+            }
+        }
+    }
+}
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+impl From<Vec<Json>> for Json {
+    fn from(value: Vec<Json>) -> Self {
+        Json::Array(value)
+    }
+}
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+struct Reader {
+    characters: Vec<char>,
+    position: usize,
+}
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.position).copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let character = self.peek();
+        self.position += 1;
+        character
+    }
+    fn whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+    fn unexpected(&self, character: char) -> String {
+        format!("unexpected {:?} at {}", character, self.position)
+    }
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.whitespace();
+        match self.next() {
+            Some(character) if character == expected => Ok(()),
+            Some(character) => Err(self.unexpected(character)),
+            None => Err(format!("expected {:?}, got end of input", expected)),
+        }
+    }
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.position += 1;
+                let mut items = vec![];
+                self.whitespace();
+                if self.peek() == Some(']') {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.whitespace();
+                    match self.next() {
+                        Some(',') => continue,
+                        Some(']') => return Ok(Json::Array(items)),
+                        Some(character) => return Err(self.unexpected(character)),
+                        None => return Err("unterminated array".to_string()),
+                    }
+                }
+            }
+            Some('{') => {
+                self.position += 1;
+                let mut fields = vec![];
+                self.whitespace();
+                if self.peek() == Some('}') {
+                    self.position += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.whitespace();
+                    match self.next() {
+                        Some(',') => continue,
+                        Some('}') => return Ok(Json::Object(fields)),
+                        Some(character) => return Err(self.unexpected(character)),
+                        None => return Err("unterminated object".to_string()),
+                    }
+                }
+            }
+            Some(character) if character == '-' || character.is_ascii_digit() => {
+                let start = self.position;
+                while self.peek().is_some_and(|character| {
+                    character.is_ascii_digit() || "+-.eE".contains(character)
+                }) {
+                    self.position += 1;
+                }
+                Ok(Json::Number(
+                    self.characters[start..self.position].iter().collect(),
+                ))
+            }
+            Some(character) => Err(self.unexpected(character)),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some('b') => value.push('\u{8}'),
+                    Some('f') => value.push('\u{c}'),
+                    Some('u') => {
+                        let code: String = (0..4).filter_map(|_| self.next()).collect();
+                        let character = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\u{}", code))?;
+                        value.push(character);
+                    }
+                    Some(character) => value.push(character),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(character) => value.push(character),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for character in value.chars() {
+        match character {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            character if character.is_control() => write!(f, "\\u{:04x}", character as u32)?,
+            character => write!(f, "{}", character)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let json = Json::object([
+            ("name", "Ada \"Lovelace\"\n".into()),
+            (
+                "tags",
+                vec![Json::Number("1".to_string()), Json::Null].into(),
+            ),
+            ("empty", Json::object([])),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"Ada \"Lovelace\"\n","tags":[1,null],"empty":{}}"#
+        );
+        assert_eq!(
+            format!("{:#}", json),
+            "{\n  \"name\": \"Ada \\\"Lovelace\\\"\\n\",\n  \"tags\": [\n    1,\n    null\n  ],\n  \"empty\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let json = Json::parse(
+            r#" {"name": "Ada \"L\"\n", "tags": [1, -2.5e3, null, true], "empty": {}} "#,
+        )
+        .unwrap();
+        assert_eq!(
+            json,
+            Json::object([
+                ("name", "Ada \"L\"\n".into()),
+                (
+                    "tags",
+                    vec![
+                        Json::Number("1".to_string()),
+                        Json::Number("-2.5e3".to_string()),
+                        Json::Null,
+                        Json::Bool(true)
+                    ]
+                    .into()
+                ),
+                ("empty", Json::object([])),
+            ])
+        );
+        assert_eq!(json.get("name").and_then(Json::as_str), Some("Ada \"L\"\n"));
+        assert_eq!(Json::parse("[1,").unwrap_err(), "unexpected end of input");
+    }
+}
@@ -0,0 +1,7320 @@
+macro_rules! error {
+    ($kind:ident, $($arg:tt)*) => {
+        $crate::CompileError::new($crate::ErrorKind::$kind, format!($($arg)*))
+    };
+}
+
+pub mod arena;
+pub mod backend;
+pub mod cst;
+pub mod dialect;
+pub mod elasticsearch;
+pub mod format;
+pub mod import;
+pub mod intern;
+pub mod json;
+pub mod lint;
+pub mod mongodb;
+pub mod plan;
+pub mod query;
+pub mod schema;
+pub mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use arena::{Arena, NodeId, Nodes};
+use dialect::Dialect;
+use intern::Symbol;
+use json::Json;
+use schema::{Schema, Table, Type};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use visit::{Fold, Visitor};
+
+pub type Result<T> = std::result::Result<T, CompileError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    Lex,
+    Parse,
+    Schema,
+    Eval,
+}
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Lex => "E0001",
+            ErrorKind::Parse => "E0002",
+            ErrorKind::Schema => "E0003",
+            ErrorKind::Eval => "E0004",
+        }
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Lex => "LEX",
+            ErrorKind::Parse => "PARSE",
+            ErrorKind::Schema => "SCHEMA",
+            ErrorKind::Eval => "EVAL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A range of generated SQL and the range of source it was compiled from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mapping {
+    pub generated: Span,
+    pub source: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompileError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Option<Span>,
+}
+impl CompileError {
+    fn new(kind: ErrorKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            span: None,
+        }
+    }
+    fn at(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+}
+impl CompileError {
+    /// The error with its code and the source line it points at, underlined
+    pub fn render(&self, name: &str, source: &str, color: bool) -> String {
+        let header = format!("{} ERROR[{}]", self.kind.name(), self.kind.code());
+        snippet(
+            &header,
+            "1;31",
+            &self.message,
+            self.span,
+            name,
+            source,
+            color,
+        )
+    }
+}
+
+/// A header painted in `tint`, the message, and the source line the span points at
+pub(crate) fn snippet(
+    header: &str,
+    tint: &str,
+    message: &str,
+    span: Option<Span>,
+    name: &str,
+    source: &str,
+    color: bool,
+) -> String {
+    let paint = |code: &str, text: &str| match color {
+        true => format!("\x1b[{}m{}\x1b[0m", code, text),
+        false => text.to_string(),
+    };
+    let mut output = format!("{}: {}", paint(tint, header), message);
+    let Some(span) = span.filter(|span| span.start <= source.len()) else {
+        return output;
+    };
+    let line_start = source[..span.start]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |index| span.start + index);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+    let number = (source[..line_start].matches('\n').count() + 1).to_string();
+    let column = source[line_start..span.start].chars().count();
+    let width = source
+        .get(span.start..span.end.min(line_end))
+        .map_or(0, |text| text.chars().count())
+        .max(1);
+    let gutter = " ".repeat(number.len());
+    output.push_str(&format!(
+        "\n{}{} {}:{}:{}",
+        gutter,
+        paint("1;34", "-->"),
+        name,
+        number,
+        column + 1
+    ));
+    output.push_str(&format!("\n{} {}", gutter, paint("1;34", "|")));
+    output.push_str(&format!(
+        "\n{} {} {}",
+        paint("1;34", &number),
+        paint("1;34", "|"),
+        line
+    ));
+    output.push_str(&format!(
+        "\n{} {} {}{}",
+        gutter,
+        paint("1;34", "|"),
+        " ".repeat(column),
+        paint(tint, &"^".repeat(width))
+    ));
+    output
+}
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ERROR: {}", self.kind.name(), self.message)
+    }
+}
+impl std::error::Error for CompileError {}
+
+pub fn compile(input: &str, context: &mut Context) -> Result<String> {
+    Program::new(input)?.run_with(context)
+}
+
+/// Compiles untrusted bytes without panicking. Input that isn't UTF-8 is a lex error
+pub fn try_compile(input: &[u8], context: &mut Context) -> Result<String> {
+    let input = std::str::from_utf8(input).map_err(|error| {
+        let start = error.valid_up_to();
+        let end = error
+            .error_len()
+            .map_or(input.len(), |length| start + length);
+        error!(Lex, "Invalid UTF-8").at(Span { start, end })
+    })?;
+    unwind(|| compile(input, context))?
+}
+
+/// Parses untrusted bytes without panicking, invalid UTF-8 is replaced before lexing
+pub fn parse_lossy(input: &[u8]) -> (Program, Vec<CompileError>) {
+    let input = String::from_utf8_lossy(input);
+    unwind(|| Program::parse(&input))
+        .unwrap_or_else(|error| (Program::from_arena(Arena::default(), vec![]), vec![error]))
+}
+
+// The last line of defence for the entry points above. A fuzzer's panic hook aborts before this
+// runs, so fuzzing still reports the bug
+fn unwind<T>(run: impl FnOnce() -> T) -> Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(run)).map_err(|panic| {
+        let message = match panic.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
+        };
+        error!(Eval, "internal error: {}", message)
+    })
+}
+
+pub fn diagnose(input: &str, context: &mut Context) -> Vec<CompileError> {
+    let (program, mut errors) = Program::parse(input);
+    errors.extend(program.diagnose(context));
+    errors.sort_by_key(|error| error.span.map(|span| span.start));
+    errors
+}
+
+pub fn is_complete(input: &str) -> bool {
+    if input.trim().is_empty() {
+        return true;
+    }
+    let mut lexer = Lexer::new(input);
+    let mut depth = 0;
+    loop {
+        match lexer.next_token() {
+            Ok(token) if token.kind == TokenKind::Eof => return depth <= 0,
+            Ok(token) if token.kind == TokenKind::LBrace || token.kind == TokenKind::LParen => {
+                depth += 1
+            }
+            Ok(token) if token.kind == TokenKind::RBrace || token.kind == TokenKind::RParen => {
+                depth -= 1
+            }
+            Ok(_) => {}
+            Err(error) => return !error.message.starts_with("Unterminated"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TokenKind {
+    Dot,
+    Plus,
+    Tilde,
+    Minus,
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+    Eq,
+    NotEq,
+    Lt,
+    Gt,
+    LtEq,
+    GtEq,
+    Pipe,
+    Spread,
+    Asterisk,
+    Bang,
+    Semicolon,
+    Arrow,
+    Ident,
+    Variable,
+    Env,
+    Directive,
+    Number,
+    String,
+    Eof,
+    Illegal,
+    Order,
+    Group,
+    By,
+    Asc,
+    Desc,
+    Limit,
+    Offset,
+    Distinct,
+    As,
+    Where,
+    And,
+    Or,
+    Not,
+    Union,
+    All,
+    Let,
+    In,
+    Over,
+    Partition,
+    Case,
+    Is,
+    Null,
+    Between,
+    Like,
+    Ilike,
+    Fragment,
+    Comment,
+}
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TokenKind::Dot => "DOT",
+            TokenKind::Plus => "PLUS",
+            TokenKind::Tilde => "TILDE",
+            TokenKind::Minus => "MINUS",
+            TokenKind::LBrace => "LBRACK",
+            TokenKind::RBrace => "RBRACK",
+            TokenKind::LParen => "LPAREN",
+            TokenKind::RParen => "RPAREN",
+            TokenKind::Comma => "COMMA",
+            TokenKind::Colon => "COLON",
+            TokenKind::Eq => "EQ",
+            TokenKind::NotEq => "NOT_EQ",
+            TokenKind::Lt => "LT",
+            TokenKind::Gt => "GT",
+            TokenKind::LtEq => "LT_EQ",
+            TokenKind::GtEq => "GT_EQ",
+            TokenKind::Pipe => "PIPE",
+            TokenKind::Spread => "SPREAD",
+            TokenKind::Asterisk => "ASTERISK",
+            TokenKind::Bang => "BANG",
+            TokenKind::Semicolon => "SEMICOLON",
+            TokenKind::Arrow => "ARROW",
+            TokenKind::Ident => "IDENT",
+            TokenKind::Variable => "VARIABLE",
+            TokenKind::Env => "ENV",
+            TokenKind::Directive => "DIRECTIVE",
+            TokenKind::Number => "NUMBER",
+            TokenKind::String => "STRING",
+            TokenKind::Eof => "EOF",
+            TokenKind::Illegal => "ILLEGAL",
+            TokenKind::Order => "ORDER",
+            TokenKind::Group => "GROUP",
+            TokenKind::By => "BY",
+            TokenKind::Asc => "ASC",
+            TokenKind::Desc => "DESC",
+            TokenKind::Limit => "LIMIT",
+            TokenKind::Offset => "OFFSET",
+            TokenKind::Distinct => "DISTINCT",
+            TokenKind::As => "AS",
+            TokenKind::Where => "WHERE",
+            TokenKind::And => "AND",
+            TokenKind::Or => "OR",
+            TokenKind::Not => "NOT",
+            TokenKind::Union => "UNION",
+            TokenKind::All => "ALL",
+            TokenKind::Let => "LET",
+            TokenKind::In => "IN",
+            TokenKind::Over => "OVER",
+            TokenKind::Partition => "PARTITION",
+            TokenKind::Case => "CASE",
+            TokenKind::Is => "IS",
+            TokenKind::Null => "NULL",
+            TokenKind::Between => "BETWEEN",
+            TokenKind::Like => "LIKE",
+            TokenKind::Ilike => "ILIKE",
+            TokenKind::Fragment => "FRAGMENT",
+            TokenKind::Comment => "COMMENT",
+        };
+        write!(f, "{}", name)
+    }
+}
+impl TokenKind {
+    pub fn category(self) -> Category {
+        match self {
+            TokenKind::Ident | TokenKind::Variable | TokenKind::Env | TokenKind::Directive => {
+                Category::Ident
+            }
+            TokenKind::Number | TokenKind::String => Category::Literal,
+            TokenKind::Comment => Category::Comment,
+            TokenKind::Illegal => Category::Invalid,
+            TokenKind::Dot
+            | TokenKind::Plus
+            | TokenKind::Tilde
+            | TokenKind::Minus
+            | TokenKind::LBrace
+            | TokenKind::RBrace
+            | TokenKind::LParen
+            | TokenKind::RParen
+            | TokenKind::Comma
+            | TokenKind::Colon
+            | TokenKind::Eq
+            | TokenKind::NotEq
+            | TokenKind::Lt
+            | TokenKind::Gt
+            | TokenKind::LtEq
+            | TokenKind::GtEq
+            | TokenKind::Pipe
+            | TokenKind::Spread
+            | TokenKind::Asterisk
+            | TokenKind::Bang
+            | TokenKind::Semicolon
+            | TokenKind::Arrow
+            | TokenKind::Eof => Category::Punctuation,
+            _ => Category::Keyword,
+        }
+    }
+}
+impl From<TokenKind> for Json {
+    fn from(kind: TokenKind) -> Self {
+        Json::String(kind.to_string())
+    }
+}
+
+fn lookup_identifier(literal: &str) -> TokenKind {
+    match literal {
+        "order" => TokenKind::Order,
+        "group" => TokenKind::Group,
+        "by" => TokenKind::By,
+        "asc" => TokenKind::Asc,
+        "desc" => TokenKind::Desc,
+        "limit" => TokenKind::Limit,
+        "offset" => TokenKind::Offset,
+        "distinct" => TokenKind::Distinct,
+        "as" => TokenKind::As,
+        "where" => TokenKind::Where,
+        "and" => TokenKind::And,
+        "or" => TokenKind::Or,
+        "not" => TokenKind::Not,
+        "union" => TokenKind::Union,
+        "all" => TokenKind::All,
+        "let" => TokenKind::Let,
+        "in" => TokenKind::In,
+        "over" => TokenKind::Over,
+        "partition" => TokenKind::Partition,
+        "case" => TokenKind::Case,
+        "is" => TokenKind::Is,
+        "null" => TokenKind::Null,
+        "between" => TokenKind::Between,
+        "like" => TokenKind::Like,
+        "ilike" => TokenKind::Ilike,
+        "fragment" => TokenKind::Fragment,
+        _ => TokenKind::Ident,
+    }
+}
+
+/// How a token is highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Keyword,
+    Ident,
+    Punctuation,
+    Literal,
+    Comment,
+    Invalid,
+}
+
+#[derive(Clone, Copy)]
+pub enum Placeholder {
+    Question,
+    Dollar,
+    Colon,
+    At,
+}
+impl Placeholder {
+    pub fn new(style: &str) -> Option<Self> {
+        match style {
+            "?" => Some(Placeholder::Question),
+            "$n" => Some(Placeholder::Dollar),
+            ":name" => Some(Placeholder::Colon),
+            "@pn" => Some(Placeholder::At),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Explain {
+    Plan,
+    Analyze,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeywordCase {
+    Upper,
+    Lower,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Case {
+    AsIs,
+    Snake,
+    Camel,
+    Pascal,
+}
+
+pub struct Context {
+    pub dialect: Box<dyn Dialect>,
+    pub placeholder: Option<Placeholder>,
+    /// Bound values as a driver takes them, strings unquoted
+    pub parameters: Vec<String>,
+    pub parameter_names: Vec<String>,
+    /// The literal kind of each bound value, `Number` or `String`
+    pub parameter_kinds: Vec<TokenKind>,
+    pub allow_full_delete: bool,
+    pub pretty: bool,
+    pub newlines: bool,
+    pub transaction: bool,
+    pub explain: Option<Explain>,
+    pub case: Case,
+    pub keyword_case: KeywordCase,
+    pub terminator: String,
+    pub schema: Option<Schema>,
+    pub variables: HashMap<String, Json>,
+    /// Looks up `env("NAME")` and `${NAME}` values, the process environment by default
+    pub environment: fn(&str) -> Option<String>,
+}
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            dialect: Box::new(dialect::Postgres),
+            placeholder: None,
+            parameters: vec![],
+            parameter_names: vec![],
+            parameter_kinds: vec![],
+            allow_full_delete: false,
+            pretty: false,
+            newlines: false,
+            transaction: false,
+            explain: None,
+            case: Case::AsIs,
+            keyword_case: KeywordCase::Upper,
+            terminator: ";".to_string(),
+            schema: None,
+            variables: HashMap::new(),
+            environment: |name| std::env::var(name).ok(),
+        }
+    }
+}
+impl Context {
+    fn style(&self, sql: String) -> String {
+        match self.keyword_case {
+            KeywordCase::Upper => sql,
+            KeywordCase::Lower => lowercase_keywords(&sql),
+        }
+    }
+    fn explain(&self, sql: String) -> Result<String> {
+        let Some(explain) = self.explain else {
+            return Ok(sql);
+        };
+        let analyze = matches!(explain, Explain::Analyze);
+        match self.dialect.explain(analyze) {
+            Some(keyword) => Ok(format!("{} {}", keyword, sql)),
+            None if analyze => Err(error!(
+                Eval,
+                "EXPLAIN ANALYZE is not supported by this dialect"
+            )),
+            None => Err(error!(Eval, "EXPLAIN is not supported by this dialect")),
+        }
+    }
+    fn bind(&mut self, name: &str, kind: TokenKind, value: &str) -> String {
+        let Some(placeholder) = self.placeholder else {
+            return match kind {
+                TokenKind::String => self.dialect.string(value),
+                _ => value.to_string(),
+            };
+        };
+        self.parameters.push(value.to_string());
+        self.parameter_kinds.push(kind);
+        self.parameter_names.push(match name {
+            "" => format!("p{}", self.parameters.len()),
+            name => name.to_string(),
+        });
+        match placeholder {
+            Placeholder::Question => "?".to_string(),
+            Placeholder::Dollar => format!("${}", self.parameters.len()),
+            Placeholder::Colon if name.is_empty() => format!(":p{}", self.parameters.len()),
+            Placeholder::Colon => format!(
+                ":{}",
+                name.replace(|character: char| !character.is_alphanumeric(), "_")
+            ),
+            Placeholder::At => format!("@p{}", self.parameters.len()),
+        }
+    }
+}
+
+struct Writer {
+    pretty: bool,
+    terminator: String,
+    clauses: Vec<String>,
+}
+impl Writer {
+    fn new(context: &Context) -> Self {
+        Self {
+            pretty: context.pretty,
+            terminator: context.terminator.clone(),
+            clauses: vec![],
+        }
+    }
+    fn clause(&mut self, clause: String) {
+        self.clauses.push(clause);
+    }
+    fn list(&mut self, keyword: &str, items: &[String]) {
+        if self.pretty && items.len() > 1 {
+            self.clause(format!("{}\n  {}", keyword, items.join(",\n  ")));
+        } else {
+            self.clause(format!("{} {}", keyword, items.join(", ")));
+        }
+    }
+    fn conditions(&mut self, items: &[String]) {
+        if items.is_empty() {
+            return;
+        }
+        let separator = if self.pretty { "\n  AND " } else { " AND " };
+        self.clause(format!("WHERE {}", items.join(separator)));
+    }
+    fn build(self) -> String {
+        let separator = if self.pretty { "\n" } else { " " };
+        self.clauses.join(separator)
+    }
+    fn finish(mut self) -> String {
+        let terminator = std::mem::take(&mut self.terminator);
+        format!("{}{}", self.build(), terminator)
+    }
+}
+
+const KEYWORDS: [&str; 76] = [
+    "ALL",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "COMMIT",
+    "CONFLICT",
+    "CURRENT_TIMESTAMP",
+    "DATE",
+    "DATEADD",
+    "DATETIME",
+    "DATETIME2",
+    "DAY",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DO",
+    "DUPLICATE",
+    "ELSE",
+    "END",
+    "EXCLUDED",
+    "EXPLAIN",
+    "FETCH",
+    "FIRST",
+    "FROM",
+    "GROUP",
+    "HOUR",
+    "ILIKE",
+    "IN",
+    "INNER",
+    "INSERT",
+    "INTERVAL",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LOWER",
+    "MINUTE",
+    "MONTH",
+    "NEXT",
+    "NOT",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "ONLY",
+    "OR",
+    "ORDER",
+    "OUTPUT",
+    "OVER",
+    "PARTITION",
+    "PLAN",
+    "QUERY",
+    "RETURNING",
+    "ROWS",
+    "SECOND",
+    "SELECT",
+    "SET",
+    "THEN",
+    "TIMESTAMP",
+    "TRANSACTION",
+    "UNION",
+    "UPDATE",
+    "VALUES",
+    "WEEK",
+    "WHEN",
+    "WHERE",
+    "WITH",
+    "YEAR",
+];
+
+// Quoted strings and identifiers are copied as they are
+fn lowercase_keywords(sql: &str) -> String {
+    let mut output = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(character) = rest.chars().next() {
+        let end = match character {
+            '\'' | '"' | '`' | '[' => {
+                let close = if character == '[' { ']' } else { character };
+                rest[1..].find(close).map_or(rest.len(), |index| index + 2)
+            }
+            _ if character.is_ascii_alphabetic() || character == '_' => rest
+                .find(|next: char| !next.is_ascii_alphanumeric() && next != '_')
+                .unwrap_or(rest.len()),
+            _ => character.len_utf8(),
+        };
+        let token = &rest[..end];
+        if KEYWORDS.contains(&token) {
+            output.push_str(&token.to_lowercase());
+        } else {
+            output.push_str(token);
+        }
+        rest = &rest[end..];
+    }
+    output
+}
+
+trait Statement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String>;
+    fn ast(&self, arena: &Arena) -> Json;
+    fn bind(&self, arena: &Arena, _name: &str, context: &mut Context) -> Result<String> {
+        self.eval(arena, context)
+    }
+    fn names(&self, arena: &Arena) -> Vec<String> {
+        self.eval(arena, &mut Context::default())
+            .into_iter()
+            .collect()
+    }
+    fn precedence(&self) -> Precedence {
+        Precedence::Prefix
+    }
+    fn columns<'a>(&'a self, _arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        vec![]
+    }
+    fn check(&self, _arena: &Arena, _schema: &Schema) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prepared {
+    pub name: Option<String>,
+    pub sql: String,
+    pub parameters: Vec<Parameter>,
+    pub tables: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub kind: &'static str,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub range: Span,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Program {
+    statements: Vec<Stmt>,
+    arena: Arena,
+    spans: Vec<Span>,
+    // from a `query name:` header before the statement
+    #[cfg_attr(feature = "serde", serde(default))]
+    names: Vec<Option<String>>,
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
+    warnings: Vec<lint::Warning>,
+    source: String,
+    // statements can be reparsed one by one when no let, fragment or error ties them together
+    standalone: bool,
+}
+impl Program {
+    /// A program of statements built outside the parser, whose ids point into the arena
+    pub fn from_arena(arena: Arena, statements: Vec<Stmt>) -> Self {
+        Self {
+            spans: vec![Span::default(); statements.len()],
+            names: vec![None; statements.len()],
+            statements,
+            arena,
+            warnings: vec![],
+            source: String::new(),
+            standalone: false,
+        }
+    }
+    pub fn new(input: &str) -> Result<Self> {
+        let (program, errors) = Self::parse(input);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(program),
+        }
+    }
+    pub fn parse(input: &str) -> (Self, Vec<CompileError>) {
+        let mut parser = Parser::new(input);
+        let program = parser.run();
+        (program, parser.errors)
+    }
+    pub fn reparse(&mut self, edit: TextEdit) -> Vec<CompileError> {
+        let TextEdit { range, text } = edit;
+        if range.start > range.end || self.source.get(range.start..range.end).is_none() {
+            return vec![error!(
+                Parse,
+                "edit {}..{} is outside of the program", range.start, range.end
+            )];
+        }
+        let removed = range.end - range.start;
+        let before = self
+            .spans
+            .iter()
+            .take_while(|span| span.end < range.start)
+            .count();
+        let after = before
+            + self.spans[before..]
+                .iter()
+                .take_while(|span| span.start <= range.end)
+                .count();
+        let start = before
+            .checked_sub(1)
+            .map_or(0, |index| self.spans[index].end);
+        let end = self
+            .spans
+            .get(after)
+            .map_or(self.source.len(), |span| span.start);
+        self.source.replace_range(range.start..range.end, &text);
+        let shift = |span: Span| Span {
+            start: span.start - removed + text.len(),
+            end: span.end - removed + text.len(),
+        };
+        let slice = &self.source[start..end - removed + text.len()];
+        let program = match self.standalone {
+            true => Some(Parser::new(slice).run()).filter(|program| program.standalone),
+            false => None,
+        };
+        let Some(program) = program else {
+            let (program, errors) = Self::parse(&self.source);
+            *self = program;
+            return errors;
+        };
+        let offset = |span: Span| Span {
+            start: span.start + start,
+            end: span.end + start,
+        };
+        let spans = program.spans.into_iter().map(offset);
+        let parsed = spans.len();
+        self.spans.splice(before..after, spans);
+        // the nodes of a standalone program follow the order of its statements, so the replaced
+        // ones sit between the last node before them and the first one after
+        let low = self.statements[..before]
+            .iter()
+            .rev()
+            .find_map(arena::end)
+            .unwrap_or(0);
+        let high = self.statements[before..after]
+            .iter()
+            .rev()
+            .find_map(arena::end)
+            .unwrap_or(low);
+        let mut statements = program.statements;
+        self.arena.splice(
+            low..high,
+            program.arena,
+            &mut statements,
+            &mut self.statements[after..],
+        );
+        let mut moved = Moved {
+            removed: 0,
+            added: start,
+        };
+        let statements: Vec<Stmt> = statements
+            .into_iter()
+            .map(|statement| moved.fold_stmt(&mut self.arena, statement))
+            .collect();
+        let mut moved = Moved {
+            removed,
+            added: text.len(),
+        };
+        let later: Vec<Stmt> = self
+            .statements
+            .drain(after..)
+            .map(|statement| moved.fold_stmt(&mut self.arena, statement))
+            .collect();
+        self.statements.truncate(before);
+        self.statements.extend(statements.into_iter().chain(later));
+        self.names.splice(before..after, program.names);
+        for span in &mut self.spans[before + parsed..] {
+            *span = shift(*span);
+        }
+        let (mut warnings, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut self.warnings)
+            .into_iter()
+            .partition(|warning| warning.span.is_none_or(|span| span.start < start));
+        warnings.extend(program.warnings.into_iter().map(|warning| lint::Warning {
+            span: warning.span.map(offset),
+            ..warning
+        }));
+        warnings.extend(
+            rest.into_iter()
+                .filter(|warning| warning.span.is_some_and(|span| span.start >= end))
+                .map(|warning| lint::Warning {
+                    span: warning.span.map(shift),
+                    ..warning
+                }),
+        );
+        self.warnings = warnings;
+        vec![]
+    }
+    pub fn run_with(&self, context: &mut Context) -> Result<String> {
+        Ok(self.source_map(context)?.0)
+    }
+    /// The SQL along with the source span each generated statement came from
+    pub fn source_map(&self, context: &mut Context) -> Result<(String, Vec<Mapping>)> {
+        let program = self.cased(context);
+        program.check(context)?;
+        let separator = match (context.pretty, context.newlines) {
+            (true, _) => "\n\n",
+            (false, true) => "\n",
+            (false, false) => " ",
+        };
+        let statements = program
+            .statements
+            .iter()
+            .zip(&program.spans)
+            .map(|(statement, span)| {
+                statement
+                    .eval(&program.arena, context)
+                    .and_then(|sql| context.explain(sql))
+                    .map(|sql| (context.style(sql), *span))
+                    .map_err(|error| error.at(*span))
+            })
+            .collect::<Result<Vec<(String, Span)>>>()?;
+        let mut sql = String::new();
+        let mut mappings = vec![];
+        let mut push = |statement: String, span: Option<Span>| {
+            if !sql.is_empty() {
+                sql.push_str(separator);
+            }
+            let start = sql.len();
+            sql.push_str(&statement);
+            if let Some(source) = span {
+                let generated = Span {
+                    start,
+                    end: sql.len(),
+                };
+                mappings.push(Mapping { generated, source });
+            }
+        };
+        if context.transaction {
+            let begin = format!("{}{}", context.dialect.begin(), context.terminator);
+            push(context.style(begin), None);
+        }
+        for (statement, span) in statements {
+            push(statement, Some(span));
+        }
+        if context.transaction {
+            push(context.style(format!("COMMIT{}", context.terminator)), None);
+        }
+        Ok((sql, mappings))
+    }
+    pub fn queries(&self, context: &mut Context) -> Result<Vec<(String, Vec<Parameter>)>> {
+        Ok(self
+            .prepare(context)?
+            .into_iter()
+            .map(|prepared| (prepared.sql, prepared.parameters))
+            .collect())
+    }
+    pub fn prepare(&self, context: &mut Context) -> Result<Vec<Prepared>> {
+        let program = self.cased(context);
+        program.check(context)?;
+        program
+            .statements
+            .iter()
+            .zip(&program.spans)
+            .zip(&program.names)
+            .map(|((statement, span), name)| {
+                context.parameters.clear();
+                context.parameter_names.clear();
+                context.parameter_kinds.clear();
+                let sql = statement
+                    .eval(&program.arena, context)
+                    .and_then(|sql| context.explain(sql))
+                    .map(|sql| context.style(sql))
+                    .map_err(|error| error.at(*span))?;
+                let names = std::mem::take(&mut context.parameter_names);
+                let values = std::mem::take(&mut context.parameters);
+                let kinds = std::mem::take(&mut context.parameter_kinds);
+                let parameters = names
+                    .into_iter()
+                    .zip(values)
+                    .zip(kinds)
+                    .map(|((name, value), kind)| Parameter {
+                        name,
+                        kind: match kind {
+                            TokenKind::Number => "number",
+                            _ => "string",
+                        },
+                        value,
+                    })
+                    .collect();
+                let mut tables = Tables::default();
+                tables.visit_stmt(&program.arena, statement);
+                Ok(Prepared {
+                    name: name.clone(),
+                    sql,
+                    parameters,
+                    tables: tables.touched(),
+                })
+            })
+            .collect()
+    }
+    pub fn diagnose(&self, context: &mut Context) -> Vec<CompileError> {
+        let program = self.cased(context);
+        let mut errors = vec![];
+        for (statement, span) in program.statements.iter().zip(&program.spans) {
+            if let Err(error) = check(&program.arena, statement, context) {
+                errors.push(error.at(*span));
+            } else if let Err(error) = statement.eval(&program.arena, context) {
+                errors.push(error.at(*span));
+            }
+        }
+        errors
+    }
+    pub fn visit(&self, visitor: &mut impl visit::Visitor) {
+        for statement in &self.statements {
+            visitor.visit_stmt(&self.arena, statement);
+        }
+    }
+    pub fn fold(self, folder: &mut impl visit::Fold) -> Self {
+        let mut arena = self.arena;
+        Self {
+            statements: self
+                .statements
+                .into_iter()
+                .map(|statement| folder.fold_stmt(&mut arena, statement))
+                .collect(),
+            arena,
+            spans: self.spans,
+            names: self.names,
+            warnings: self.warnings,
+            source: self.source,
+            standalone: false,
+        }
+    }
+    pub fn statements(&self) -> &[Stmt] {
+        &self.statements
+    }
+    /// The nodes the ids of the statements point into
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+    pub fn names(&self) -> &[Option<String>] {
+        &self.names
+    }
+    pub fn warnings(&self) -> &[lint::Warning] {
+        &self.warnings
+    }
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    fn cased(&self, context: &Context) -> Cow<'_, Program> {
+        match context.case {
+            Case::AsIs => Cow::Borrowed(self),
+            case => Cow::Owned(self.clone().fold(&mut visit::Recase(case))),
+        }
+    }
+    fn check(&self, context: &Context) -> Result<()> {
+        for (statement, span) in self.statements.iter().zip(&self.spans) {
+            check(&self.arena, statement, context).map_err(|error| error.at(*span))?;
+        }
+        Ok(())
+    }
+    pub fn ast(&self) -> Json {
+        Json::Array(
+            self.statements
+                .iter()
+                .map(|statement| statement.ast(&self.arena))
+                .collect(),
+        )
+    }
+}
+
+macro_rules! each {
+    ($statement:expr, $node:ident => $body:expr) => {
+        match $statement {
+            Stmt::Dot($node) => $body,
+            Stmt::Insert($node) => $body,
+            Stmt::Update($node) => $body,
+            Stmt::Delete($node) => $body,
+            Stmt::Block($node) => $body,
+            Stmt::Identifier($node) => $body,
+            Stmt::Literal($node) => $body,
+            Stmt::Prefix($node) => $body,
+            Stmt::Infix($node) => $body,
+            Stmt::IsNull($node) => $body,
+            Stmt::Call($node) => $body,
+            Stmt::Alias($node) => $body,
+            Stmt::Join($node) => $body,
+            Stmt::Subquery($node) => $body,
+            Stmt::Union($node) => $body,
+            Stmt::With($node) => $body,
+            Stmt::Case($node) => $body,
+            Stmt::In($node) => $body,
+            Stmt::Between($node) => $body,
+            Stmt::Like($node) => $body,
+            Stmt::Spread($node) => $body,
+            Stmt::Wildcard($node) => $body,
+            Stmt::Variable($node) => $body,
+            Stmt::Env($node) => $body,
+            Stmt::Temporal($node) => $body,
+        }
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stmt {
+    Dot(DotStatement),
+    Insert(InsertStatement),
+    Update(UpdateStatement),
+    Delete(DeleteStatement),
+    Block(BlockStatement),
+    Identifier(IdentifierStatement),
+    Literal(LiteralStatement),
+    Prefix(PrefixExpression),
+    Infix(InfixExpression),
+    IsNull(IsNullExpression),
+    Call(FunctionCall),
+    Alias(AliasStatement),
+    Join(JoinStatement),
+    Subquery(SubqueryStatement),
+    Union(UnionStatement),
+    With(WithStatement),
+    Case(CaseExpression),
+    In(InExpression),
+    Between(BetweenExpression),
+    Like(LikeExpression),
+    Spread(SpreadStatement),
+    Wildcard(WildcardStatement),
+    Variable(VariableStatement),
+    Env(EnvStatement),
+    Temporal(TemporalStatement),
+}
+impl Statement for Stmt {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        each!(self, node => node.eval(arena, context))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        each!(self, node => node.ast(arena))
+    }
+    fn bind(&self, arena: &Arena, name: &str, context: &mut Context) -> Result<String> {
+        each!(self, node => node.bind(arena, name, context))
+    }
+    fn names(&self, arena: &Arena) -> Vec<String> {
+        each!(self, node => node.names(arena))
+    }
+    fn precedence(&self) -> Precedence {
+        each!(self, node => node.precedence())
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        each!(self, node => node.columns(arena))
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        each!(self, node => node.check(arena, schema))
+    }
+}
+impl Stmt {
+    // Plural name of the construct, for errors from targets that can't express it
+    pub(crate) fn noun(&self) -> &'static str {
+        match self {
+            Stmt::Dot(_) => "selects",
+            Stmt::Insert(_) => "inserts",
+            Stmt::Update(_) => "updates",
+            Stmt::Delete(_) => "deletes",
+            Stmt::Block(_) => "blocks",
+            Stmt::Identifier(_) => "column references",
+            Stmt::Literal(_) => "literals",
+            Stmt::Prefix(_) => "not expressions",
+            Stmt::Infix(_) => "operators",
+            Stmt::IsNull(_) => "is null tests",
+            Stmt::Call(_) => "function calls",
+            Stmt::Alias(_) => "aliases",
+            Stmt::Join(_) => "joins",
+            Stmt::Subquery(_) => "subqueries",
+            Stmt::Union(_) => "unions",
+            Stmt::With(_) => "let queries",
+            Stmt::Case(_) => "case expressions",
+            Stmt::In(_) => "in lists",
+            Stmt::Between(_) => "between ranges",
+            Stmt::Like(_) => "like patterns",
+            Stmt::Spread(_) => "fragment spreads",
+            Stmt::Wildcard(_) => "wildcards",
+            Stmt::Variable(_) => "variables",
+            Stmt::Env(_) => "environment variables",
+            Stmt::Temporal(_) => "dates and times",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdentifierStatement {
+    pub literal: Symbol,
+    /// Where the name was written, in the source its statement was parsed from
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub span: Option<Span>,
+}
+// Schema errors about a name point at it rather than the whole statement
+impl IdentifierStatement {
+    fn locate(&self, error: CompileError) -> CompileError {
+        match self.span {
+            Some(span) => error.at(span),
+            None => error,
+        }
+    }
+    fn table<'a>(&self, schema: &'a Schema) -> Result<&'a Table> {
+        schema
+            .table(&self.literal)
+            .map_err(|error| self.locate(error))
+    }
+    fn column(&self, table: &Table) -> Result<()> {
+        table
+            .column(&self.literal)
+            .map_err(|error| self.locate(error))
+    }
+}
+// The same name written in two places is still the same name
+impl PartialEq for IdentifierStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.literal == other.literal
+    }
+}
+impl Statement for IdentifierStatement {
+    fn eval(&self, _arena: &Arena, context: &mut Context) -> Result<String> {
+        Ok(context.dialect.identifier(&self.literal))
+    }
+    fn ast(&self, _arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Identifier".into()),
+            ("name", self.literal.as_str().into()),
+        ])
+    }
+    fn columns<'a>(&'a self, _arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        vec![self]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DotStatement {
+    pub ident: IdentifierStatement,
+    pub filters: Vec<PairStatement>,
+    pub alias: Option<IdentifierStatement>,
+    pub joins: Vec<JoinClause>,
+    pub condition: Option<NodeId>,
+    pub within: Option<WithinStatement>,
+    pub distinct: bool,
+    pub distinct_on: Vec<IdentifierStatement>,
+    pub block: BlockStatement,
+    pub grouping: Vec<IdentifierStatement>,
+    pub ordering: Vec<OrderStatement>,
+    pub limit: Option<LiteralStatement>,
+    pub offset: Option<LiteralStatement>,
+}
+impl DotStatement {
+    fn select(&self, arena: &Arena, context: &mut Context) -> Result<Writer> {
+        let mut columns = arena[self.block.properties]
+            .iter()
+            .map(|statement| match statement {
+                Stmt::Wildcard(wildcard) => wildcard.expand(&self.ident.literal, None, context),
+                _ => statement.eval(arena, context),
+            })
+            .collect::<Result<Vec<String>>>()?;
+        columns.retain(|column| !column.is_empty());
+        if columns.is_empty() {
+            columns.push("*".to_string());
+        }
+        let table = match &self.alias {
+            Some(alias) => format!(
+                "{} {}",
+                self.ident.eval(arena, context)?,
+                alias.eval(arena, context)?
+            ),
+            None => self.ident.eval(arena, context)?,
+        };
+        let select = if !self.distinct_on.is_empty() {
+            let on = self
+                .distinct_on
+                .iter()
+                .map(|statement| statement.eval(arena, context))
+                .collect::<Result<Vec<String>>>()?
+                .join(", ");
+            let distinct = context
+                .dialect
+                .distinct_on(&on)
+                .ok_or_else(|| error!(Eval, "DISTINCT ON is not supported by this dialect"))?;
+            format!("SELECT {}", distinct)
+        } else if self.distinct {
+            "SELECT DISTINCT".to_string()
+        } else {
+            "SELECT".to_string()
+        };
+        let mut query = Writer::new(context);
+        query.list(&select, &columns);
+        query.clause(format!("FROM {}", table));
+        for join in &self.joins {
+            query.clause(join.eval(arena, context)?);
+        }
+        let parent = self.alias.as_ref().unwrap_or(&self.ident);
+        for join in joins(
+            arena,
+            &self.block,
+            &self.ident.literal,
+            &parent.literal,
+            context,
+        )? {
+            query.clause(join);
+        }
+        let mut filters = self
+            .filters
+            .iter()
+            .map(|statement| statement.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?;
+        if let Some(within) = &self.within {
+            filters.push(within.filter(arena, &self.ident.literal, context)?);
+        }
+        if let Some(condition) = self.condition {
+            if filters.is_empty() {
+                filters.push(arena[condition].eval(arena, context)?);
+            } else {
+                filters.push(wrap(arena, &arena[condition], Precedence::And, context)?);
+            }
+        }
+        query.conditions(&filters);
+        if !self.grouping.is_empty() {
+            let grouping = self
+                .grouping
+                .iter()
+                .map(|statement| statement.eval(arena, context))
+                .collect::<Result<Vec<String>>>()?
+                .join(", ");
+            query.clause(format!("GROUP BY {}", grouping));
+        }
+        if !self.ordering.is_empty() {
+            let ordering = self
+                .ordering
+                .iter()
+                .map(|statement| statement.eval(arena, context))
+                .collect::<Result<Vec<String>>>()?
+                .join(", ");
+            query.clause(format!("ORDER BY {}", ordering));
+        }
+        for clause in context.dialect.paginate(
+            self.limit.as_ref().map(|limit| limit.literal.as_str()),
+            self.offset.as_ref().map(|offset| offset.literal.as_str()),
+            !self.ordering.is_empty(),
+        ) {
+            query.clause(clause);
+        }
+        Ok(query)
+    }
+}
+impl DotStatement {
+    fn member(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let query = self.select(arena, context)?.build();
+        let limited = self.limit.is_some() || self.offset.is_some();
+        if self.ordering.is_empty() && !limited {
+            return Ok(query);
+        }
+        context
+            .dialect
+            .union_member(&query, limited)
+            .ok_or_else(|| {
+                error!(
+                    Eval,
+                    "ORDER BY in a union member needs a limit on this dialect"
+                )
+            })
+    }
+}
+impl Statement for DotStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        Ok(self.select(arena, context)?.finish())
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Select".into()),
+            ("table", self.ident.ast(arena)),
+            (
+                "filters",
+                Json::Array(self.filters.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "alias",
+                self.alias.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+            (
+                "joins",
+                Json::Array(self.joins.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "condition",
+                self.condition
+                    .map(|condition| arena[condition].ast(arena))
+                    .into(),
+            ),
+            (
+                "within",
+                self.within.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+            ("distinct", self.distinct.into()),
+            (
+                "distinct_on",
+                Json::Array(
+                    self.distinct_on
+                        .iter()
+                        .map(|node| node.ast(arena))
+                        .collect(),
+                ),
+            ),
+            ("block", self.block.ast(arena)),
+            (
+                "grouping",
+                Json::Array(self.grouping.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "ordering",
+                Json::Array(self.ordering.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "limit",
+                self.limit.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+            (
+                "offset",
+                self.offset.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        let table = self.ident.table(schema)?;
+        let selections = arena[self.block.properties]
+            .iter()
+            .flat_map(|statement| statement.columns(arena));
+        let filters = self
+            .filters
+            .iter()
+            .flat_map(|statement| statement.columns(arena));
+        let condition = self
+            .condition
+            .iter()
+            .chain(self.joins.iter().flat_map(|join| &join.condition))
+            .flat_map(|&statement| arena[statement].columns(arena));
+        let mut tables = vec![(self.alias.as_ref().unwrap_or(&self.ident).literal, table)];
+        for join in &self.joins {
+            let joined = join.table.table(schema)?;
+            tables.push((join.alias.as_ref().unwrap_or(&join.table).literal, joined));
+        }
+        for column in selections.chain(filters).chain(condition) {
+            joined_column(&tables, &column.literal).map_err(|error| column.locate(error))?;
+        }
+        for property in &arena[self.block.properties] {
+            property.check(arena, schema)?;
+        }
+        compare_pairs(arena, &self.filters, table)?;
+        // Types are only known for the columns of a single table
+        if !self.joins.is_empty() {
+            return Ok(());
+        }
+        if let Some(condition) = self.condition {
+            typed(arena, &arena[condition], table)?;
+        }
+        for property in &arena[self.block.properties] {
+            typed(arena, property, table)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LiteralStatement {
+    pub kind: TokenKind,
+    pub literal: String,
+}
+impl Statement for LiteralStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        self.bind(arena, "", context)
+    }
+    fn bind(&self, _arena: &Arena, name: &str, context: &mut Context) -> Result<String> {
+        if self.kind == TokenKind::String && self.literal.contains('\0') {
+            return Err(error!(Eval, "strings cannot contain NUL characters"));
+        }
+        Ok(context.bind(name, self.kind, &self.literal))
+    }
+    fn ast(&self, _arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Literal".into()),
+            ("kind", self.kind.into()),
+            ("value", self.literal.as_str().into()),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemporalKind {
+    Date,
+    Timestamp,
+    Interval,
+    Now,
+}
+
+/// `date"2024-01-01"`, `timestamp"2024-01-01 09:30"`, `interval "7 days"` or `now()`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TemporalStatement {
+    pub kind: TemporalKind,
+    pub value: String,
+}
+impl Statement for TemporalStatement {
+    fn eval(&self, _arena: &Arena, context: &mut Context) -> Result<String> {
+        match self.kind {
+            TemporalKind::Date => Ok(context.dialect.date(&self.value, false)),
+            TemporalKind::Timestamp => Ok(context.dialect.date(&self.value, true)),
+            TemporalKind::Now => Ok("CURRENT_TIMESTAMP".to_string()),
+            TemporalKind::Interval => Err(error!(
+                Eval,
+                "interval \"{}\" must be added to or subtracted from a date", self.value
+            )),
+        }
+    }
+    fn ast(&self, _arena: &Arena) -> Json {
+        let kind = match self.kind {
+            TemporalKind::Date => "date",
+            TemporalKind::Timestamp => "timestamp",
+            TemporalKind::Interval => "interval",
+            TemporalKind::Now => "now",
+        };
+        Json::object([
+            ("type", "Temporal".into()),
+            ("kind", kind.into()),
+            ("value", self.value.as_str().into()),
+        ])
+    }
+}
+
+// The amount and singular unit of an interval like "7 days". Amounts fit in an i32, so dialects
+// can negate them or turn weeks into days without overflowing
+fn interval(value: &str) -> Option<(i64, &'static str)> {
+    let mut parts = value.split_whitespace();
+    let amount = i64::from(parts.next()?.parse::<i32>().ok()?);
+    let unit = parts.next()?;
+    let unit = UNITS
+        .into_iter()
+        .find(|name| unit == *name || unit.strip_suffix('s') == Some(name))?;
+    parts.next().is_none().then_some((amount, unit))
+}
+
+const UNITS: [&str; 7] = ["second", "minute", "hour", "day", "week", "month", "year"];
+
+// How many days the month has, only called once the year and month are known to be numbers
+fn days(year: &str, month: &str) -> u32 {
+    let year: u32 = year.parse().unwrap_or_default();
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month.parse().unwrap_or_default() {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// YYYY-MM-DD, followed by HH:MM or HH:MM:SS for timestamps
+fn valid_date(value: &str, time: bool) -> bool {
+    let digits = |text: &str, low: u32, high: u32| {
+        !text.is_empty()
+            && text.bytes().all(|byte| byte.is_ascii_digit())
+            && text
+                .parse::<u32>()
+                .is_ok_and(|number| (low..=high).contains(&number))
+    };
+    let (date, clock) = match value.split_once([' ', 'T']) {
+        Some((date, clock)) if time => (date, clock),
+        None if !time => (value, ""),
+        _ => return false,
+    };
+    let parts: Vec<&str> = date.split('-').collect();
+    let valid = parts.len() == 3
+        && parts[0].len() == 4
+        && digits(parts[0], 0, 9999)
+        && parts[1].len() == 2
+        && digits(parts[1], 1, 12)
+        && parts[2].len() == 2
+        && digits(parts[2], 1, days(parts[0], parts[1]));
+    if !time {
+        return valid;
+    }
+    let parts: Vec<&str> = clock.split(':').collect();
+    let seconds = parts.get(2).is_none_or(|seconds| {
+        let whole = seconds.split_once('.').map_or(*seconds, |(whole, _)| whole);
+        whole.len() == 2 && digits(whole, 0, 59)
+    });
+    valid
+        && (2..=3).contains(&parts.len())
+        && parts[0].len() == 2
+        && digits(parts[0], 0, 23)
+        && parts[1].len() == 2
+        && digits(parts[1], 0, 59)
+        && seconds
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableStatement {
+    pub name: String,
+}
+impl Statement for VariableStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        self.bind(arena, "", context)
+    }
+    fn bind(&self, arena: &Arena, name: &str, context: &mut Context) -> Result<String> {
+        let literal = match context.variables.get(&self.name) {
+            Some(Json::Number(number)) => LiteralStatement {
+                kind: TokenKind::Number,
+                literal: number.clone(),
+            },
+            Some(Json::String(string)) => LiteralStatement {
+                kind: TokenKind::String,
+                literal: string.clone(),
+            },
+            Some(_) => {
+                return Err(error!(
+                    Eval,
+                    "variable ${} must be a number or a string", self.name
+                ))
+            }
+            None => return Err(error!(Eval, "unbound variable ${}", self.name)),
+        };
+        literal.bind(arena, name, context)
+    }
+    fn names(&self, _arena: &Arena) -> Vec<String> {
+        vec![]
+    }
+    fn ast(&self, _arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Variable".into()),
+            ("name", self.name.as_str().into()),
+        ])
+    }
+}
+
+/// `env("NAME")` or `${NAME}`, a string read from the environment while compiling
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvStatement {
+    pub name: String,
+}
+impl EnvStatement {
+    pub(crate) fn resolve(&self, context: &Context) -> Result<String> {
+        (context.environment)(&self.name)
+            .ok_or_else(|| error!(Eval, "environment variable {} is not set", self.name))
+    }
+}
+impl Statement for EnvStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        self.bind(arena, "", context)
+    }
+    fn bind(&self, arena: &Arena, name: &str, context: &mut Context) -> Result<String> {
+        let literal = LiteralStatement {
+            kind: TokenKind::String,
+            literal: self.resolve(context)?,
+        };
+        literal.bind(arena, name, context)
+    }
+    fn names(&self, _arena: &Arena) -> Vec<String> {
+        vec![]
+    }
+    fn ast(&self, _arena: &Arena) -> Json {
+        Json::object([("type", "Env".into()), ("name", self.name.as_str().into())])
+    }
+}
+
+fn check(arena: &Arena, statement: &Stmt, context: &Context) -> Result<()> {
+    let mut unbound = Variables(&context.variables, vec![]);
+    unbound.visit_stmt(arena, statement);
+    if !unbound.1.is_empty() {
+        return Err(error!(Eval, "unbound variables: {}", unbound.1.join(", ")));
+    }
+    match &context.schema {
+        Some(schema) => statement.check(arena, schema),
+        None => Ok(()),
+    }
+}
+
+// The type of an expression over the columns of one table, None when it can't be known
+// A qualified column is looked up in the table it names, a bare one in any of them
+fn joined_column(tables: &[(Symbol, &Table)], column: &str) -> Result<()> {
+    let own = tables[0].1;
+    if tables.len() == 1 {
+        return own.column(column);
+    }
+    match column.rsplit_once('.') {
+        Some((qualifier, _)) => tables
+            .iter()
+            .find(|(name, _)| name.as_str() == qualifier)
+            .map_or(own, |(_, table)| table)
+            .column(column),
+        None if tables.iter().any(|(_, table)| table.column(column).is_ok()) => Ok(()),
+        None => own.column(column),
+    }
+}
+
+fn typed(arena: &Arena, statement: &Stmt, table: &Table) -> Result<Option<Type>> {
+    Ok(match statement {
+        Stmt::Identifier(identifier) => table.type_of(&identifier.literal),
+        Stmt::Literal(literal) => match literal.kind {
+            TokenKind::Number => Some(Type::Int),
+            TokenKind::String => Some(Type::Text),
+            _ => None,
+        },
+        Stmt::Temporal(temporal) => match temporal.kind {
+            TemporalKind::Interval => None,
+            _ => Some(Type::Timestamp),
+        },
+        Stmt::Alias(alias) => typed(arena, &arena[alias.selection], table)?,
+        Stmt::Prefix(prefix) => {
+            typed(arena, &arena[prefix.right], table)?;
+            Some(Type::Bool)
+        }
+        Stmt::Infix(infix) => {
+            match infix.operator {
+                TokenKind::And | TokenKind::Or => {
+                    typed(arena, &arena[infix.left], table)?;
+                    typed(arena, &arena[infix.right], table)?;
+                }
+                TokenKind::Plus | TokenKind::Minus if matches!(&arena[infix.right], Stmt::Temporal(temporal) if temporal.kind == TemporalKind::Interval) =>
+                {
+                    return typed(arena, &arena[infix.left], table);
+                }
+                _ => compare(arena, &arena[infix.left], &arena[infix.right], table)?,
+            }
+            Some(Type::Bool)
+        }
+        Stmt::IsNull(is_null) => {
+            typed(arena, &arena[is_null.operand], table)?;
+            Some(Type::Bool)
+        }
+        Stmt::In(is_in) => {
+            for value in &arena[is_in.values] {
+                compare(arena, &arena[is_in.operand], value, table)?;
+            }
+            Some(Type::Bool)
+        }
+        Stmt::Between(between) => {
+            compare(arena, &arena[between.operand], &arena[between.low], table)?;
+            compare(arena, &arena[between.operand], &arena[between.high], table)?;
+            Some(Type::Bool)
+        }
+        Stmt::Like(like) => {
+            match typed(arena, &arena[like.operand], table)? {
+                Some(kind) if !kind.compatible(Type::Text) => {
+                    return Err(locate(
+                        &arena[like.operand],
+                        error!(
+                            Schema,
+                            "cannot match a pattern against {}",
+                            describe(&arena[like.operand], kind)
+                        ),
+                    ))
+                }
+                _ => {}
+            }
+            Some(Type::Bool)
+        }
+        Stmt::Call(call) => {
+            let mut arguments = vec![];
+            for argument in &arena[call.arguments] {
+                arguments.push(typed(arena, argument, table)?);
+            }
+            match (
+                call.function.literal.as_str(),
+                arena[call.arguments].first(),
+            ) {
+                ("count", _) => Some(Type::Int),
+                ("sum" | "avg", Some(argument)) => match arguments[0] {
+                    Some(Type::Int) | None => Some(Type::Int),
+                    Some(kind) => {
+                        return Err(locate(
+                            argument,
+                            error!(
+                                Schema,
+                                "{}() needs a number, got {}",
+                                call.function.literal,
+                                describe(argument, kind)
+                            ),
+                        ))
+                    }
+                },
+                ("min" | "max", Some(_)) => arguments[0],
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+fn compare(arena: &Arena, left: &Stmt, right: &Stmt, table: &Table) -> Result<()> {
+    match (typed(arena, left, table)?, typed(arena, right, table)?) {
+        (Some(kind), Some(other)) if !kind.compatible(other) => {
+            let error = error!(
+                Schema,
+                "cannot compare {} to {}",
+                describe(left, kind),
+                describe(right, other)
+            );
+            Err(match left {
+                Stmt::Identifier(_) => locate(left, error),
+                _ => locate(right, error),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+// Type errors point at the column they name, if there is one
+fn locate(statement: &Stmt, error: CompileError) -> CompileError {
+    match statement {
+        Stmt::Identifier(identifier) => identifier.locate(error),
+        _ => error,
+    }
+}
+
+fn describe(statement: &Stmt, kind: Type) -> String {
+    match statement {
+        Stmt::Identifier(identifier) => format!("{} column '{}'", kind, identifier.literal),
+        _ => kind.to_string(),
+    }
+}
+
+fn compare_pairs(arena: &Arena, pairs: &[PairStatement], table: &Table) -> Result<()> {
+    for pair in pairs {
+        let column = Stmt::Identifier(pair.ident.clone());
+        compare(arena, &column, &arena[pair.value], table)?;
+    }
+    Ok(())
+}
+
+struct Variables<'a>(&'a HashMap<String, Json>, Vec<String>);
+impl visit::Visitor for Variables<'_> {
+    fn visit_stmt(&mut self, arena: &Arena, statement: &Stmt) {
+        match statement {
+            Stmt::Variable(variable) => {
+                let name = format!("${}", variable.name);
+                if !self.0.contains_key(&variable.name) && !self.1.contains(&name) {
+                    self.1.push(name);
+                }
+            }
+            statement => visit::walk_stmt(self, arena, statement),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PairStatement {
+    pub ident: IdentifierStatement,
+    pub value: NodeId,
+}
+impl Statement for PairStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let value = arena[self.value].bind(arena, &self.ident.literal, context)?;
+        Ok(format!("{} = {}", self.ident.eval(arena, context)?, value))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Pair".into()),
+            ("column", self.ident.ast(arena)),
+            ("value", arena[self.value].ast(arena)),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        self.ident.columns(arena)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrefixExpression {
+    pub operator: TokenKind,
+    pub right: NodeId,
+}
+impl Statement for PrefixExpression {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let right = wrap(arena, &arena[self.right], Precedence::Not, context)?;
+        Ok(format!("{} {}", operator(self.operator), right))
+    }
+    fn precedence(&self) -> Precedence {
+        Precedence::Not
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Prefix".into()),
+            ("operator", self.operator.into()),
+            ("right", arena[self.right].ast(arena)),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        arena[self.right].columns(arena)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InfixExpression {
+    pub left: NodeId,
+    pub operator: TokenKind,
+    pub right: NodeId,
+}
+impl Statement for InfixExpression {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let precedence = self.precedence();
+        let left = wrap(arena, &arena[self.left], precedence, context)?;
+        if let TokenKind::Plus | TokenKind::Minus = self.operator {
+            let value = match &arena[self.right] {
+                Stmt::Temporal(temporal) if temporal.kind == TemporalKind::Interval => {
+                    &temporal.value
+                }
+                _ => {
+                    return Err(error!(
+                        Eval,
+                        "only an interval can be added to or subtracted from a date"
+                    ))
+                }
+            };
+            let Some((amount, unit)) = interval(value) else {
+                return Err(error!(Eval, "invalid interval \"{}\"", value));
+            };
+            let amount = match self.operator {
+                TokenKind::Minus => -amount,
+                _ => amount,
+            };
+            return Ok(context.dialect.interval(&left, amount, unit));
+        }
+        let right = if precedence > Precedence::Not {
+            let name = arena[self.left]
+                .names(arena)
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            arena[self.right].bind(arena, &name, context)?
+        } else {
+            wrap(arena, &arena[self.right], precedence, context)?
+        };
+        Ok(format!("{} {} {}", left, operator(self.operator), right))
+    }
+    fn precedence(&self) -> Precedence {
+        precedence(self.operator)
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Infix".into()),
+            ("left", arena[self.left].ast(arena)),
+            ("operator", self.operator.into()),
+            ("right", arena[self.right].ast(arena)),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        let mut columns = arena[self.left].columns(arena);
+        columns.extend(arena[self.right].columns(arena));
+        columns
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IsNullExpression {
+    pub operand: NodeId,
+    pub negated: bool,
+}
+impl Statement for IsNullExpression {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let operand = wrap(
+            arena,
+            &arena[self.operand],
+            Precedence::LessGreater,
+            context,
+        )?;
+        if self.negated {
+            Ok(format!("{} IS NOT NULL", operand))
+        } else {
+            Ok(format!("{} IS NULL", operand))
+        }
+    }
+    fn precedence(&self) -> Precedence {
+        Precedence::Equals
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "IsNull".into()),
+            ("operand", arena[self.operand].ast(arena)),
+            ("negated", self.negated.into()),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        arena[self.operand].columns(arena)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InExpression {
+    pub operand: NodeId,
+    pub values: Nodes,
+}
+impl Statement for InExpression {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let operand = wrap(
+            arena,
+            &arena[self.operand],
+            Precedence::LessGreater,
+            context,
+        )?;
+        let name = arena[self.operand]
+            .names(arena)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let values = arena[self.values]
+            .iter()
+            .map(|value| value.bind(arena, &name, context))
+            .collect::<Result<Vec<String>>>()?;
+        Ok(format!("{} IN ({})", operand, values.join(", ")))
+    }
+    fn precedence(&self) -> Precedence {
+        Precedence::Equals
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "In".into()),
+            ("operand", arena[self.operand].ast(arena)),
+            (
+                "values",
+                Json::Array(
+                    arena[self.values]
+                        .iter()
+                        .map(|value| value.ast(arena))
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        arena[self.operand].columns(arena)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BetweenExpression {
+    pub operand: NodeId,
+    pub low: NodeId,
+    pub high: NodeId,
+}
+impl Statement for BetweenExpression {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let operand = wrap(
+            arena,
+            &arena[self.operand],
+            Precedence::LessGreater,
+            context,
+        )?;
+        let name = arena[self.operand]
+            .names(arena)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let low = arena[self.low].bind(arena, &name, context)?;
+        let high = arena[self.high].bind(arena, &name, context)?;
+        Ok(format!("{} BETWEEN {} AND {}", operand, low, high))
+    }
+    fn precedence(&self) -> Precedence {
+        Precedence::Equals
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Between".into()),
+            ("operand", arena[self.operand].ast(arena)),
+            ("low", arena[self.low].ast(arena)),
+            ("high", arena[self.high].ast(arena)),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        arena[self.operand].columns(arena)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LikeExpression {
+    pub operand: NodeId,
+    pub pattern: NodeId,
+    pub insensitive: bool,
+}
+impl Statement for LikeExpression {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let operand = wrap(
+            arena,
+            &arena[self.operand],
+            Precedence::LessGreater,
+            context,
+        )?;
+        let name = arena[self.operand]
+            .names(arena)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let pattern = arena[self.pattern].bind(arena, &name, context)?;
+        if self.insensitive {
+            Ok(context.dialect.ilike(&operand, &pattern))
+        } else {
+            Ok(format!("{} LIKE {}", operand, pattern))
+        }
+    }
+    fn precedence(&self) -> Precedence {
+        Precedence::Equals
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Like".into()),
+            ("operand", arena[self.operand].ast(arena)),
+            ("pattern", arena[self.pattern].ast(arena)),
+            ("insensitive", self.insensitive.into()),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        arena[self.operand].columns(arena)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CaseExpression {
+    pub branches: Vec<(NodeId, NodeId)>,
+    pub otherwise: Option<NodeId>,
+}
+impl Statement for CaseExpression {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let mut clauses = vec!["CASE".to_string()];
+        for &(condition, result) in &self.branches {
+            let condition = arena[condition].eval(arena, context)?;
+            let result = arena[result].eval(arena, context)?;
+            clauses.push(format!("WHEN {} THEN {}", condition, result));
+        }
+        if let Some(otherwise) = self.otherwise {
+            clauses.push(format!("ELSE {}", arena[otherwise].eval(arena, context)?));
+        }
+        clauses.push("END".to_string());
+        Ok(clauses.join(" "))
+    }
+    fn names(&self, _arena: &Arena) -> Vec<String> {
+        vec![]
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        let branches = self.branches.iter().map(|&(condition, result)| {
+            Json::object([
+                ("condition", arena[condition].ast(arena)),
+                ("result", arena[result].ast(arena)),
+            ])
+        });
+        Json::object([
+            ("type", "Case".into()),
+            ("branches", Json::Array(branches.collect())),
+            (
+                "otherwise",
+                self.otherwise
+                    .map(|otherwise| arena[otherwise].ast(arena))
+                    .into(),
+            ),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        let branches = self.branches.iter();
+        let mut columns: Vec<&IdentifierStatement> = branches
+            .flat_map(|&(condition, result)| {
+                [
+                    arena[condition].columns(arena),
+                    arena[result].columns(arena),
+                ]
+            })
+            .flatten()
+            .collect();
+        if let Some(otherwise) = self.otherwise {
+            columns.extend(arena[otherwise].columns(arena));
+        }
+        columns
+    }
+}
+
+fn operator(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Eq => "=",
+        TokenKind::NotEq => "<>",
+        TokenKind::Lt => "<",
+        TokenKind::Gt => ">",
+        TokenKind::LtEq => "<=",
+        TokenKind::GtEq => ">=",
+        TokenKind::And => "AND",
+        TokenKind::Or => "OR",
+        TokenKind::Not => "NOT",
+        _ => unreachable!("{} is not an operator", kind),
+    }
+}
+
+fn wrap(
+    arena: &Arena,
+    statement: &Stmt,
+    precedence: Precedence,
+    context: &mut Context,
+) -> Result<String> {
+    if statement.precedence() < precedence {
+        Ok(format!("({})", statement.eval(arena, context)?))
+    } else {
+        statement.eval(arena, context)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateStatement {
+    pub ident: IdentifierStatement,
+    pub filters: Vec<PairStatement>,
+    pub values: Vec<PairStatement>,
+    pub returning: Option<ReturningStatement>,
+}
+impl Statement for UpdateStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let values = self
+            .values
+            .iter()
+            .map(|statement| statement.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?;
+        let filters = self
+            .filters
+            .iter()
+            .map(|statement| statement.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?;
+        let (output, returning) = returning(arena, &self.returning, false, context)?;
+        let mut query = Writer::new(context);
+        query.clause(format!("UPDATE {}", self.ident.eval(arena, context)?));
+        query.list("SET", &values);
+        query.clauses.extend(output);
+        query.conditions(&filters);
+        query.clauses.extend(returning);
+        Ok(query.finish())
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Update".into()),
+            ("table", self.ident.ast(arena)),
+            (
+                "filters",
+                Json::Array(self.filters.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "values",
+                Json::Array(self.values.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "returning",
+                self.returning.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        let table = self.ident.table(schema)?;
+        for pair in self.filters.iter().chain(&self.values) {
+            pair.ident.column(table)?;
+        }
+        if let Some(returning) = &self.returning {
+            returning.check(table)?;
+        }
+        compare_pairs(arena, &self.filters, table)?;
+        compare_pairs(arena, &self.values, table)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeleteStatement {
+    pub ident: IdentifierStatement,
+    pub filters: Vec<PairStatement>,
+    pub condition: Option<NodeId>,
+    pub returning: Option<ReturningStatement>,
+}
+impl Statement for DeleteStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let table = self.ident.eval(arena, context)?;
+        if self.is_unfiltered() && !context.allow_full_delete {
+            return Err(error!(
+                Eval,
+                "refusing to delete every row from {}, pass --allow-full-delete", table
+            ));
+        }
+        let mut filters = self
+            .filters
+            .iter()
+            .map(|statement| statement.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?;
+        if let Some(condition) = self.condition {
+            if filters.is_empty() {
+                filters.push(arena[condition].eval(arena, context)?);
+            } else {
+                filters.push(wrap(arena, &arena[condition], Precedence::And, context)?);
+            }
+        }
+        let (output, returning) = returning(arena, &self.returning, true, context)?;
+        let mut query = Writer::new(context);
+        query.clause(format!("DELETE FROM {}", table));
+        query.clauses.extend(output);
+        query.conditions(&filters);
+        query.clauses.extend(returning);
+        Ok(query.finish())
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Delete".into()),
+            ("table", self.ident.ast(arena)),
+            (
+                "filters",
+                Json::Array(self.filters.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "condition",
+                self.condition
+                    .map(|condition| arena[condition].ast(arena))
+                    .into(),
+            ),
+            (
+                "returning",
+                self.returning.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        let table = self.ident.table(schema)?;
+        for pair in self.filters.iter() {
+            pair.ident.column(table)?;
+        }
+        if let Some(returning) = &self.returning {
+            returning.check(table)?;
+        }
+        compare_pairs(arena, &self.filters, table)?;
+        if let Some(condition) = self.condition {
+            typed(arena, &arena[condition], table)?;
+        }
+        Ok(())
+    }
+}
+impl DeleteStatement {
+    /// Whether the delete would remove every row of the table
+    pub fn is_unfiltered(&self) -> bool {
+        self.filters.is_empty() && self.condition.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InsertStatement {
+    pub ident: IdentifierStatement,
+    pub values: Vec<PairStatement>,
+    pub conflict: Option<ConflictStatement>,
+    pub returning: Option<ReturningStatement>,
+}
+impl Statement for InsertStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let columns = self
+            .values
+            .iter()
+            .map(|pair| pair.ident.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?
+            .join(", ");
+        let values = self
+            .values
+            .iter()
+            .map(|pair| arena[pair.value].bind(arena, &pair.ident.literal, context))
+            .collect::<Result<Vec<String>>>()?
+            .join(", ");
+        let conflict = self
+            .conflict
+            .as_ref()
+            .map(|conflict| conflict.eval(arena, context))
+            .transpose()?;
+        let (output, returning) = returning(arena, &self.returning, false, context)?;
+        let mut query = Writer::new(context);
+        if self.values.is_empty() {
+            query.clause(format!("INSERT INTO {}", self.ident.eval(arena, context)?));
+            query.clauses.extend(output);
+            query.clause(context.dialect.default_values().to_string());
+        } else {
+            query.clause(format!(
+                "INSERT INTO {} ({})",
+                self.ident.eval(arena, context)?,
+                columns
+            ));
+            query.clauses.extend(output);
+            query.clause(format!("VALUES ({})", values));
+        }
+        query.clauses.extend(conflict);
+        query.clauses.extend(returning);
+        Ok(query.finish())
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Insert".into()),
+            ("table", self.ident.ast(arena)),
+            (
+                "values",
+                Json::Array(self.values.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "conflict",
+                self.conflict.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+            (
+                "returning",
+                self.returning.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        let table = self.ident.table(schema)?;
+        for pair in self.values.iter() {
+            pair.ident.column(table)?;
+        }
+        if let Some(conflict) = &self.conflict {
+            for ident in conflict.columns.iter().chain(&conflict.update) {
+                ident.column(table)?;
+            }
+        }
+        if let Some(returning) = &self.returning {
+            returning.check(table)?;
+        }
+        compare_pairs(arena, &self.values, table)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReturningStatement {
+    pub columns: Vec<IdentifierStatement>,
+}
+impl ReturningStatement {
+    fn check(&self, table: &Table) -> Result<()> {
+        for ident in &self.columns {
+            ident.column(table)?;
+        }
+        Ok(())
+    }
+}
+impl Statement for ReturningStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|ident| ident.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?;
+        context
+            .dialect
+            .returning(&columns)
+            .ok_or_else(|| error!(Eval, "RETURNING is not supported by this dialect"))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Returning".into()),
+            (
+                "columns",
+                Json::Array(self.columns.iter().map(|node| node.ast(arena)).collect()),
+            ),
+        ])
+    }
+}
+
+// The OUTPUT clause for dialects that have one, otherwise the RETURNING clause
+fn returning(
+    arena: &Arena,
+    returning: &Option<ReturningStatement>,
+    deleted: bool,
+    context: &mut Context,
+) -> Result<(Option<String>, Option<String>)> {
+    let Some(returning) = returning else {
+        return Ok((None, None));
+    };
+    let columns = returning
+        .columns
+        .iter()
+        .map(|ident| ident.eval(arena, context))
+        .collect::<Result<Vec<String>>>()?;
+    match context.dialect.output(&columns, deleted) {
+        Some(output) => Ok((Some(output), None)),
+        None => Ok((None, Some(returning.eval(arena, context)?))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConflictStatement {
+    pub columns: Vec<IdentifierStatement>,
+    pub update: Vec<IdentifierStatement>,
+}
+impl Statement for ConflictStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|ident| ident.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?
+            .join(", ");
+        let update = self
+            .update
+            .iter()
+            .map(|ident| ident.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?;
+        context
+            .dialect
+            .upsert(&columns, &update)
+            .ok_or_else(|| error!(Eval, "ON CONFLICT is not supported by this dialect"))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Conflict".into()),
+            (
+                "columns",
+                Json::Array(self.columns.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "update",
+                Json::Array(self.update.iter().map(|node| node.ast(arena)).collect()),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderStatement {
+    pub ident: IdentifierStatement,
+    pub direction: Option<TokenKind>,
+}
+impl Statement for OrderStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        match self.direction {
+            Some(direction) => Ok(format!(
+                "{} {}",
+                self.ident.eval(arena, context)?,
+                direction
+            )),
+            None => self.ident.eval(arena, context),
+        }
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Order".into()),
+            ("column", self.ident.ast(arena)),
+            ("direction", self.direction.into()),
+        ])
+    }
+}
+
+const AGGREGATES: [&str; 5] = ["count", "sum", "avg", "min", "max"];
+
+const WINDOWS: [&str; 10] = [
+    "row_number",
+    "rank",
+    "dense_rank",
+    "percent_rank",
+    "cume_dist",
+    "ntile",
+    "lag",
+    "lead",
+    "first_value",
+    "last_value",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionCall {
+    pub function: IdentifierStatement,
+    pub arguments: Nodes,
+    pub window: Option<WindowStatement>,
+}
+impl Statement for FunctionCall {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let arguments = arena[self.arguments]
+            .iter()
+            .map(|statement| statement.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?
+            .join(", ");
+        let function = match context.keyword_case {
+            KeywordCase::Upper => self.function.literal.to_uppercase(),
+            KeywordCase::Lower => self.function.literal.to_lowercase(),
+        };
+        let call = format!("{}({})", function, arguments);
+        match &self.window {
+            Some(window) => Ok(format!("{} OVER ({})", call, window.eval(arena, context)?)),
+            None => Ok(call),
+        }
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Call".into()),
+            ("function", self.function.ast(arena)),
+            (
+                "arguments",
+                Json::Array(
+                    arena[self.arguments]
+                        .iter()
+                        .map(|argument| argument.ast(arena))
+                        .collect(),
+                ),
+            ),
+            (
+                "window",
+                self.window.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        let mut columns: Vec<&IdentifierStatement> = arena[self.arguments]
+            .iter()
+            .flat_map(|statement| statement.columns(arena))
+            .collect();
+        if let Some(window) = &self.window {
+            columns.extend(window.columns(arena));
+        }
+        columns
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowStatement {
+    pub partition: Vec<IdentifierStatement>,
+    pub ordering: Vec<OrderStatement>,
+}
+impl Statement for WindowStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let mut clauses = vec![];
+        if !self.partition.is_empty() {
+            let partition = self
+                .partition
+                .iter()
+                .map(|statement| statement.eval(arena, context))
+                .collect::<Result<Vec<String>>>()?;
+            clauses.push(format!("PARTITION BY {}", partition.join(", ")));
+        }
+        if !self.ordering.is_empty() {
+            let ordering = self
+                .ordering
+                .iter()
+                .map(|statement| statement.eval(arena, context))
+                .collect::<Result<Vec<String>>>()?;
+            clauses.push(format!("ORDER BY {}", ordering.join(", ")));
+        }
+        Ok(clauses.join(" "))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Window".into()),
+            (
+                "partition",
+                Json::Array(self.partition.iter().map(|node| node.ast(arena)).collect()),
+            ),
+            (
+                "ordering",
+                Json::Array(self.ordering.iter().map(|node| node.ast(arena)).collect()),
+            ),
+        ])
+    }
+    fn columns<'a>(&'a self, _arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        let ordering = self.ordering.iter().map(|order| &order.ident);
+        self.partition.iter().chain(ordering).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AliasStatement {
+    pub selection: NodeId,
+    pub alias: IdentifierStatement,
+}
+impl Statement for AliasStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        Ok(format!(
+            "{} AS {}",
+            arena[self.selection].eval(arena, context)?,
+            self.alias.eval(arena, context)?
+        ))
+    }
+    fn names(&self, arena: &Arena) -> Vec<String> {
+        let mut names = arena[self.selection].names(arena);
+        names.extend(self.alias.names(arena));
+        names
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Alias".into()),
+            ("selection", arena[self.selection].ast(arena)),
+            ("alias", self.alias.ast(arena)),
+        ])
+    }
+    fn columns<'a>(&'a self, arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        arena[self.selection].columns(arena)
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        arena[self.selection].check(arena, schema)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WithinStatement {
+    pub name: IdentifierStatement,
+    pub table: IdentifierStatement,
+}
+impl WithinStatement {
+    fn filter(&self, arena: &Arena, table: &str, context: &mut Context) -> Result<String> {
+        let Some(schema) = &context.schema else {
+            return Err(error!(
+                Eval,
+                "filtering {} in {} requires a schema with foreign keys", table, self.name.literal
+            ));
+        };
+        let (column, references) = schema.relation(&self.table.literal, table)?;
+        Ok(format!(
+            "{} IN (SELECT {} FROM {})",
+            context.dialect.identifier(&column),
+            context.dialect.identifier(&references),
+            self.name.eval(arena, context)?
+        ))
+    }
+}
+impl Statement for WithinStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        self.name.eval(arena, context)
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Within".into()),
+            ("name", self.name.ast(arena)),
+            ("table", self.table.ast(arena)),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommonTable {
+    pub name: IdentifierStatement,
+    pub query: Stmt,
+}
+impl CommonTable {
+    fn source(&self, arena: &Arena) -> IdentifierStatement {
+        let mut query = &self.query;
+        while let Stmt::Union(union) = query {
+            query = &arena[union.left];
+        }
+        match query {
+            Stmt::Dot(dot) => dot.ident.clone(),
+            _ => self.name.clone(),
+        }
+    }
+    fn table(&self, arena: &Arena, schema: &Schema) -> Table {
+        let mut query = &self.query;
+        while let Stmt::Union(union) = query {
+            query = &arena[union.left];
+        }
+        let (columns, types) = match query {
+            Stmt::Dot(dot) if dot.block.properties.is_empty() => schema
+                .table(&dot.ident.literal)
+                .map(|table| (table.columns.clone(), table.types.clone()))
+                .unwrap_or_default(),
+            Stmt::Dot(dot) => {
+                let source = schema.table(&dot.ident.literal).ok();
+                let columns: Vec<(String, Option<Type>)> = arena[dot.block.properties]
+                    .iter()
+                    .filter_map(|property| {
+                        let name = match property {
+                            Stmt::Alias(alias) => alias.alias.literal.to_string(),
+                            Stmt::Identifier(ident) => {
+                                ident.literal.rsplit('.').next()?.to_string()
+                            }
+                            _ => return None,
+                        };
+                        let kind = source.and_then(|table| typed(arena, property, table).ok()?);
+                        Some((name, kind))
+                    })
+                    .collect();
+                let types = columns
+                    .iter()
+                    .filter_map(|(name, kind)| Some((name.clone(), (*kind)?)))
+                    .collect();
+                (columns.into_iter().map(|(name, _)| name).collect(), types)
+            }
+            _ => Default::default(),
+        };
+        Table {
+            name: self.name.literal.to_string(),
+            columns,
+            foreign_keys: vec![],
+            types,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WithStatement {
+    pub tables: Vec<CommonTable>,
+    pub statement: NodeId,
+}
+impl Statement for WithStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let tables = self
+            .tables
+            .iter()
+            .map(|table| {
+                let query = match &table.query {
+                    Stmt::Dot(dot) => dot.select(arena, context)?.build(),
+                    Stmt::Union(union) => union.build(arena, context)?,
+                    statement => statement.eval(arena, context)?,
+                };
+                Ok(format!(
+                    "{} AS ({})",
+                    table.name.eval(arena, context)?,
+                    query
+                ))
+            })
+            .collect::<Result<Vec<String>>>()?;
+        let (separator, newline) = if context.pretty {
+            (",\n", "\n")
+        } else {
+            (", ", " ")
+        };
+        let statement = arena[self.statement].eval(arena, context)?;
+        Ok(format!(
+            "WITH {}{}{}",
+            tables.join(separator),
+            newline,
+            statement
+        ))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "With".into()),
+            (
+                "tables",
+                Json::Array(
+                    self.tables
+                        .iter()
+                        .map(|table| {
+                            Json::object([
+                                ("name", table.name.ast(arena)),
+                                ("query", table.query.ast(arena)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            ("statement", arena[self.statement].ast(arena)),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        let mut schema = schema.clone();
+        for table in &self.tables {
+            table.query.check(arena, &schema)?;
+            schema.insert(table.table(arena, &schema));
+        }
+        arena[self.statement].check(arena, &schema)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnionStatement {
+    pub left: NodeId,
+    pub right: DotStatement,
+    pub all: bool,
+}
+impl UnionStatement {
+    fn build(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let left = match &arena[self.left] {
+            Stmt::Union(union) => union.build(arena, context)?,
+            Stmt::Dot(dot) => dot.member(arena, context)?,
+            statement => statement.eval(arena, context)?,
+        };
+        let right = self.right.member(arena, context)?;
+        let operator = if self.all { "UNION ALL" } else { "UNION" };
+        let separator = if context.pretty { "\n" } else { " " };
+        Ok([left, operator.to_string(), right].join(separator))
+    }
+}
+impl Statement for UnionStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        Ok(format!(
+            "{}{}",
+            self.build(arena, context)?,
+            context.terminator
+        ))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Union".into()),
+            ("left", arena[self.left].ast(arena)),
+            ("right", self.right.ast(arena)),
+            ("all", self.all.into()),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        arena[self.left].check(arena, schema)?;
+        self.right.check(arena, schema)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubqueryStatement {
+    pub query: DotStatement,
+}
+impl Statement for SubqueryStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        Ok(format!("({})", self.query.select(arena, context)?.build()))
+    }
+    fn names(&self, _arena: &Arena) -> Vec<String> {
+        vec![]
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Subquery".into()),
+            ("query", self.query.ast(arena)),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        self.query.check(arena, schema)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpreadStatement {
+    pub fragment: IdentifierStatement,
+}
+impl Statement for SpreadStatement {
+    fn eval(&self, _arena: &Arena, _context: &mut Context) -> Result<String> {
+        Err(error!(
+            Eval,
+            "fragment {} was not expanded", self.fragment.literal
+        ))
+    }
+    fn names(&self, _arena: &Arena) -> Vec<String> {
+        vec![]
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Spread".into()),
+            ("fragment", self.fragment.ast(arena)),
+        ])
+    }
+}
+
+// The names of a statement reparsed from a slice, or after an edit, are where it was
+struct Moved {
+    removed: usize,
+    added: usize,
+}
+impl Fold for Moved {
+    fn fold_table(&mut self, table: IdentifierStatement) -> IdentifierStatement {
+        self.fold_identifier(table)
+    }
+    fn fold_identifier(&mut self, mut identifier: IdentifierStatement) -> IdentifierStatement {
+        identifier.span = identifier.span.map(|span| Span {
+            start: span.start + self.added - self.removed,
+            end: span.end + self.added - self.removed,
+        });
+        identifier
+    }
+}
+
+struct Expand<'a> {
+    fragments: &'a [(IdentifierStatement, BlockStatement)],
+    stack: Vec<String>,
+    error: Option<CompileError>,
+}
+impl Expand<'_> {
+    fn expand(&mut self, arena: &mut Arena, name: &str) -> Vec<Stmt> {
+        if self.stack.iter().any(|fragment| fragment == name) {
+            let mut cycle = self.stack.clone();
+            cycle.push(name.to_string());
+            self.fail(error!(
+                Parse,
+                "fragment {} is cyclic: {}",
+                name,
+                cycle.join(" -> ")
+            ));
+            return vec![];
+        }
+        let Some((_, block)) = self
+            .fragments
+            .iter()
+            .find(|(ident, _)| ident.literal == name)
+        else {
+            self.fail(error!(Parse, "fragment {} is not defined", name));
+            return vec![];
+        };
+        // every spread gets its own copy of the fragment, folds change nodes in place
+        let properties = arena[block.properties].to_vec();
+        let properties = properties
+            .iter()
+            .map(|property| arena.duplicate(property))
+            .collect::<Vec<Stmt>>();
+        let block = BlockStatement {
+            properties: arena.alloc_all(properties),
+        };
+        self.stack.push(name.to_string());
+        let block = self.fold_block(arena, block);
+        self.stack.pop();
+        arena[block.properties].to_vec()
+    }
+    fn fail(&mut self, error: CompileError) {
+        self.error.get_or_insert(error);
+    }
+}
+impl visit::Fold for Expand<'_> {
+    fn fold_block(&mut self, arena: &mut Arena, block: BlockStatement) -> BlockStatement {
+        let spread = |property: &Stmt| matches!(property, Stmt::Spread(_));
+        if !arena[block.properties].iter().any(spread) {
+            return BlockStatement {
+                properties: visit::fold_all(self, arena, block.properties),
+            };
+        }
+        // a spread changes how many properties the block has, so they move to a new slice
+        let mut properties = vec![];
+        for id in block.properties.ids() {
+            match arena.take(id) {
+                Stmt::Spread(spread) => {
+                    properties.extend(self.expand(arena, &spread.fragment.literal))
+                }
+                property => properties.push(self.fold_stmt(arena, property)),
+            }
+        }
+        BlockStatement {
+            properties: arena.alloc_all(properties),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoinStatement {
+    pub ident: IdentifierStatement,
+    pub block: BlockStatement,
+}
+impl Statement for JoinStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let mut qualify = Qualify(&self.ident.literal);
+        let columns = arena[self.block.properties]
+            .iter()
+            .map(|statement| match statement {
+                Stmt::Join(join) => join.eval(arena, context),
+                Stmt::Wildcard(wildcard) => {
+                    let table = &self.ident.literal;
+                    wildcard.expand(table, Some(table), context)
+                }
+                // qualified in a scratch arena, the program's own nodes are shared with its columns
+                _ => {
+                    let mut scratch = Arena::default();
+                    let statement = arena.copy(statement, &mut scratch);
+                    let qualified = qualify.fold_stmt(&mut scratch, statement);
+                    qualified.eval(&scratch, context)
+                }
+            })
+            .collect::<Result<Vec<String>>>()?;
+        Ok(columns
+            .into_iter()
+            .filter(|column| !column.is_empty())
+            .collect::<Vec<String>>()
+            .join(", "))
+    }
+    fn names(&self, _arena: &Arena) -> Vec<String> {
+        vec![]
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Join".into()),
+            ("table", self.ident.ast(arena)),
+            ("block", self.block.ast(arena)),
+        ])
+    }
+    fn check(&self, arena: &Arena, schema: &Schema) -> Result<()> {
+        let table = self.ident.table(schema)?;
+        for statement in &arena[self.block.properties] {
+            statement.check(arena, schema)?;
+            for column in statement.columns(arena) {
+                column.column(table)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The tables a statement reads or writes, leaving out the ones its with clause defines
+#[derive(Default)]
+struct Tables {
+    tables: Vec<String>,
+    defined: Vec<String>,
+}
+impl Tables {
+    fn touched(self) -> Vec<String> {
+        let defined = self.defined;
+        let tables = self.tables.into_iter();
+        tables.filter(|table| !defined.contains(table)).collect()
+    }
+}
+impl visit::Visitor for Tables {
+    fn visit_stmt(&mut self, arena: &Arena, statement: &Stmt) {
+        if let Stmt::With(with) = statement {
+            let names = with
+                .tables
+                .iter()
+                .map(|table| table.name.literal.to_string());
+            self.defined.extend(names);
+        }
+        visit::walk_stmt(self, arena, statement);
+    }
+
+    fn visit_table(&mut self, table: &IdentifierStatement) {
+        if !self
+            .tables
+            .iter()
+            .any(|name| table.literal == name.as_str())
+        {
+            self.tables.push(table.literal.to_string());
+        }
+    }
+}
+
+struct References<'a>(&'a [CommonTable], &'a mut Vec<Symbol>);
+impl visit::Visitor for References<'_> {
+    fn visit_table(&mut self, table: &IdentifierStatement) {
+        let defined = self.0.iter().any(|candidate| candidate.name == *table);
+        if defined && !self.1.contains(&table.literal) {
+            self.1.push(table.literal);
+        }
+    }
+}
+
+struct Qualify<'a>(&'a str);
+impl visit::Fold for Qualify<'_> {
+    fn fold_identifier(&mut self, mut identifier: IdentifierStatement) -> IdentifierStatement {
+        if !identifier.literal.contains('.') {
+            identifier.literal = format!("{}.{}", self.0, identifier.literal).into();
+        }
+        identifier
+    }
+}
+
+fn joins(
+    arena: &Arena,
+    block: &BlockStatement,
+    from: &str,
+    parent: &str,
+    context: &mut Context,
+) -> Result<Vec<String>> {
+    let mut clauses = vec![];
+    for statement in &arena[block.properties] {
+        let Stmt::Join(join) = statement else {
+            continue;
+        };
+        let table = &join.ident.literal;
+        let Some(schema) = &context.schema else {
+            return Err(error!(
+                Eval,
+                "joining {} requires a schema with foreign keys", table
+            ));
+        };
+        let (column, references) = schema.relation(from, table)?;
+        clauses.push(format!(
+            "JOIN {} ON {} = {}",
+            context.dialect.identifier(table),
+            context.dialect.identifier(&format!("{}.{}", table, column)),
+            context
+                .dialect
+                .identifier(&format!("{}.{}", parent, references))
+        ));
+        clauses.extend(joins(arena, &join.block, table, table, context)?);
+    }
+    Ok(clauses)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+    Cross,
+}
+impl JoinKind {
+    fn from_word(word: &str) -> Option<Self> {
+        Some(match word {
+            "join" | "inner" => JoinKind::Inner,
+            "left" => JoinKind::Left,
+            "right" => JoinKind::Right,
+            "full" => JoinKind::Full,
+            "cross" => JoinKind::Cross,
+            _ => return None,
+        })
+    }
+    fn word(&self) -> &'static str {
+        match self {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+            JoinKind::Right => "right",
+            JoinKind::Full => "full",
+            JoinKind::Cross => "cross",
+        }
+    }
+    fn keyword(&self) -> &'static str {
+        match self {
+            JoinKind::Inner => "JOIN",
+            JoinKind::Left => "LEFT JOIN",
+            JoinKind::Right => "RIGHT JOIN",
+            JoinKind::Full => "FULL JOIN",
+            JoinKind::Cross => "CROSS JOIN",
+        }
+    }
+}
+
+/// A join written out with its condition, unlike the ones nested blocks get from the schema
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoinClause {
+    pub kind: JoinKind,
+    pub table: IdentifierStatement,
+    pub alias: Option<IdentifierStatement>,
+    pub condition: Option<NodeId>,
+}
+impl Statement for JoinClause {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        let mut clause = format!(
+            "{} {}",
+            self.kind.keyword(),
+            self.table.eval(arena, context)?
+        );
+        if let Some(alias) = &self.alias {
+            clause = format!("{} {}", clause, alias.eval(arena, context)?);
+        }
+        if let Some(condition) = self.condition {
+            clause = format!("{} ON {}", clause, arena[condition].eval(arena, context)?);
+        }
+        Ok(clause)
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "JoinClause".into()),
+            ("kind", self.kind.word().into()),
+            ("table", self.table.ast(arena)),
+            (
+                "alias",
+                self.alias.as_ref().map(|node| node.ast(arena)).into(),
+            ),
+            (
+                "condition",
+                self.condition
+                    .map(|condition| arena[condition].ast(arena))
+                    .into(),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WildcardStatement {
+    pub exclude: Vec<IdentifierStatement>,
+}
+impl WildcardStatement {
+    fn expand(&self, table: &str, prefix: Option<&str>, context: &mut Context) -> Result<String> {
+        let qualify = |column: &str| match prefix {
+            Some(prefix) => format!("{}.{}", prefix, column),
+            None => column.to_string(),
+        };
+        if self.exclude.is_empty() {
+            return Ok(match prefix {
+                Some(prefix) => format!("{}.*", context.dialect.identifier(prefix)),
+                None => "*".to_string(),
+            });
+        }
+        let Some(schema) = &context.schema else {
+            return Err(error!(Eval, "excluding columns from * requires a schema"));
+        };
+        let columns = schema
+            .table(table)?
+            .columns
+            .iter()
+            .filter(|column| {
+                !self
+                    .exclude
+                    .iter()
+                    .any(|ident| ident.literal == column.as_str())
+            })
+            .map(|column| context.dialect.identifier(&qualify(column)))
+            .collect::<Vec<String>>();
+        if columns.is_empty() {
+            return Err(error!(Eval, "* excludes every column of {}", table));
+        }
+        Ok(columns.join(", "))
+    }
+}
+impl Statement for WildcardStatement {
+    fn eval(&self, _arena: &Arena, _context: &mut Context) -> Result<String> {
+        if !self.exclude.is_empty() {
+            return Err(error!(Eval, "* can only exclude columns of a table"));
+        }
+        Ok("*".to_string())
+    }
+    fn names(&self, _arena: &Arena) -> Vec<String> {
+        vec![]
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Wildcard".into()),
+            (
+                "exclude",
+                Json::Array(self.exclude.iter().map(|node| node.ast(arena)).collect()),
+            ),
+        ])
+    }
+    fn columns<'a>(&'a self, _arena: &'a Arena) -> Vec<&'a IdentifierStatement> {
+        self.exclude.iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockStatement {
+    pub properties: Nodes,
+}
+impl Statement for BlockStatement {
+    fn eval(&self, arena: &Arena, context: &mut Context) -> Result<String> {
+        Ok(arena[self.properties]
+            .iter()
+            .map(|statement| statement.eval(arena, context))
+            .collect::<Result<Vec<String>>>()?
+            .join(", "))
+    }
+    fn ast(&self, arena: &Arena) -> Json {
+        Json::object([
+            ("type", "Block".into()),
+            (
+                "properties",
+                Json::Array(
+                    arena[self.properties]
+                        .iter()
+                        .map(|property| property.ast(arena))
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
+enum Precedence {
+    Lowest,
+    Or,
+    And,
+    Not,
+    Equals,
+    LessGreater,
+    Sum,
+    Prefix,
+}
+
+fn precedence(kind: TokenKind) -> Precedence {
+    match kind {
+        TokenKind::Or => Precedence::Or,
+        TokenKind::And => Precedence::And,
+        TokenKind::Eq
+        | TokenKind::NotEq
+        | TokenKind::Is
+        | TokenKind::In
+        | TokenKind::Between
+        | TokenKind::Like
+        | TokenKind::Ilike => Precedence::Equals,
+        TokenKind::Lt | TokenKind::Gt | TokenKind::LtEq | TokenKind::GtEq => {
+            Precedence::LessGreater
+        }
+        TokenKind::Plus | TokenKind::Minus => Precedence::Sum,
+        _ => Precedence::Lowest,
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    current_token: Token<'a>,
+    current_span: Span,
+    peek_token: Token<'a>,
+    peek_span: Span,
+    errors: Vec<CompileError>,
+    scopes: Vec<Symbol>,
+    definitions: Vec<CommonTable>,
+    references: Vec<Symbol>,
+    fragments: Vec<(IdentifierStatement, BlockStatement)>,
+    warnings: Vec<lint::Warning>,
+    arena: Arena,
+}
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        let eof = Token {
+            kind: TokenKind::Eof,
+            literal: "",
+        };
+        let mut parser = Self {
+            lexer: Lexer::new(input),
+            current_token: eof,
+            current_span: Span::default(),
+            peek_token: eof,
+            peek_span: Span::default(),
+            errors: vec![],
+            scopes: vec![],
+            definitions: vec![],
+            references: vec![],
+            fragments: vec![],
+            warnings: vec![],
+            arena: Arena::default(),
+        };
+        for _ in 0..2 {
+            if let Err(error) = parser.next_token() {
+                parser.errors.push(error);
+            }
+        }
+        parser
+    }
+    fn run(&mut self) -> Program {
+        let program = self.parse_program();
+        let mut program = self.expand(program);
+        program.standalone =
+            self.errors.is_empty() && self.definitions.is_empty() && self.fragments.is_empty();
+        program
+    }
+    fn parse_program(&mut self) -> Program {
+        let mut program = Program {
+            statements: vec![],
+            arena: Arena::default(),
+            spans: vec![],
+            names: vec![],
+            warnings: vec![],
+            source: self.lexer.input.to_string(),
+            standalone: false,
+        };
+        loop {
+            match self.current_token.kind {
+                TokenKind::Eof => break,
+                TokenKind::Illegal => {
+                    self.synchronize(self.current_span.start);
+                    continue;
+                }
+                TokenKind::Let | TokenKind::Fragment => {
+                    let start = self.current_span.start;
+                    let defined = if self.current_token.kind == TokenKind::Let {
+                        self.parse_let()
+                    } else {
+                        self.parse_fragment()
+                    };
+                    if let Err(error) = defined {
+                        self.errors.push(error.at(self.current_span));
+                        self.synchronize(start);
+                    } else if let Err(error) = self.next_token() {
+                        self.errors.push(error);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            let name = match self.parse_header(&program.names) {
+                Ok(name) => name,
+                Err(error) => {
+                    let start = self.current_span.start;
+                    self.errors.push(error.at(self.current_span));
+                    self.synchronize(start);
+                    continue;
+                }
+            };
+            let start = self.current_span.start;
+            match self.parse_statement() {
+                Ok(statement) => {
+                    program.statements.push(statement);
+                    program.names.push(name);
+                    program.spans.push(Span {
+                        start,
+                        end: self.current_span.end,
+                    });
+                }
+                Err(error) => {
+                    self.errors.push(error.at(self.current_span));
+                    self.synchronize(start);
+                    continue;
+                }
+            }
+            if let Err(error) = self.next_token() {
+                self.errors.push(error);
+            }
+        }
+        program.warnings = std::mem::take(&mut self.warnings);
+        // fragments and common tables stay behind in the parser, but the ids they hold are the same
+        program.arena = std::mem::take(&mut self.arena);
+        program
+    }
+    fn is_header(&self) -> bool {
+        self.current_token.literal == "query" && self.peek_token.kind == TokenKind::Ident
+    }
+    fn parse_header(&mut self, names: &[Option<String>]) -> Result<Option<String>> {
+        if self.current_token.kind != TokenKind::Ident || !self.is_header() {
+            return Ok(None);
+        }
+        self.next_token()?;
+        let name = self.current_token.literal.to_string();
+        if names.iter().flatten().any(|named| *named == name) {
+            return Err(error!(Parse, "query {} is named twice", name));
+        }
+        self.expect_peek(TokenKind::Colon)?;
+        self.next_token()?;
+        Ok(Some(name))
+    }
+    fn synchronize(&mut self, start: usize) {
+        loop {
+            let boundary = match self.current_token.kind {
+                TokenKind::Eof => return,
+                TokenKind::Dot
+                | TokenKind::Plus
+                | TokenKind::Tilde
+                | TokenKind::Let
+                | TokenKind::Fragment => true,
+                TokenKind::Minus => self.peek_token.kind == TokenKind::Ident,
+                TokenKind::Ident => self.is_header(),
+                _ => false,
+            };
+            if boundary && self.current_span.start > start {
+                return;
+            }
+            if let Err(error) = self.next_token() {
+                self.errors.push(error);
+            }
+        }
+    }
+    fn next_token(&mut self) -> Result<()> {
+        self.current_token = self.peek_token;
+        self.current_span = self.peek_span;
+        let token = self.lexer.next_token();
+        self.peek_span = self.lexer.span();
+        match token {
+            Ok(token) => {
+                self.peek_token = token;
+                Ok(())
+            }
+            Err(error) => {
+                self.peek_token = Token {
+                    kind: TokenKind::Illegal,
+                    literal: "",
+                };
+                Err(error)
+            }
+        }
+    }
+    fn parse_fragment(&mut self) -> Result<()> {
+        self.expect_identifier()?;
+        let name = self.parse_identifier();
+        if self.fragments.iter().any(|(fragment, _)| *fragment == name) {
+            return Err(error!(
+                Parse,
+                "fragment {} is already defined", name.literal
+            ));
+        }
+        self.expect_peek(TokenKind::LBrace)?;
+        let block = self.parse_block_statement()?;
+        self.fragments.push((name, block));
+        Ok(())
+    }
+    fn expand(&mut self, program: Program) -> Program {
+        let mut arena = program.arena;
+        let mut expanded = Program {
+            statements: vec![],
+            arena: Arena::default(),
+            spans: vec![],
+            names: vec![],
+            warnings: program.warnings,
+            source: program.source,
+            standalone: false,
+        };
+        let statements = program.statements.into_iter().zip(program.spans);
+        for ((statement, span), name) in statements.zip(program.names) {
+            let mut expand = Expand {
+                fragments: &self.fragments,
+                stack: vec![],
+                error: None,
+            };
+            let statement = expand.fold_stmt(&mut arena, statement);
+            match expand.error {
+                Some(error) => self.errors.push(error.at(span)),
+                None => {
+                    expanded.statements.push(statement);
+                    expanded.spans.push(span);
+                    expanded.names.push(name);
+                }
+            }
+        }
+        expanded.arena = arena;
+        expanded
+    }
+    fn parse_let(&mut self) -> Result<()> {
+        self.expect_identifier()?;
+        let name = self.parse_identifier();
+        if self.definitions.iter().any(|table| table.name == name) {
+            return Err(error!(Parse, "{} is already defined", name.literal));
+        }
+        self.expect_peek(TokenKind::Eq)?;
+        self.expect_peek(TokenKind::Dot)?;
+        self.references.clear();
+        let query = self.parse_union()?;
+        if self.peek_token.kind == TokenKind::Semicolon {
+            self.next_token()?;
+        }
+        self.definitions.push(CommonTable { name, query });
+        Ok(())
+    }
+    fn parse_statement(&mut self) -> Result<Stmt> {
+        self.references.clear();
+        let statement = self.parse_plain_statement()?;
+        if self.references.is_empty() {
+            return Ok(statement);
+        }
+        let mut needed = std::mem::take(&mut self.references);
+        let mut tables = vec![];
+        for table in self.definitions.iter().rev() {
+            if needed.contains(&table.name.literal) {
+                let mut visitor = References(&self.definitions, &mut needed);
+                visitor.visit_stmt(&self.arena, &table.query);
+                // each statement gets its own copy, so folding one doesn't change the others
+                let query = self.arena.duplicate(&table.query);
+                tables.insert(
+                    0,
+                    CommonTable {
+                        name: table.name.clone(),
+                        query,
+                    },
+                );
+            }
+        }
+        Ok(Stmt::With(WithStatement {
+            tables,
+            statement: self.arena.alloc(statement),
+        }))
+    }
+    fn parse_plain_statement(&mut self) -> Result<Stmt> {
+        Ok(match self.current_token.kind {
+            TokenKind::Dot => {
+                let start = self.current_span.start;
+                self.expect_identifier()?;
+                let ident = self.parse_identifier();
+                if self.peek_token.kind == TokenKind::Ident && self.peek_token.literal == "insert" {
+                    self.next_token()?;
+                    Stmt::Insert(self.parse_insert_into(start, ident)?)
+                } else if self.peek_token.kind == TokenKind::Ident
+                    && self.peek_token.literal == "delete"
+                {
+                    self.next_token()?;
+                    Stmt::Delete(self.parse_delete_from(ident)?)
+                } else {
+                    let first = self.parse_select(ident)?;
+                    self.parse_union_from(first)?
+                }
+            }
+            TokenKind::Plus => Stmt::Insert(self.parse_insert()?),
+            TokenKind::Tilde => Stmt::Update(self.parse_update()?),
+            TokenKind::Minus => Stmt::Delete(self.parse_delete()?),
+            TokenKind::LBrace => Stmt::Block(self.parse_block_statement()?),
+            _ => return Err(error!(Parse, "unknown token {}", self.current_token.kind)),
+        })
+    }
+    fn parse_union(&mut self) -> Result<Stmt> {
+        let first = self.parse_dot()?;
+        self.parse_union_from(first)
+    }
+    fn parse_union_from(&mut self, first: DotStatement) -> Result<Stmt> {
+        let mut statement = Stmt::Dot(first);
+        while matches!(self.peek_token.kind, TokenKind::Pipe | TokenKind::Union) {
+            self.next_token()?;
+            let all = self.peek_token.kind == TokenKind::All;
+            if all {
+                self.next_token()?;
+            }
+            self.expect_peek(TokenKind::Dot)?;
+            let right = self.parse_dot()?;
+            statement = Stmt::Union(UnionStatement {
+                left: self.arena.alloc(statement),
+                right,
+                all,
+            });
+        }
+        Ok(statement)
+    }
+    fn parse_block_statement(&mut self) -> Result<BlockStatement> {
+        self.next_token()?;
+        let mut properties: Vec<Stmt> = vec![];
+        let mut selected: Vec<Symbol> = vec![];
+        loop {
+            match self.current_token.kind {
+                TokenKind::RBrace => break,
+                TokenKind::Eof => return Err(error!(Parse, "expected RBRACK, got EOF")),
+                TokenKind::Comma => {
+                    return Err(
+                        error!(Parse, "expected a selection, got COMMA").at(self.current_span)
+                    )
+                }
+                _ => {}
+            }
+            if self.current_token.kind == TokenKind::Ident
+                && self.peek_token.kind == TokenKind::LBrace
+            {
+                let ident = self.parse_identifier();
+                self.next_token()?;
+                self.scopes.push(ident.literal);
+                let block = self.parse_block_statement();
+                self.scopes.pop();
+                properties.push(Stmt::Join(JoinStatement {
+                    ident,
+                    block: block?,
+                }));
+            } else if self.current_token.kind == TokenKind::Asterisk {
+                properties.push(Stmt::Wildcard(WildcardStatement { exclude: vec![] }));
+            } else if self.current_token.kind == TokenKind::Bang {
+                self.expect_identifier()?;
+                let ident = self.parse_identifier();
+                let wildcard = properties.iter_mut().find_map(|property| match property {
+                    Stmt::Wildcard(wildcard) => Some(wildcard),
+                    _ => None,
+                });
+                let Some(wildcard) = wildcard else {
+                    return Err(error!(
+                        Parse,
+                        "!{} excludes a column from *, but the block does not select *",
+                        ident.literal
+                    ));
+                };
+                wildcard.exclude.push(ident);
+            } else if self.current_token.kind == TokenKind::Spread {
+                self.expect_identifier()?;
+                properties.push(Stmt::Spread(SpreadStatement {
+                    fragment: self.parse_identifier(),
+                }));
+            } else {
+                let start = self.current_span.start;
+                let mut selection = match self.parse_selection()? {
+                    Stmt::Identifier(ident) if ident.literal == "count" => {
+                        count_all(&mut self.arena)
+                    }
+                    selection => selection,
+                };
+                if self.peek_token.kind == TokenKind::As {
+                    self.next_token()?;
+                    self.expect_identifier()?;
+                    selection = Stmt::Alias(AliasStatement {
+                        selection: self.arena.alloc(selection),
+                        alias: self.parse_identifier(),
+                    });
+                }
+                let name = match &selection {
+                    Stmt::Identifier(identifier) => Some(identifier.literal),
+                    Stmt::Alias(alias) => Some(alias.alias.literal),
+                    _ => None,
+                };
+                if let Some(name) = name.filter(|name| selected.contains(name)) {
+                    let span = Span {
+                        start,
+                        end: self.current_span.end,
+                    };
+                    return Err(error!(Parse, "column {} is selected twice", name).at(span));
+                }
+                selected.extend(name);
+                properties.push(selection);
+            }
+            self.next_token()?;
+            match self.current_token.kind {
+                TokenKind::Comma => self.next_token()?,
+                TokenKind::RBrace | TokenKind::Eof => {}
+                _ => {
+                    return Err(error!(
+                        Parse,
+                        "missing comma before {}", self.current_token.literal
+                    )
+                    .at(self.current_span))
+                }
+            }
+        }
+        Ok(BlockStatement {
+            properties: self.arena.alloc_all(properties),
+        })
+    }
+    fn parse_selection(&mut self) -> Result<Stmt> {
+        if self.current_token.kind == TokenKind::Ident && self.is_temporal() {
+            return self.parse_temporal();
+        }
+        if self.current_token.kind == TokenKind::Ident && self.peek_token.kind == TokenKind::LParen
+        {
+            return Ok(Stmt::Call(self.parse_function_call()?));
+        }
+        if self.current_token.kind == TokenKind::Case {
+            return self.parse_case();
+        }
+        if self.current_token.kind == TokenKind::LParen {
+            self.expect_peek(TokenKind::Dot)?;
+            let query = self.parse_dot()?;
+            self.expect_peek(TokenKind::RParen)?;
+            return Ok(Stmt::Subquery(SubqueryStatement { query }));
+        }
+        Ok(Stmt::Identifier(self.parse_identifier()))
+    }
+    fn parse_function_call(&mut self) -> Result<FunctionCall> {
+        let function = self.parse_identifier();
+        let name = function.literal.as_str();
+        let windowed = WINDOWS.contains(&name);
+        if !windowed && !AGGREGATES.contains(&name) {
+            let names = AGGREGATES.iter().chain(WINDOWS.iter()).copied();
+            return Err(error!(
+                Parse,
+                "unknown function {}{}",
+                function.literal,
+                schema::suggest(name, names)
+            ));
+        }
+        self.expect_peek(TokenKind::LParen)?;
+        let mut arguments: Vec<Stmt> = vec![];
+        loop {
+            self.next_token()?;
+            match self.current_token.kind {
+                TokenKind::RParen => break,
+                TokenKind::Comma => continue,
+                TokenKind::Ident => arguments.push(self.parse_selection()?),
+                TokenKind::Asterisk if name == "count" => {
+                    arguments.push(Stmt::Wildcard(WildcardStatement { exclude: vec![] }))
+                }
+                TokenKind::Number => arguments.push(Stmt::Literal(self.parse_literal())),
+                kind => return Err(error!(Parse, "unexpected {} in arguments", kind)),
+            }
+        }
+        let mut window = None;
+        if self.peek_token.kind == TokenKind::Over {
+            self.next_token()?;
+            window = Some(self.parse_window()?);
+        } else if windowed {
+            return Err(error!(
+                Parse,
+                "{} requires an over clause", function.literal
+            ));
+        }
+        Ok(FunctionCall {
+            function,
+            arguments: self.arena.alloc_all(arguments),
+            window,
+        })
+    }
+    fn parse_window(&mut self) -> Result<WindowStatement> {
+        self.expect_peek(TokenKind::LParen)?;
+        let mut partition = vec![];
+        if self.peek_token.kind == TokenKind::Partition {
+            self.next_token()?;
+            self.expect_peek(TokenKind::By)?;
+            loop {
+                self.expect_identifier()?;
+                partition.push(self.parse_identifier());
+                if self.peek_token.kind != TokenKind::Comma {
+                    break;
+                }
+                self.next_token()?;
+            }
+        }
+        let mut ordering = vec![];
+        if self.peek_token.kind == TokenKind::Order {
+            self.next_token()?;
+            ordering = self.parse_order_statements()?;
+        }
+        self.expect_peek(TokenKind::RParen)?;
+        Ok(WindowStatement {
+            partition,
+            ordering,
+        })
+    }
+    fn parse_dot(&mut self) -> Result<DotStatement> {
+        self.expect_identifier()?;
+        let ident = self.parse_identifier();
+        self.parse_select(ident)
+    }
+    fn parse_select(&mut self, mut ident: IdentifierStatement) -> Result<DotStatement> {
+        self.reference(ident.literal);
+        let mut filters = vec![];
+        if self.peek_token.kind == TokenKind::LParen {
+            self.next_token()?;
+            filters = self.parse_pair_statements(TokenKind::RParen)?;
+        }
+        let alias = self.parse_alias()?;
+        let mut joins = vec![];
+        while self.peek_token.kind == TokenKind::Ident
+            && JoinKind::from_word(self.peek_token.literal).is_some()
+        {
+            joins.push(self.parse_join()?);
+        }
+        let mut within = None;
+        if self.peek_token.kind == TokenKind::In {
+            self.next_token()?;
+            self.expect_identifier()?;
+            let name = self.parse_identifier();
+            let Some(table) = self
+                .definitions
+                .iter()
+                .find(|table| table.name == name)
+                .map(|table| table.source(&self.arena))
+            else {
+                return Err(error!(Parse, "{} is not defined with let", name.literal));
+            };
+            self.reference(name.literal);
+            within = Some(WithinStatement { name, table });
+        }
+        let mut condition = None;
+        if self.peek_token.kind == TokenKind::Where {
+            self.next_token()?;
+            let filter = self.parse_where()?;
+            condition = Some(self.arena.alloc(filter));
+        }
+        let mut distinct = false;
+        let mut distinct_on = vec![];
+        if self.peek_token.kind == TokenKind::Distinct {
+            self.next_token()?;
+            distinct = true;
+            if self.peek_token.kind == TokenKind::Ident && self.peek_token.literal == "on" {
+                self.next_token()?;
+                self.expect_peek(TokenKind::LParen)?;
+                distinct_on = self.parse_identifiers(TokenKind::RParen)?;
+            }
+        }
+        let mut grouping = vec![];
+        let mut ordering = vec![];
+        // DISTINCT ON keeps the first row of each group, so the order can lead
+        if self.peek_token.kind == TokenKind::Order {
+            self.next_token()?;
+            ordering = self.parse_order_statements()?;
+        }
+        let mut limit = None;
+        let mut offset = None;
+        while self.peek_token.kind == TokenKind::Directive {
+            self.next_token()?;
+            match self.current_token.literal {
+                "distinct" => distinct = true,
+                "limit" => {
+                    self.expect_peek(TokenKind::LParen)?;
+                    limit = Some(self.parse_number()?);
+                    self.expect_peek(TokenKind::RParen)?;
+                }
+                "offset" => {
+                    self.expect_peek(TokenKind::LParen)?;
+                    offset = Some(self.parse_number()?);
+                    self.expect_peek(TokenKind::RParen)?;
+                }
+                "order" => {
+                    self.expect_peek(TokenKind::LParen)?;
+                    ordering = self.parse_order_directive()?;
+                }
+                directive => {
+                    let names = ["distinct", "limit", "offset", "order"].into_iter();
+                    let suggestion = schema::closest(directive, names)
+                        .map(|name| format!(", did you mean '@{}'?", name))
+                        .unwrap_or_default();
+                    return Err(error!(
+                        Parse,
+                        "unknown directive @{}{}", directive, suggestion
+                    ));
+                }
+            }
+        }
+        let counted = match self.peek_token.kind {
+            TokenKind::LBrace => None,
+            _ => ident.literal.strip_suffix(".count").map(Symbol::from),
+        };
+        let block = if let Some(table) = counted {
+            ident.literal = table;
+            self.reference(table);
+            let count = count_all(&mut self.arena);
+            BlockStatement {
+                properties: self.arena.alloc_all([count]),
+            }
+        } else {
+            self.expect_peek(TokenKind::LBrace)?;
+            let start = self.current_span.start;
+            if self.peek_token.kind == TokenKind::Distinct {
+                self.next_token()?;
+                distinct = true;
+            }
+            let scope = alias.as_ref().unwrap_or(&ident).literal;
+            self.scopes.push(scope);
+            let block = self.parse_block_statement();
+            self.scopes.pop();
+            let block = block?;
+            if block.properties.is_empty() {
+                self.warnings.push(lint::Warning {
+                    lint: "empty-block",
+                    message: format!(
+                        "the block of {} is empty, write {{ * }} to select every column",
+                        ident.literal
+                    ),
+                    span: Some(Span {
+                        start,
+                        end: self.current_span.end,
+                    }),
+                });
+            }
+            block
+        };
+        loop {
+            let kind = self.peek_token.kind;
+            let duplicate = match kind {
+                TokenKind::Order => !ordering.is_empty(),
+                TokenKind::Limit => limit.is_some(),
+                TokenKind::Offset => offset.is_some(),
+                _ => false,
+            };
+            if duplicate {
+                return Err(error!(Parse, "{} is given twice", kind).at(self.peek_span));
+            }
+            match kind {
+                TokenKind::Group => {
+                    self.next_token()?;
+                    grouping = self.parse_group_statements(&block)?;
+                }
+                TokenKind::Order => {
+                    self.next_token()?;
+                    ordering = self.parse_order_statements()?;
+                }
+                TokenKind::Limit => {
+                    self.next_token()?;
+                    limit = Some(self.parse_number()?);
+                }
+                TokenKind::Offset => {
+                    self.next_token()?;
+                    offset = Some(self.parse_number()?);
+                }
+                TokenKind::Ident => match self.unknown_clause()? {
+                    Some(error) => return Err(error),
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        Ok(DotStatement {
+            ident,
+            filters,
+            alias,
+            joins,
+            condition,
+            within,
+            distinct,
+            distinct_on,
+            block,
+            grouping,
+            ordering,
+            limit,
+            offset,
+        })
+    }
+    // A misspelled clause after a block, told apart from the start of the next statement
+    fn unknown_clause(&mut self) -> Result<Option<CompileError>> {
+        let clauses = ["group", "order", "limit", "offset"].into_iter();
+        let Some(clause) = schema::closest(self.peek_token.literal, clauses) else {
+            return Ok(None);
+        };
+        self.next_token()?;
+        let start = self.current_span.start;
+        let mut written = self.current_token.literal.to_string();
+        if self.peek_token.kind == TokenKind::By {
+            self.next_token()?;
+            written.push_str(" by");
+        }
+        let expected = match clause {
+            "group" | "order" => format!("{} by", clause),
+            _ => clause.to_string(),
+        };
+        Ok(Some(
+            error!(
+                Parse,
+                "unknown clause '{}', did you mean '{}'?", written, expected
+            )
+            .at(Span {
+                start,
+                end: self.current_span.end,
+            }),
+        ))
+    }
+    // Join words can't be aliases, so `.users left join` starts a join
+    fn parse_alias(&mut self) -> Result<Option<IdentifierStatement>> {
+        let token = self.peek_token;
+        if token.kind != TokenKind::Ident
+            || token.literal == "on"
+            || token.literal == "insert"
+            || token.literal == "delete"
+            || JoinKind::from_word(token.literal).is_some()
+        {
+            return Ok(None);
+        }
+        self.next_token()?;
+        Ok(Some(self.parse_identifier()))
+    }
+    fn parse_join(&mut self) -> Result<JoinClause> {
+        self.next_token()?;
+        let word = self.current_token.literal;
+        let kind = JoinKind::from_word(word).unwrap_or(JoinKind::Inner);
+        if word != "join" {
+            if matches!(kind, JoinKind::Left | JoinKind::Right | JoinKind::Full)
+                && self.peek_token.literal == "outer"
+            {
+                self.next_token()?;
+            }
+            self.expect_word("join")?;
+        }
+        self.expect_peek(TokenKind::Dot)?;
+        self.expect_identifier()?;
+        let table = self.parse_identifier();
+        self.reference(table.literal);
+        let alias = self.parse_alias()?;
+        let mut condition = None;
+        if kind != JoinKind::Cross {
+            self.expect_word("on")?;
+            let on = self.parse_where()?;
+            condition = Some(self.arena.alloc(on));
+        }
+        Ok(JoinClause {
+            kind,
+            table,
+            alias,
+            condition,
+        })
+    }
+    fn parse_insert(&mut self) -> Result<InsertStatement> {
+        let start = self.current_span.start;
+        self.expect_identifier()?;
+        let ident = self.parse_identifier();
+        self.parse_insert_into(start, ident)
+    }
+    // `+users { ... }` and `.users insert { ... }` are the same insert
+    fn parse_insert_into(
+        &mut self,
+        start: usize,
+        ident: IdentifierStatement,
+    ) -> Result<InsertStatement> {
+        self.expect_peek(TokenKind::LBrace)?;
+        let values = self.parse_pair_statements(TokenKind::RBrace)?;
+        if values.is_empty() {
+            self.warnings.push(lint::Warning {
+                lint: "empty-block",
+                message: format!(
+                    "the insert into {} sets no columns, every column takes its default",
+                    ident.literal
+                ),
+                span: Some(Span {
+                    start,
+                    end: self.current_span.end,
+                }),
+            });
+        }
+        let mut conflict = None;
+        if self.peek_token.kind == TokenKind::Ident && self.peek_token.literal == "on" {
+            self.next_token()?;
+            self.expect_word("conflict")?;
+            self.expect_peek(TokenKind::LParen)?;
+            let columns = self.parse_identifiers(TokenKind::RParen)?;
+            self.expect_word("update")?;
+            self.expect_peek(TokenKind::LBrace)?;
+            let update = self.parse_identifiers(TokenKind::RBrace)?;
+            if columns.is_empty() || update.is_empty() {
+                return Err(error!(
+                    Parse,
+                    "on conflict needs the columns that collide and the ones to update"
+                ));
+            }
+            conflict = Some(ConflictStatement { columns, update });
+        }
+        Ok(InsertStatement {
+            ident,
+            values,
+            conflict,
+            returning: self.parse_returning()?,
+        })
+    }
+    fn parse_update(&mut self) -> Result<UpdateStatement> {
+        self.expect_identifier()?;
+        let ident = self.parse_identifier();
+        let mut filters = vec![];
+        if self.peek_token.kind == TokenKind::LParen {
+            self.next_token()?;
+            filters = self.parse_pair_statements(TokenKind::RParen)?;
+        }
+        self.expect_peek(TokenKind::LBrace)?;
+        let values = self.parse_pair_statements(TokenKind::RBrace)?;
+        if values.is_empty() {
+            return Err(error!(Parse, "update of {} has no values", ident.literal));
+        }
+        Ok(UpdateStatement {
+            ident,
+            filters,
+            values,
+            returning: self.parse_returning()?,
+        })
+    }
+    fn parse_delete(&mut self) -> Result<DeleteStatement> {
+        self.expect_identifier()?;
+        let ident = self.parse_identifier();
+        self.parse_delete_from(ident)
+    }
+    // `-users(id: 1)` and `.users delete (id: 1)` are the same delete, only the latter takes a where
+    fn parse_delete_from(&mut self, ident: IdentifierStatement) -> Result<DeleteStatement> {
+        let mut filters = vec![];
+        if self.peek_token.kind == TokenKind::LParen {
+            self.next_token()?;
+            filters = self.parse_pair_statements(TokenKind::RParen)?;
+        }
+        let mut condition = None;
+        if self.peek_token.kind == TokenKind::Where {
+            self.next_token()?;
+            let filter = self.parse_where()?;
+            condition = Some(self.arena.alloc(filter));
+        }
+        Ok(DeleteStatement {
+            ident,
+            filters,
+            condition,
+            returning: self.parse_returning()?,
+        })
+    }
+    fn parse_returning(&mut self) -> Result<Option<ReturningStatement>> {
+        if self.peek_token.kind != TokenKind::Ident || self.peek_token.literal != "returning" {
+            return Ok(None);
+        }
+        self.next_token()?;
+        self.expect_peek(TokenKind::LBrace)?;
+        let columns = self.parse_identifiers(TokenKind::RBrace)?;
+        if columns.is_empty() {
+            return Err(error!(Parse, "returning needs at least one column"));
+        }
+        Ok(Some(ReturningStatement { columns }))
+    }
+    // Names separated by commas up to and including the closing token
+    fn parse_identifiers(&mut self, end: TokenKind) -> Result<Vec<IdentifierStatement>> {
+        let mut identifiers = vec![];
+        while self.peek_token.kind != end {
+            self.expect_identifier()?;
+            identifiers.push(self.parse_identifier());
+            if self.peek_token.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token()?;
+        }
+        self.expect_peek(end)?;
+        Ok(identifiers)
+    }
+    fn parse_pair_statements(&mut self, end: TokenKind) -> Result<Vec<PairStatement>> {
+        let mut pairs = vec![];
+        while self.peek_token.kind != end {
+            self.expect_identifier()?;
+            let ident = self.parse_identifier();
+            self.expect_peek(TokenKind::Colon)?;
+            self.next_token()?;
+            let value = self.parse_value()?;
+            let value = self.arena.alloc(value);
+            pairs.push(PairStatement { ident, value });
+            if self.peek_token.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token()?;
+        }
+        self.expect_peek(end)?;
+        Ok(pairs)
+    }
+    fn is_temporal(&self) -> bool {
+        matches!(
+            (self.current_token.literal, self.peek_token.kind),
+            ("date" | "timestamp" | "interval", TokenKind::String) | ("now", TokenKind::LParen)
+        )
+    }
+    fn parse_temporal(&mut self) -> Result<Stmt> {
+        let kind = match self.current_token.literal {
+            "date" => TemporalKind::Date,
+            "timestamp" => TemporalKind::Timestamp,
+            "interval" => TemporalKind::Interval,
+            _ => {
+                self.expect_peek(TokenKind::LParen)?;
+                self.expect_peek(TokenKind::RParen)?;
+                return Ok(Stmt::Temporal(TemporalStatement {
+                    kind: TemporalKind::Now,
+                    value: String::new(),
+                }));
+            }
+        };
+        self.next_token()?;
+        let value = self.current_token.literal.to_string();
+        let valid = match kind {
+            TemporalKind::Interval => interval(&value).is_some(),
+            _ => valid_date(&value, kind == TemporalKind::Timestamp),
+        };
+        if !valid {
+            let (name, expected) = match kind {
+                TemporalKind::Date => ("date", "YYYY-MM-DD"),
+                TemporalKind::Timestamp => ("timestamp", "YYYY-MM-DD HH:MM:SS"),
+                _ => ("interval", "an amount and a unit like \"7 days\""),
+            };
+            return Err(error!(
+                Parse,
+                "invalid {} \"{}\", expected {}", name, value, expected
+            )
+            .at(self.current_span));
+        }
+        Ok(Stmt::Temporal(TemporalStatement { kind, value }))
+    }
+    fn is_env(&self) -> bool {
+        self.current_token.kind == TokenKind::Env
+            || (self.current_token.literal == "env" && self.peek_token.kind == TokenKind::LParen)
+    }
+    fn parse_env(&mut self) -> Result<Stmt> {
+        if self.current_token.kind == TokenKind::Ident {
+            self.expect_peek(TokenKind::LParen)?;
+            self.expect_peek(TokenKind::String)?;
+        }
+        let name = self.current_token.literal.to_string();
+        if self.current_token.kind == TokenKind::String {
+            self.expect_peek(TokenKind::RParen)?;
+        }
+        Ok(Stmt::Env(EnvStatement { name }))
+    }
+    fn parse_value(&mut self) -> Result<Stmt> {
+        match self.current_token.kind {
+            TokenKind::Ident if self.is_temporal() => self.parse_temporal(),
+            TokenKind::Ident | TokenKind::Env if self.is_env() => self.parse_env(),
+            TokenKind::Number | TokenKind::String => Ok(Stmt::Literal(self.parse_literal())),
+            TokenKind::Minus => {
+                self.expect_peek(TokenKind::Number)?;
+                let mut literal = self.parse_literal();
+                literal.literal.insert(0, '-');
+                Ok(Stmt::Literal(literal))
+            }
+            TokenKind::Variable => Ok(Stmt::Variable(VariableStatement {
+                name: self.current_token.literal.to_string(),
+            })),
+            TokenKind::Ident if !self.scopes.is_empty() => {
+                let scope = &self.scopes[self.scopes.len() - 1];
+                Ok(Stmt::Identifier(IdentifierStatement {
+                    literal: format!("{}.{}", scope, self.current_token.literal).into(),
+                    span: Some(self.current_span),
+                }))
+            }
+            kind => Err(error!(Parse, "expected value, got {}", kind)),
+        }
+    }
+    fn parse_where(&mut self) -> Result<Stmt> {
+        self.expect_peek(TokenKind::LBrace)?;
+        self.next_token()?;
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(TokenKind::RBrace)?;
+        Ok(condition)
+    }
+    fn parse_expression(&mut self, precedence: Precedence) -> Result<Stmt> {
+        let mut left = match self.current_token.kind {
+            TokenKind::Ident if self.is_temporal() => self.parse_temporal()?,
+            TokenKind::Ident | TokenKind::Env if self.is_env() => self.parse_env()?,
+            TokenKind::Ident => Stmt::Identifier(self.parse_identifier()),
+            TokenKind::Not => self.parse_prefix_expression()?,
+            TokenKind::Case => self.parse_case()?,
+            TokenKind::LParen => {
+                self.next_token()?;
+                let expression = self.parse_expression(Precedence::Lowest)?;
+                self.expect_peek(TokenKind::RParen)?;
+                expression
+            }
+            _ => self.parse_value()?,
+        };
+        while precedence < self::precedence(self.peek_token.kind) {
+            self.next_token()?;
+            left = self.parse_infix_expression(left)?;
+        }
+        Ok(left)
+    }
+    fn parse_case(&mut self) -> Result<Stmt> {
+        self.expect_peek(TokenKind::LBrace)?;
+        let mut branches = vec![];
+        let mut otherwise = None;
+        loop {
+            self.next_token()?;
+            match self.current_token.kind {
+                TokenKind::RBrace => break,
+                TokenKind::Comma => continue,
+                TokenKind::Eof => return Err(error!(Parse, "expected RBRACK, got EOF")),
+                _ => {}
+            }
+            if otherwise.is_some() {
+                return Err(error!(Parse, "_ must be the last case branch"));
+            }
+            let fallback = self.current_token.literal == "_";
+            let condition = if fallback {
+                None
+            } else {
+                Some(self.parse_expression(Precedence::Lowest)?)
+            };
+            self.expect_peek(TokenKind::Arrow)?;
+            self.next_token()?;
+            let result = self.parse_expression(Precedence::Lowest)?;
+            let result = self.arena.alloc(result);
+            match condition {
+                Some(condition) => branches.push((self.arena.alloc(condition), result)),
+                None => otherwise = Some(result),
+            }
+        }
+        if branches.is_empty() {
+            return Err(error!(Parse, "case has no branches"));
+        }
+        Ok(Stmt::Case(CaseExpression {
+            branches,
+            otherwise,
+        }))
+    }
+    fn parse_prefix_expression(&mut self) -> Result<Stmt> {
+        let operator = self.current_token.kind;
+        self.next_token()?;
+        let right = self.parse_expression(Precedence::Not)?;
+        Ok(Stmt::Prefix(PrefixExpression {
+            operator,
+            right: self.arena.alloc(right),
+        }))
+    }
+    fn parse_infix_expression(&mut self, left: Stmt) -> Result<Stmt> {
+        let operator = self.current_token.kind;
+        let left = self.arena.alloc(left);
+        if operator == TokenKind::Is {
+            let negated = self.peek_token.kind == TokenKind::Not;
+            if negated {
+                self.next_token()?;
+            }
+            self.expect_peek(TokenKind::Null)?;
+            return Ok(Stmt::IsNull(IsNullExpression {
+                operand: left,
+                negated,
+            }));
+        }
+        if operator == TokenKind::Like || operator == TokenKind::Ilike {
+            self.next_token()?;
+            let pattern = self.parse_value()?;
+            return Ok(Stmt::Like(LikeExpression {
+                operand: left,
+                pattern: self.arena.alloc(pattern),
+                insensitive: operator == TokenKind::Ilike,
+            }));
+        }
+        if operator == TokenKind::Between {
+            self.next_token()?;
+            let low = self.parse_value()?;
+            self.expect_peek(TokenKind::And)?;
+            self.next_token()?;
+            let high = self.parse_value()?;
+            if let (Stmt::Literal(low), Stmt::Literal(high)) = (&low, &high) {
+                if low.kind != high.kind {
+                    return Err(error!(
+                        Parse,
+                        "between bounds must have the same type, got {} and {}",
+                        low.kind,
+                        high.kind
+                    ));
+                }
+            }
+            return Ok(Stmt::Between(BetweenExpression {
+                operand: left,
+                low: self.arena.alloc(low),
+                high: self.arena.alloc(high),
+            }));
+        }
+        if operator == TokenKind::In {
+            self.expect_peek(TokenKind::LParen)?;
+            let mut values = vec![];
+            while self.peek_token.kind != TokenKind::RParen {
+                self.next_token()?;
+                values.push(self.parse_value()?);
+                if self.peek_token.kind != TokenKind::Comma {
+                    break;
+                }
+                self.next_token()?;
+            }
+            self.expect_peek(TokenKind::RParen)?;
+            if values.is_empty() {
+                return Err(error!(Parse, "in list is empty"));
+            }
+            return Ok(Stmt::In(InExpression {
+                operand: left,
+                values: self.arena.alloc_all(values),
+            }));
+        }
+        self.next_token()?;
+        if matches!(operator, TokenKind::Plus | TokenKind::Minus) {
+            if self.current_token.literal != "interval" || !self.is_temporal() {
+                return Err(error!(
+                    Parse,
+                    "only an interval can be added to or subtracted from a date"
+                )
+                .at(self.current_span));
+            }
+            let right = self.parse_temporal()?;
+            return Ok(Stmt::Infix(InfixExpression {
+                left,
+                operator,
+                right: self.arena.alloc(right),
+            }));
+        }
+        let right = self.parse_expression(precedence(operator))?;
+        Ok(Stmt::Infix(InfixExpression {
+            left,
+            operator,
+            right: self.arena.alloc(right),
+        }))
+    }
+    fn parse_group_statements(
+        &mut self,
+        block: &BlockStatement,
+    ) -> Result<Vec<IdentifierStatement>> {
+        self.expect_peek(TokenKind::By)?;
+        let properties = &self.arena[block.properties];
+        let selected = properties
+            .iter()
+            .flat_map(|statement| statement.names(&self.arena))
+            .collect::<Vec<String>>();
+        let spread = properties
+            .iter()
+            .any(|statement| matches!(statement, Stmt::Spread(_) | Stmt::Wildcard(_)));
+
+        let mut grouping = vec![];
+        loop {
+            self.expect_identifier()?;
+            let ident = self.parse_identifier();
+            if !spread && !selected.iter().any(|name| ident.literal == name.as_str()) {
+                return Err(error!(
+                    Parse,
+                    "grouped column {} is not selected", ident.literal
+                ));
+            }
+            grouping.push(ident);
+            if self.peek_token.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token()?;
+        }
+        Ok(grouping)
+    }
+    fn parse_order_statements(&mut self) -> Result<Vec<OrderStatement>> {
+        self.expect_peek(TokenKind::By)?;
+        let mut ordering = vec![];
+        loop {
+            self.expect_identifier()?;
+            let ident = self.parse_identifier();
+            let mut direction = None;
+            if self.peek_token.kind == TokenKind::Asc || self.peek_token.kind == TokenKind::Desc {
+                self.next_token()?;
+                direction = Some(self.current_token.kind);
+            }
+            ordering.push(OrderStatement { ident, direction });
+            if self.peek_token.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token()?;
+        }
+        Ok(ordering)
+    }
+    fn parse_order_directive(&mut self) -> Result<Vec<OrderStatement>> {
+        let mut ordering: Vec<OrderStatement> = vec![];
+        while self.peek_token.kind != TokenKind::RParen {
+            if let TokenKind::Asc | TokenKind::Desc = self.peek_token.kind {
+                // `@order(name, desc)` is the direction of the column before it
+                let Some(order) = ordering
+                    .last_mut()
+                    .filter(|order| order.direction.is_none())
+                else {
+                    return Err(
+                        error!(Parse, "expected a column before {}", self.peek_token.kind)
+                            .at(self.peek_span),
+                    );
+                };
+                self.next_token()?;
+                order.direction = Some(self.current_token.kind);
+            } else {
+                self.expect_identifier()?;
+                let ident = self.parse_identifier();
+                let mut direction = None;
+                if self.peek_token.kind == TokenKind::Colon {
+                    self.next_token()?;
+                    match self.peek_token.kind {
+                        TokenKind::Asc | TokenKind::Desc => {
+                            self.next_token()?;
+                            direction = Some(self.current_token.kind);
+                        }
+                        kind => return Err(error!(Parse, "expected ASC or DESC, got {}", kind)),
+                    }
+                }
+                ordering.push(OrderStatement { ident, direction });
+            }
+            if self.peek_token.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token()?;
+        }
+        self.expect_peek(TokenKind::RParen)?;
+        Ok(ordering)
+    }
+    fn reference(&mut self, name: Symbol) {
+        let defined = self
+            .definitions
+            .iter()
+            .any(|table| table.name.literal == name);
+        if defined && !self.references.contains(&name) {
+            self.references.push(name);
+        }
+    }
+    fn parse_identifier(&mut self) -> IdentifierStatement {
+        IdentifierStatement {
+            literal: Symbol::intern(self.current_token.literal),
+            span: Some(self.current_span),
+        }
+    }
+    fn parse_number(&mut self) -> Result<LiteralStatement> {
+        self.expect_peek(TokenKind::Number)?;
+        if self.current_token.literal.contains('.') {
+            return Err(error!(
+                Parse,
+                "expected integer, got {}", self.current_token.literal
+            ));
+        }
+        Ok(self.parse_literal())
+    }
+    fn parse_literal(&mut self) -> LiteralStatement {
+        let literal = match self.current_token.kind {
+            TokenKind::String => unescape(self.current_token.literal),
+            _ => self.current_token.literal.to_string(),
+        };
+        LiteralStatement {
+            kind: self.current_token.kind,
+            literal,
+        }
+    }
+    // Where only a name can go, a double quoted string is a quoted name
+    fn expect_identifier(&mut self) -> Result<()> {
+        let token = &self.peek_token;
+        let quoted = token.kind == TokenKind::String && !token.literal.is_empty();
+        if token.kind != TokenKind::Ident
+            && lookup_identifier(token.literal) != token.kind
+            && !quoted
+        {
+            return Err(error!(Parse, "expected IDENT, got {}", token.kind).at(self.peek_span));
+        }
+        self.next_token()
+    }
+    // Words that only mean something in one place aren't keywords, so `on` can still be a column
+    fn expect_word(&mut self, word: &str) -> Result<()> {
+        if self.peek_token.kind != TokenKind::Ident || self.peek_token.literal != word {
+            return Err(
+                error!(Parse, "expected {}, got {}", word, self.peek_token.kind).at(self.peek_span),
+            );
+        }
+        self.next_token()
+    }
+    fn expect_peek(&mut self, kind: TokenKind) -> Result<()> {
+        if self.peek_token.kind != kind {
+            return Err(
+                error!(Parse, "expected {}, got {}", kind, self.peek_token.kind).at(self.peek_span),
+            );
+        }
+        self.next_token()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    literal: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpannedToken {
+    pub kind: TokenKind,
+    pub literal: String,
+    pub span: Span,
+}
+
+pub struct TokenStream<R> {
+    reader: R,
+    buffer: String,
+    start: usize,
+    offset: usize,
+    exhausted: bool,
+    done: bool,
+}
+impl<R: BufRead> TokenStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            start: 0,
+            offset: 0,
+            exhausted: false,
+            done: false,
+        }
+    }
+    fn fill(&mut self) -> Result<()> {
+        self.offset += self.start;
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        let read = self
+            .reader
+            .read_line(&mut self.buffer)
+            .map_err(|error| error!(Lex, "Failed to read input: {}", error))?;
+        self.exhausted = read == 0;
+        Ok(())
+    }
+}
+impl<R: BufRead> Iterator for TokenStream<R> {
+    type Item = Result<SpannedToken>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let mut lexer = Lexer::new(&self.buffer[self.start..]);
+            let token = lexer.next_token();
+            // strings and comments may continue on the next lines
+            if !self.exhausted && lexer.position >= lexer.input.len() {
+                if let Err(error) = self.fill() {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+                continue;
+            }
+            let shift = |span: Span| Span {
+                start: span.start + self.offset + self.start,
+                end: span.end + self.offset + self.start,
+            };
+            let span = shift(lexer.span());
+            let consumed = lexer.position;
+            let token = match token {
+                Ok(token) if token.kind == TokenKind::Eof => None,
+                Ok(token) => Some(Ok(SpannedToken {
+                    kind: token.kind,
+                    literal: token.literal.to_string(),
+                    span,
+                })),
+                Err(error) => Some(Err(CompileError {
+                    span: error.span.map(shift),
+                    ..error
+                })),
+            };
+            self.start += consumed;
+            self.done = token.is_none();
+            return token;
+        }
+        None
+    }
+}
+/// Every token of the input with its span, comments included. Input that does not lex becomes
+/// `Illegal` tokens instead of an error, so half typed queries can still be highlighted
+pub fn tokenize(input: &str) -> Vec<SpannedToken> {
+    let mut lexer = Lexer::new(input);
+    let mut tokens = vec![];
+    let mut end = 0;
+    loop {
+        let token = lexer.next_token();
+        let span = lexer.span();
+        comments(input, end, span.start, &mut tokens);
+        let kind = match &token {
+            Ok(token) if token.kind == TokenKind::Eof => return tokens,
+            Ok(token) => token.kind,
+            Err(_) => TokenKind::Illegal,
+        };
+        let literal = match token {
+            Ok(token) => token.literal,
+            Err(_) => &input[span.start..span.end],
+        };
+        tokens.push(SpannedToken {
+            kind,
+            literal: literal.to_string(),
+            span,
+        });
+        if lexer.position == span.start {
+            lexer.read_char();
+        }
+        end = lexer.position;
+    }
+}
+
+fn comments(input: &str, start: usize, end: usize, tokens: &mut Vec<SpannedToken>) {
+    let mut position = start;
+    while position < end {
+        let rest = &input[position..end];
+        let trimmed = rest.trim_start();
+        position += rest.len() - trimmed.len();
+        let Some(length) = comment_length(trimmed) else {
+            return;
+        };
+        tokens.push(SpannedToken {
+            kind: TokenKind::Comment,
+            literal: trimmed[..length].to_string(),
+            span: Span {
+                start: position,
+                end: position + length,
+            },
+        });
+        position += length;
+    }
+}
+
+// The length of the comment the text starts with, scanned by the lexer so every tool that keeps
+// comments agrees with the compiler. An unterminated block comment runs to the end of the text
+pub(crate) fn comment_length(text: &str) -> Option<usize> {
+    let mut lexer = Lexer::new(text);
+    match lexer.skip_comment() {
+        Ok(true) => Some(lexer.position),
+        Ok(false) => None,
+        Err(_) => Some(text.len()),
+    }
+}
+
+fn count_all(arena: &mut Arena) -> Stmt {
+    let wildcard = Stmt::Wildcard(WildcardStatement { exclude: vec![] });
+    Stmt::Call(FunctionCall {
+        function: IdentifierStatement {
+            literal: "count".into(),
+            span: None,
+        },
+        arguments: arena.alloc_all([wildcard]),
+        window: None,
+    })
+}
+
+fn unescape(literal: &str) -> String {
+    let mut unescaped = String::with_capacity(literal.len());
+    let mut characters = literal.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unescaped.push(character);
+            continue;
+        }
+        unescaped.push(match characters.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some(character) => character,
+            None => break,
+        });
+    }
+    unescaped
+}
+
+fn is_identifier_start(character: char) -> bool {
+    character == '_' || unicode_ident::is_xid_start(character)
+}
+
+fn is_identifier_continue(character: char) -> bool {
+    unicode_ident::is_xid_continue(character)
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    position: usize,
+    read_position: usize,
+    /// `None` once the input has ended, a NUL in the input is just another character
+    character: Option<char>,
+    start: usize,
+}
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str) -> Self {
+        let mut lexer = Self {
+            input,
+            position: 0,
+            read_position: 0,
+            character: None,
+            start: 0,
+        };
+        lexer.read_char();
+        lexer
+    }
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.position.max(self.start),
+        }
+    }
+    fn next_token(&mut self) -> Result<Token<'a>> {
+        self.start = self.position;
+        self.skip_whitespace()
+            .map_err(|error| error.at(self.span()))?;
+        self.start = self.position;
+        self.read_token().map_err(|error| error.at(self.span()))
+    }
+    fn read_token(&mut self) -> Result<Token<'a>> {
+        let token = match self.character {
+            Some('.') if self.input[self.position..].starts_with("...") => {
+                self.read_char();
+                self.read_char();
+                Token {
+                    kind: TokenKind::Spread,
+                    literal: "...",
+                }
+            }
+            Some('.') => Token {
+                kind: TokenKind::Dot,
+                literal: ".",
+            },
+            Some('+') => Token {
+                kind: TokenKind::Plus,
+                literal: "+",
+            },
+            Some('~') => Token {
+                kind: TokenKind::Tilde,
+                literal: "~",
+            },
+            Some('-') => Token {
+                kind: TokenKind::Minus,
+                literal: "-",
+            },
+            Some('"') => Token {
+                kind: TokenKind::String,
+                literal: self.read_string()?,
+            },
+            Some('`') => Token {
+                kind: TokenKind::Ident,
+                literal: self.read_quoted()?,
+            },
+            Some('{') => Token {
+                kind: TokenKind::LBrace,
+                literal: "{",
+            },
+            Some('}') => Token {
+                kind: TokenKind::RBrace,
+                literal: "}",
+            },
+            Some('(') => Token {
+                kind: TokenKind::LParen,
+                literal: "(",
+            },
+            Some(')') => Token {
+                kind: TokenKind::RParen,
+                literal: ")",
+            },
+            Some('|') => Token {
+                kind: TokenKind::Pipe,
+                literal: "|",
+            },
+            Some(';') => Token {
+                kind: TokenKind::Semicolon,
+                literal: ";",
+            },
+            Some(',') => Token {
+                kind: TokenKind::Comma,
+                literal: ",",
+            },
+            Some(':') => Token {
+                kind: TokenKind::Colon,
+                literal: ":",
+            },
+            Some('=') if self.peek_char() == Some('>') => {
+                self.read_char();
+                Token {
+                    kind: TokenKind::Arrow,
+                    literal: "=>",
+                }
+            }
+            Some('=') => Token {
+                kind: TokenKind::Eq,
+                literal: "=",
+            },
+            Some('!') if self.peek_char() == Some('=') => {
+                self.read_char();
+                Token {
+                    kind: TokenKind::NotEq,
+                    literal: "!=",
+                }
+            }
+            Some('!') => Token {
+                kind: TokenKind::Bang,
+                literal: "!",
+            },
+            Some('*') => Token {
+                kind: TokenKind::Asterisk,
+                literal: "*",
+            },
+            Some('<') if self.peek_char() == Some('=') => {
+                self.read_char();
+                Token {
+                    kind: TokenKind::LtEq,
+                    literal: "<=",
+                }
+            }
+            Some('<') => Token {
+                kind: TokenKind::Lt,
+                literal: "<",
+            },
+            Some('>') if self.peek_char() == Some('=') => {
+                self.read_char();
+                Token {
+                    kind: TokenKind::GtEq,
+                    literal: ">=",
+                }
+            }
+            Some('>') => Token {
+                kind: TokenKind::Gt,
+                literal: ">",
+            },
+            Some('$') if self.peek_char() == Some('{') => {
+                self.read_char();
+                self.read_char();
+                let name = self.read_identifier();
+                if name.is_empty() || self.character != Some('}') {
+                    return Err(error!(Lex, "Unterminated environment variable"));
+                }
+                Token {
+                    kind: TokenKind::Env,
+                    literal: name,
+                }
+            }
+            Some('$') if self.peek_char().is_some_and(is_identifier_start) => {
+                self.read_char();
+                return Ok(Token {
+                    kind: TokenKind::Variable,
+                    literal: self.read_identifier(),
+                });
+            }
+            Some('@') if self.peek_char().is_some_and(is_identifier_start) => {
+                self.read_char();
+                return Ok(Token {
+                    kind: TokenKind::Directive,
+                    literal: self.read_identifier(),
+                });
+            }
+            None => Token {
+                kind: TokenKind::Eof,
+                literal: "",
+            },
+            Some(character) => {
+                if is_identifier_start(character) {
+                    let literal = self.read_identifier();
+                    return Ok(Token {
+                        kind: lookup_identifier(literal),
+                        literal,
+                    });
+                }
+                if character.is_ascii_digit() {
+                    return Ok(Token {
+                        kind: TokenKind::Number,
+                        literal: self.read_number(),
+                    });
+                }
+                self.read_char();
+                return Err(error!(Lex, "Unknown token {}", character.escape_debug()));
+            }
+        };
+        self.read_char();
+        Ok(token)
+    }
+    fn read_identifier(&mut self) -> &'a str {
+        let position = self.position;
+        while self.character.is_some_and(is_identifier_continue)
+            || (self.character == Some('.') && self.peek_char().is_some_and(is_identifier_start))
+        {
+            self.read_char();
+        }
+        &self.input[position..self.position]
+    }
+    fn read_string(&mut self) -> Result<&'a str> {
+        let position = self.read_position;
+        loop {
+            self.read_char();
+            match self.character {
+                Some('"') => break,
+                None => return Err(error!(Lex, "Unterminated string")),
+                Some('\\') => {
+                    self.read_char();
+                    match self.character {
+                        Some(character) if "ntr\"\\".contains(character) => {}
+                        Some(character) => {
+                            let error = error!(Lex, "Unknown escape \\{}", character);
+                            self.skip_string();
+                            return Err(error);
+                        }
+                        None => return Err(error!(Lex, "Unterminated string")),
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(&self.input[position..self.position])
+    }
+    // Skips past the closing quote, so lexing goes on after a string with an error
+    fn skip_string(&mut self) {
+        loop {
+            self.read_char();
+            match self.character {
+                Some('"') | None => break,
+                Some('\\') => self.read_char(),
+                Some(_) => {}
+            }
+        }
+        self.read_char();
+    }
+    fn read_quoted(&mut self) -> Result<&'a str> {
+        let position = self.read_position;
+        loop {
+            self.read_char();
+            match self.character {
+                Some('`') => break,
+                None | Some('\n') => return Err(error!(Lex, "Unterminated quoted identifier")),
+                Some(_) => {}
+            }
+        }
+        let name = &self.input[position..self.position];
+        if name.is_empty() {
+            self.read_char();
+            return Err(error!(Lex, "Empty quoted identifier"));
+        }
+        if name.contains('.') {
+            self.read_char();
+            return Err(error!(Lex, "Quoted identifiers cannot contain dots"));
+        }
+        Ok(name)
+    }
+    fn read_number(&mut self) -> &'a str {
+        let position = self.position;
+        while self
+            .character
+            .is_some_and(|character| character.is_ascii_digit())
+        {
+            self.read_char();
+        }
+        if self.character == Some('.') && self.peek_char().is_some_and(|c| c.is_ascii_digit()) {
+            self.read_char();
+            while self
+                .character
+                .is_some_and(|character| character.is_ascii_digit())
+            {
+                self.read_char();
+            }
+        }
+        &self.input[position..self.position]
+    }
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.read_position..].chars().next()
+    }
+    fn read_char(&mut self) {
+        self.position = self.read_position;
+        self.character = self.peek_char();
+        if let Some(character) = self.character {
+            self.read_position += character.len_utf8();
+        }
+    }
+    fn skip_whitespace(&mut self) -> Result<()> {
+        loop {
+            if self.character.is_some_and(char::is_whitespace) {
+                self.read_char();
+            } else if !self.skip_comment()? {
+                break;
+            }
+        }
+        Ok(())
+    }
+    // Skips the comment starting at the current character, telling whether there was one
+    fn skip_comment(&mut self) -> Result<bool> {
+        match (self.character, self.peek_char()) {
+            (Some('#'), _) | (Some('/'), Some('/')) => self.skip_line_comment(),
+            (Some('/'), Some('*')) => self.skip_block_comment()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+    fn skip_line_comment(&mut self) {
+        while !matches!(self.character, Some('\n') | None) {
+            self.read_char();
+        }
+    }
+    fn skip_block_comment(&mut self) -> Result<()> {
+        self.read_char();
+        loop {
+            self.read_char();
+            match self.character {
+                Some('*') if self.peek_char() == Some('/') => break,
+                None => return Err(error!(Lex, "Unterminated comment")),
+                Some(_) => {}
+            }
+        }
+        self.read_char();
+        self.read_char();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str) -> String {
+        compile(input, &mut Context::default()).unwrap()
+    }
+
+    fn run_with(input: &str, context: &mut Context) -> String {
+        compile(input, context).unwrap()
+    }
+
+    fn run_err(input: &str) -> String {
+        compile(input, &mut Context::default())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[cfg(test)]
+    #[rustfmt::skip]
+    mod test_lexer {
+        use super::*;
+
+        macro_rules! test_lexer {
+            ($input:expr, $expected:expr) => {
+                let mut lexer = Lexer::new($input);
+                let mut tokens = vec![];
+                loop {
+                    let token = lexer.next_token().unwrap();
+                    tokens.push(token.clone());
+                    if token.kind == TokenKind::Eof {
+                        break;
+                    }
+                }
+                $expected.iter().zip(tokens.iter()).for_each(|(expected, result)| {
+                    assert_eq!(expected.kind, result.kind);
+                    assert_eq!(expected.literal, result.literal);
+                });
+            };
+        }
+
+        #[test]
+        fn dot_statement() {
+            test_lexer!(".users {}", [
+                Token {
+                    kind: TokenKind::Dot,
+                    literal: ".",
+                },
+                Token {
+                    kind: TokenKind::Ident,
+                    literal: "users",
+                },
+                Token {
+                    kind: TokenKind::LBrace,
+                    literal: "{",
+                },
+                Token {
+                    kind: TokenKind::RBrace,
+                    literal: "}",
+                },
+            ]);
+        }
+
+        #[test]
+        fn block_statement() {
+            test_lexer!(".users { name, id }", [
+                Token {
+                    kind: TokenKind::Dot,
+                    literal: ".",
+                },
+                Token {
+                    kind: TokenKind::Ident,
+                    literal: "users",
+                },
+                Token {
+                    kind: TokenKind::LBrace,
+                    literal: "{",
+                },
+                Token {
+                    kind: TokenKind::Ident,
+                    literal: "name",
+                },
+                Token {
+                    kind: TokenKind::Comma,
+                    literal: ",",
+                },
+                Token {
+                    kind: TokenKind::Ident,
+                    literal: "id",
+                },
+                Token {
+                    kind: TokenKind::RBrace,
+                    literal: "}",
+                },
+            ]);
+        }
+
+        #[test]
+        fn joint_dot_statement() {
+            test_lexer!(".users .posts {}", [
+                Token {
+                    kind: TokenKind::Dot,
+                    literal: ".",
+                },
+                Token {
+                    kind: TokenKind::Ident,
+                    literal: "users",
+                },
+                Token {
+                    kind: TokenKind::Dot,
+                    literal: ".",
+                },
+                Token {
+                    kind: TokenKind::Ident,
+                    literal: "posts",
+                },
+                Token {
+                    kind: TokenKind::LBrace,
+                    literal: "{",
+                },
+                Token {
+                    kind: TokenKind::RBrace,
+                    literal: "}",
+                },
+            ]);
+        }
+
+        #[test]
+        fn order_clause() {
+            test_lexer!(
+                "order by name desc",
+                [
+                    Token {
+                        kind: TokenKind::Order,
+                        literal: "order",
+                    },
+                    Token {
+                        kind: TokenKind::By,
+                        literal: "by",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "name",
+                    },
+                    Token {
+                        kind: TokenKind::Desc,
+                        literal: "desc",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn function_call() {
+            test_lexer!(
+                "count(id)",
+                [
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "count",
+                    },
+                    Token {
+                        kind: TokenKind::LParen,
+                        literal: "(",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "id",
+                    },
+                    Token {
+                        kind: TokenKind::RParen,
+                        literal: ")",
+                    },
+                    Token {
+                        kind: TokenKind::Eof,
+                        literal: "",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn qualified_identifier() {
+            test_lexer!(
+                ".users u { u.name }",
+                [
+                    Token {
+                        kind: TokenKind::Dot,
+                        literal: ".",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "users",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "u",
+                    },
+                    Token {
+                        kind: TokenKind::LBrace,
+                        literal: "{",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "u.name",
+                    },
+                    Token {
+                        kind: TokenKind::RBrace,
+                        literal: "}",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn insert_statement() {
+            test_lexer!(
+                r#"+users { name: "Ada Lovelace" }"#,
+                [
+                    Token {
+                        kind: TokenKind::Plus,
+                        literal: "+",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "users",
+                    },
+                    Token {
+                        kind: TokenKind::LBrace,
+                        literal: "{",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "name",
+                    },
+                    Token {
+                        kind: TokenKind::Colon,
+                        literal: ":",
+                    },
+                    Token {
+                        kind: TokenKind::String,
+                        literal: "Ada Lovelace",
+                    },
+                    Token {
+                        kind: TokenKind::RBrace,
+                        literal: "}",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn literals() {
+            test_lexer!(
+                r#""say \"hi\"\n" 42 3.14 "C:\\""#,
+                [
+                    Token {
+                        kind: TokenKind::String,
+                        literal: r#"say \"hi\"\n"#,
+                    },
+                    Token {
+                        kind: TokenKind::Number,
+                        literal: "42",
+                    },
+                    Token {
+                        kind: TokenKind::Number,
+                        literal: "3.14",
+                    },
+                    Token {
+                        kind: TokenKind::String,
+                        literal: r"C:\\",
+                    },
+                    Token {
+                        kind: TokenKind::Eof,
+                        literal: "",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn operators() {
+            test_lexer!(
+                "= != < > <= >=",
+                [
+                    Token {
+                        kind: TokenKind::Eq,
+                        literal: "=",
+                    },
+                    Token {
+                        kind: TokenKind::NotEq,
+                        literal: "!=",
+                    },
+                    Token {
+                        kind: TokenKind::Lt,
+                        literal: "<",
+                    },
+                    Token {
+                        kind: TokenKind::Gt,
+                        literal: ">",
+                    },
+                    Token {
+                        kind: TokenKind::LtEq,
+                        literal: "<=",
+                    },
+                    Token {
+                        kind: TokenKind::GtEq,
+                        literal: ">=",
+                    },
+                    Token {
+                        kind: TokenKind::Eof,
+                        literal: "",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn identifiers() {
+            test_lexer!(
+                "user_id table2 _private café u.straße",
+                [
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "user_id",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "table2",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "_private",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "café",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "u.straße",
+                    },
+                    Token {
+                        kind: TokenKind::Eof,
+                        literal: "",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn stream() {
+            let input = "# users\n.users where { name = \"Ada\nLovelace\" } {\n  /* every\ncolumn */ id, ? }\n";
+            let mut lexer = Lexer::new(input);
+            let mut expected = vec![];
+            loop {
+                let token = lexer.next_token().map(|token| SpannedToken {
+                    kind: token.kind,
+                    literal: token.literal.to_string(),
+                    span: lexer.span(),
+                });
+                if token
+                    .as_ref()
+                    .is_ok_and(|token| token.kind == TokenKind::Eof)
+                {
+                    break;
+                }
+                expected.push(token);
+            }
+            let tokens: Vec<_> = TokenStream::new(input.as_bytes()).collect();
+            assert_eq!(tokens, expected);
+            assert!(tokens.iter().any(Result::is_err));
+            assert_eq!(TokenStream::new("".as_bytes()).count(), 0);
+            assert_eq!(Lexer::new("").next_token().unwrap().kind, TokenKind::Eof);
+        }
+
+        #[test]
+        fn comments() {
+            test_lexer!(
+                "# users\n.users // all of them\n{ /* every\ncolumn */ }",
+                [
+                    Token {
+                        kind: TokenKind::Dot,
+                        literal: ".",
+                    },
+                    Token {
+                        kind: TokenKind::Ident,
+                        literal: "users",
+                    },
+                    Token {
+                        kind: TokenKind::LBrace,
+                        literal: "{",
+                    },
+                    Token {
+                        kind: TokenKind::RBrace,
+                        literal: "}",
+                    },
+                    Token {
+                        kind: TokenKind::Eof,
+                        literal: "",
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn limit_clause() {
+            test_lexer!(
+                "limit 10 offset 0",
+                [
+                    Token {
+                        kind: TokenKind::Limit,
+                        literal: "limit",
+                    },
+                    Token {
+                        kind: TokenKind::Number,
+                        literal: "10",
+                    },
+                    Token {
+                        kind: TokenKind::Offset,
+                        literal: "offset",
+                    },
+                    Token {
+                        kind: TokenKind::Number,
+                        literal: "0",
+                    },
+                    Token {
+                        kind: TokenKind::Eof,
+                        literal: "",
+                    },
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = ".users {
+            name,
+            id
+        }";
+        let mut parser = Parser::new(input);
+        let mut arena = Arena::default();
+        let properties = arena.alloc_all([
+            Stmt::Identifier(IdentifierStatement {
+                literal: "name".into(),
+                span: Some(Span { start: 21, end: 25 }),
+            }),
+            Stmt::Identifier(IdentifierStatement {
+                literal: "id".into(),
+                span: Some(Span { start: 39, end: 41 }),
+            }),
+        ]);
+        let expected_tree = Program {
+            arena,
+            spans: vec![],
+            names: vec![],
+            warnings: vec![],
+            source: input.to_string(),
+            standalone: true,
+            statements: vec![Stmt::Dot(DotStatement {
+                ident: IdentifierStatement {
+                    literal: "users".into(),
+                    span: Some(Span { start: 1, end: 6 }),
+                },
+                filters: vec![],
+                alias: None,
+                joins: vec![],
+                condition: None,
+                within: None,
+                distinct: false,
+                distinct_on: vec![],
+                block: BlockStatement { properties },
+                grouping: vec![],
+                ordering: vec![],
+                limit: None,
+                offset: None,
+            })],
+        };
+        let result = parser.run();
+        assert_eq!(result.statements, expected_tree.statements);
+        assert_eq!(result.arena, expected_tree.arena);
+        let spans = |arena: &Arena| -> Vec<Option<Span>> {
+            arena[properties]
+                .iter()
+                .map(|statement| match statement {
+                    Stmt::Identifier(identifier) => identifier.span,
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(spans(&result.arena), spans(&expected_tree.arena));
+    }
+
+    #[test]
+    fn test_run() {
+        assert_eq!(run(".users {}"), "SELECT * FROM users;");
+        assert_eq!(
+            run(".users {
+            name,
+            id
+        }"),
+            "SELECT name, id FROM users;"
+        );
+    }
+
+    #[test]
+    fn test_run_order_by() {
+        assert_eq!(
+            run(".users { name } order by name desc"),
+            "SELECT name FROM users ORDER BY name DESC;"
+        );
+        assert_eq!(
+            run(".users { name, id } order by name asc, id"),
+            "SELECT name, id FROM users ORDER BY name ASC, id;"
+        );
+    }
+
+    #[test]
+    fn test_run_limit_offset() {
+        assert_eq!(
+            run(".users { name } limit 10 offset 20"),
+            "SELECT name FROM users LIMIT 10 OFFSET 20;"
+        );
+        assert_eq!(
+            run(".users { name } order by name limit 10"),
+            "SELECT name FROM users ORDER BY name LIMIT 10;"
+        );
+    }
+
+    #[test]
+    fn test_run_aggregates() {
+        assert_eq!(
+            run(".orders { count(id), sum(total) }"),
+            "SELECT COUNT(id), SUM(total) FROM orders;"
+        );
+        assert_eq!(
+            run(".orders { customer, avg(total), min(total), max(total) }"),
+            "SELECT customer, AVG(total), MIN(total), MAX(total) FROM orders;"
+        );
+    }
+
+    #[test]
+    fn test_run_unknown_function() {
+        assert_eq!(
+            run_err(".orders { median(total) }"),
+            "PARSE ERROR: unknown function median"
+        );
+    }
+
+    #[test]
+    fn test_run_suggestions() {
+        assert_eq!(
+            run_err(".users { name } oder by name"),
+            "PARSE ERROR: unknown clause 'oder by', did you mean 'order by'?"
+        );
+        assert_eq!(
+            run_err(".users { name } order by name lmit 3"),
+            "PARSE ERROR: unknown clause 'lmit', did you mean 'limit'?"
+        );
+        assert_eq!(
+            run_err(".users @ofset(3) { name }"),
+            "PARSE ERROR: unknown directive @ofset, did you mean '@offset'?"
+        );
+        assert_eq!(
+            run_err(".orders { sum(total), avrg(total) }"),
+            "PARSE ERROR: unknown function avrg, did you mean 'avg'?"
+        );
+    }
+
+    #[test]
+    fn test_run_group_by() {
+        assert_eq!(
+            run(".orders { customer, sum(total) } group by customer"),
+            "SELECT customer, SUM(total) FROM orders GROUP BY customer;"
+        );
+        assert_eq!(
+            run(".orders { customer, sum(total) } group by customer order by customer"),
+            "SELECT customer, SUM(total) FROM orders GROUP BY customer ORDER BY customer;"
+        );
+    }
+
+    #[test]
+    fn test_run_group_by_unselected() {
+        assert_eq!(
+            run_err(".orders { sum(total) } group by customer"),
+            "PARSE ERROR: grouped column customer is not selected"
+        );
+    }
+
+    #[test]
+    fn test_run_distinct() {
+        assert_eq!(
+            run(".users distinct { email }"),
+            "SELECT DISTINCT email FROM users;"
+        );
+        assert_eq!(
+            run(".users { distinct email }"),
+            "SELECT DISTINCT email FROM users;"
+        );
+    }
+
+    #[test]
+    fn test_run_upsert() {
+        let query =
+            "+users { email: \"a@b.c\", name: \"Ada\" } on conflict (email) update { name }";
+        assert_eq!(
+            run(query),
+            "INSERT INTO users (email, name) VALUES ('a@b.c', 'Ada') ON CONFLICT (email) DO UPDATE SET name = EXCLUDED.name;"
+        );
+        let mut context = Context {
+            dialect: Box::new(dialect::MySql),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(query, &mut context),
+            "INSERT INTO users (email, name) VALUES ('a@b.c', 'Ada') ON DUPLICATE KEY UPDATE name = VALUES(name);"
+        );
+        context.dialect = Box::new(dialect::MsSql);
+        assert_eq!(
+            compile(query, &mut context).unwrap_err().to_string(),
+            "EVAL ERROR: ON CONFLICT is not supported by this dialect"
+        );
+        assert_eq!(
+            run_err("+users { name: \"Ada\" } on conflict (email) { name }"),
+            "PARSE ERROR: expected update, got LBRACK"
+        );
+    }
+
+    #[test]
+    fn test_run_returning() {
+        let query =
+            "+users { name: \"Ada\" } returning { id, created_at } -users(id: 2) returning { id }";
+        assert_eq!(
+            run(query),
+            "INSERT INTO users (name) VALUES ('Ada') RETURNING id, created_at; DELETE FROM users WHERE id = 2 RETURNING id;"
+        );
+        let mut context = Context {
+            dialect: Box::new(dialect::MsSql),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(query, &mut context),
+            "INSERT INTO users (name) OUTPUT INSERTED.id, INSERTED.created_at VALUES ('Ada'); DELETE FROM users OUTPUT DELETED.id WHERE id = 2;"
+        );
+        context.dialect = Box::new(dialect::MySql);
+        assert_eq!(
+            compile(query, &mut context).unwrap_err().to_string(),
+            "EVAL ERROR: RETURNING is not supported by this dialect"
+        );
+    }
+
+    #[test]
+    fn test_run_distinct_on() {
+        let query = ".events distinct on (user_id) order by created desc { user_id, created }";
+        assert_eq!(
+            run(query),
+            "SELECT DISTINCT ON (user_id) user_id, created FROM events ORDER BY created DESC;"
+        );
+        let mut context = Context {
+            dialect: Box::new(dialect::MySql),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile(query, &mut context).unwrap_err().to_string(),
+            "EVAL ERROR: DISTINCT ON is not supported by this dialect"
+        );
+        assert_eq!(
+            run_err(".events distinct on (user_id) order by id { id } order by created"),
+            "PARSE ERROR: ORDER is given twice"
+        );
+    }
+
+    #[test]
+    fn test_run_dates() {
+        let query = ".users where { created_at > now() - interval \"7 days\" and born < date\"2000-01-31\" } { name }";
+        assert_eq!(
+            run(query),
+            "SELECT name FROM users WHERE created_at > CURRENT_TIMESTAMP - INTERVAL '7 days' AND born < DATE '2000-01-31';"
+        );
+        let expected = [
+            (
+                Box::new(dialect::MySql) as Box<dyn Dialect>,
+                "created_at > CURRENT_TIMESTAMP - INTERVAL 7 DAY AND born < DATE '2000-01-31'",
+            ),
+            (
+                Box::new(dialect::Sqlite),
+                "created_at > DATETIME(CURRENT_TIMESTAMP, '-7 days') AND born < DATE('2000-01-31')",
+            ),
+            (
+                Box::new(dialect::MsSql),
+                "created_at > DATEADD(day, -7, CURRENT_TIMESTAMP) AND born < CAST('2000-01-31' AS DATE)",
+            ),
+        ];
+        for (dialect, filter) in expected {
+            let mut context = Context {
+                dialect,
+                ..Default::default()
+            };
+            assert_eq!(
+                compile(query, &mut context).unwrap(),
+                format!("SELECT name FROM users WHERE {};", filter)
+            );
+        }
+        assert_eq!(
+            run_err(".users where { born < date\"2000-02-30 10:00\" } { name }"),
+            "PARSE ERROR: invalid date \"2000-02-30 10:00\", expected YYYY-MM-DD"
+        );
+        assert_eq!(
+            run_err(".users where { created_at > now() - 7 } { name }"),
+            "PARSE ERROR: only an interval can be added to or subtracted from a date"
+        );
+    }
+
+    #[test]
+    fn test_valid_date() {
+        assert_eq!(
+            run(".users where { born = date\"2024-02-29\" } { name }"),
+            "SELECT name FROM users WHERE born = DATE '2024-02-29';"
+        );
+        for date in ["2023-02-29", "2024-02-31", "2024-04-31", "1900-02-29"] {
+            assert!(!valid_date(date, false), "{}", date);
+        }
+        assert!(valid_date("2000-02-29", false));
+        assert!(!valid_date("2024-06-31 10:00", true));
+        assert!(valid_date("2024-12-31 23:59:59", true));
+    }
+
+    #[test]
+    fn test_interval_operand() {
+        let temporal = |kind, value: &str| {
+            Stmt::Temporal(TemporalStatement {
+                kind,
+                value: value.to_string(),
+            })
+        };
+        let add = |right| {
+            let mut arena = Arena::default();
+            let expression = InfixExpression {
+                left: arena.alloc(temporal(TemporalKind::Now, "")),
+                operator: TokenKind::Plus,
+                right: arena.alloc(right),
+            };
+            (expression, arena)
+        };
+        let eval = |(expression, arena): (InfixExpression, Arena)| {
+            expression
+                .eval(&arena, &mut Context::default())
+                .map_err(|error| error.to_string())
+        };
+        assert_eq!(
+            eval(add(temporal(TemporalKind::Interval, "2 hours"))).unwrap(),
+            "CURRENT_TIMESTAMP + INTERVAL '2 hours'"
+        );
+        assert_eq!(
+            eval(add(temporal(TemporalKind::Interval, "soon"))).unwrap_err(),
+            "EVAL ERROR: invalid interval \"soon\""
+        );
+        assert_eq!(
+            eval(add(temporal(TemporalKind::Date, "2024-01-01"))).unwrap_err(),
+            "EVAL ERROR: only an interval can be added to or subtracted from a date"
+        );
+        assert_eq!(
+            run_err(".users where { a > now() - interval \"9999999999 weeks\" } { name }"),
+            "PARSE ERROR: invalid interval \"9999999999 weeks\", expected an amount and a unit like \"7 days\""
+        );
+    }
+
+    #[test]
+    fn test_run_env() {
+        let mut context = Context {
+            environment: |name| (name == "TENANT_ID").then(|| "7".to_string()),
+            placeholder: Some(Placeholder::Dollar),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile(
+                ".users where { tenant_id = env(\"TENANT_ID\") or owner = ${TENANT_ID} } { name }",
+                &mut context
+            )
+            .unwrap(),
+            "SELECT name FROM users WHERE tenant_id = $1 OR owner = $2;"
+        );
+        assert_eq!(context.parameters, ["7", "7"]);
+        assert_eq!(
+            compile(
+                ".users where { tenant_id = ${REGION} } { name }",
+                &mut context
+            )
+            .unwrap_err()
+            .to_string(),
+            "EVAL ERROR: environment variable REGION is not set"
+        );
+        assert_eq!(
+            run_err(".users where { tenant_id = ${TENANT_ID } { name }"),
+            "LEX ERROR: Unterminated environment variable"
+        );
+    }
+
+    #[test]
+    fn test_run_column_alias() {
+        assert_eq!(
+            run(".users { name as full_name, id }"),
+            "SELECT name AS full_name, id FROM users;"
+        );
+        assert_eq!(
+            run(".orders { customer as buyer, sum(total) as spent } group by buyer"),
+            "SELECT customer AS buyer, SUM(total) AS spent FROM orders GROUP BY buyer;"
+        );
+    }
+
+    #[test]
+    fn test_run_table_alias() {
+        assert_eq!(run(".users u { u.name }"), "SELECT u.name FROM users u;");
+        assert_eq!(
+            run(".users u { u.name } order by u.name .posts {}"),
+            "SELECT u.name FROM users u ORDER BY u.name; SELECT * FROM posts;"
+        );
+    }
+
+    #[test]
+    fn test_run_filters() {
+        assert_eq!(run(".users(id: 1) {}"), "SELECT * FROM users WHERE id = 1;");
+        assert_eq!(
+            run(".users(org: 2, id: 1) { name }"),
+            "SELECT name FROM users WHERE org = 2 AND id = 1;"
+        );
+    }
+
+    #[test]
+    fn test_run_parameters() {
+        let program = Program::new(".users(org: 2, id: 1) {}").unwrap();
+        let mut context = Context {
+            placeholder: Some(Placeholder::Dollar),
+            ..Default::default()
+        };
+        assert_eq!(
+            program.run_with(&mut context).unwrap(),
+            "SELECT * FROM users WHERE org = $1 AND id = $2;"
+        );
+        assert_eq!(context.parameters, vec!["2", "1"]);
+        let mut context = Context {
+            placeholder: Some(Placeholder::Question),
+            ..Default::default()
+        };
+        assert_eq!(
+            program.run_with(&mut context).unwrap(),
+            "SELECT * FROM users WHERE org = ? AND id = ?;"
+        );
+        let mut context = Context {
+            placeholder: Some(Placeholder::Colon),
+            ..Default::default()
+        };
+        assert_eq!(
+            program.run_with(&mut context).unwrap(),
+            "SELECT * FROM users WHERE org = :org AND id = :id;"
+        );
+    }
+
+    #[test]
+    fn test_run_insert() {
+        assert_eq!(
+            run(r#"+users { name: "Ada", age: 36 }"#),
+            "INSERT INTO users (name, age) VALUES ('Ada', 36);"
+        );
+        assert_eq!(
+            run(r#".users insert { name: "Ada", age: 36 } returning { id }"#),
+            "INSERT INTO users (name, age) VALUES ('Ada', 36) RETURNING id;"
+        );
+        assert_eq!(
+            run(r#".users insert { name: "Ada" } .users { id }"#),
+            "INSERT INTO users (name) VALUES ('Ada'); SELECT id FROM users;"
+        );
+        let mut context = Context {
+            placeholder: Some(Placeholder::Dollar),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(r#"+users { name: "Ada", age: 36 }"#, &mut context),
+            "INSERT INTO users (name, age) VALUES ($1, $2);"
+        );
+        assert_eq!(context.parameters, vec!["Ada", "36"]);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let input = "# users\n.users(id: 1) where { name like \"A%\" } /* all */ { * } §";
+        let tokens: Vec<(Category, &str)> = tokenize(input)
+            .iter()
+            .map(|token| {
+                let text = &input[token.span.start..token.span.end];
+                (token.kind.category(), text)
+            })
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                (Category::Comment, "# users"),
+                (Category::Punctuation, "."),
+                (Category::Ident, "users"),
+                (Category::Punctuation, "("),
+                (Category::Ident, "id"),
+                (Category::Punctuation, ":"),
+                (Category::Literal, "1"),
+                (Category::Punctuation, ")"),
+                (Category::Keyword, "where"),
+                (Category::Punctuation, "{"),
+                (Category::Ident, "name"),
+                (Category::Keyword, "like"),
+                (Category::Literal, "\"A%\""),
+                (Category::Punctuation, "}"),
+                (Category::Comment, "/* all */"),
+                (Category::Punctuation, "{"),
+                (Category::Punctuation, "*"),
+                (Category::Punctuation, "}"),
+                (Category::Invalid, "§"),
+            ]
+        );
+        let unterminated = tokenize(".users { \"name }");
+        assert_eq!(unterminated.last().unwrap().kind, TokenKind::Illegal);
+        let slashes: Vec<(TokenKind, String)> = tokenize(".users /*/ c */ # d\n{ id }")
+            .into_iter()
+            .map(|token| (token.kind, token.literal))
+            .collect();
+        assert_eq!(
+            slashes[2..5],
+            [
+                (TokenKind::Comment, "/*/ c */".to_string()),
+                (TokenKind::Comment, "# d".to_string()),
+                (TokenKind::LBrace, "{".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_map() {
+        let source = ".users { name }\n\n.posts(id: 1) {\n  title\n}";
+        let mut context = Context {
+            newlines: true,
+            transaction: true,
+            ..Default::default()
+        };
+        let (sql, mappings) = Program::new(source)
+            .unwrap()
+            .source_map(&mut context)
+            .unwrap();
+        assert_eq!(
+            mappings
+                .iter()
+                .map(|mapping| (
+                    &sql[mapping.generated.start..mapping.generated.end],
+                    &source[mapping.source.start..mapping.source.end]
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("SELECT name FROM users;", ".users { name }"),
+                (
+                    "SELECT title FROM posts WHERE id = 1;",
+                    ".posts(id: 1) {\n  title\n}"
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_count() {
+        assert_eq!(run(".users.count"), "SELECT COUNT(*) FROM users;");
+        assert_eq!(run(".users { count }"), "SELECT COUNT(*) FROM users;");
+        assert_eq!(
+            run(".users.count(active: 1) where { age > 18 }"),
+            "SELECT COUNT(*) FROM users WHERE active = 1 AND age > 18;"
+        );
+        assert_eq!(
+            run(".users { role, count(*) as total } group by role"),
+            "SELECT role, COUNT(*) AS total FROM users GROUP BY role;"
+        );
+        assert_eq!(run(".stats.count { id }"), "SELECT id FROM stats.count;");
+    }
+
+    #[test]
+    fn test_run_escaping() {
+        assert_eq!(
+            run(r#".users(name: "'; DROP TABLE users; --") {}"#),
+            "SELECT * FROM users WHERE name = '''; DROP TABLE users; --';"
+        );
+        let mut context = Context {
+            dialect: Box::new(dialect::MySql),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(r#"+users { name: "O'Brien\\" }"#, &mut context),
+            "INSERT INTO users (name) VALUES ('O''Brien\\\\');"
+        );
+        let mut context = Context {
+            dialect: Box::new(dialect::MySql),
+            placeholder: Some(Placeholder::Question),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(r#"+users { name: "O'Brien\\" }"#, &mut context),
+            "INSERT INTO users (name) VALUES (?);"
+        );
+        assert_eq!(context.parameters, vec!["O'Brien\\"]);
+        let nul = LiteralStatement {
+            kind: TokenKind::String,
+            literal: "a\0b".into(),
+        };
+        assert_eq!(
+            nul.eval(&Arena::default(), &mut Context::default())
+                .unwrap_err()
+                .to_string(),
+            "EVAL ERROR: strings cannot contain NUL characters"
+        );
+    }
+
+    #[test]
+    fn test_run_empty_insert() {
+        assert_eq!(run("+users {}"), "INSERT INTO users DEFAULT VALUES;");
+        let mut context = Context {
+            dialect: Box::new(dialect::MySql),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with("+users {}", &mut context),
+            "INSERT INTO users () VALUES ();"
+        );
+        assert_eq!(
+            run_err(r#"~users(id: 1) {}"#),
+            "PARSE ERROR: update of users has no values"
+        );
+    }
+
+    #[test]
+    fn test_run_update() {
+        assert_eq!(
+            run(r#"~users(id: 1) { name: "Ada" }"#),
+            "UPDATE users SET name = 'Ada' WHERE id = 1;"
+        );
+        let mut context = Context {
+            placeholder: Some(Placeholder::Dollar),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(r#"~users(id: 1) { name: "Ada", age: 36 }"#, &mut context),
+            "UPDATE users SET name = $1, age = $2 WHERE id = $3;"
+        );
+        assert_eq!(context.parameters, vec!["Ada", "36", "1"]);
+    }
+
+    #[test]
+    fn test_run_delete() {
+        assert_eq!(run("-users(id: 1)"), "DELETE FROM users WHERE id = 1;");
+        assert_eq!(
+            run(".users delete (id: 1)"),
+            "DELETE FROM users WHERE id = 1;"
+        );
+        assert_eq!(
+            run(".users delete where { id = 1 }"),
+            "DELETE FROM users WHERE id = 1;"
+        );
+        assert_eq!(
+            run(".users delete (org: 2) where { age < 18 or banned } returning { id }"),
+            "DELETE FROM users WHERE org = 2 AND (age < 18 OR banned) RETURNING id;"
+        );
+        let mut context = Context {
+            allow_full_delete: true,
+            ..Default::default()
+        };
+        assert_eq!(run_with("-users", &mut context), "DELETE FROM users;");
+    }
+
+    #[test]
+    fn test_run_full_delete() {
+        assert_eq!(
+            run_err("-users"),
+            "EVAL ERROR: refusing to delete every row from users, pass --allow-full-delete"
+        );
+        assert_eq!(
+            run_err(".users delete"),
+            "EVAL ERROR: refusing to delete every row from users, pass --allow-full-delete"
+        );
+    }
+
+    #[test]
+    fn test_run_dialects() {
+        let program = Program::new(".users(id: 1) { name } limit 10").unwrap();
+        let mut context = Context {
+            dialect: dialect::from_name("mssql").unwrap(),
+            ..Default::default()
+        };
+        context.placeholder = Some(context.dialect.placeholder());
+        assert_eq!(
+            program.run_with(&mut context).unwrap(),
+            "SELECT name FROM users WHERE id = @p1 ORDER BY (SELECT NULL) OFFSET 0 ROWS FETCH NEXT 10 ROWS ONLY;"
+        );
+        let mut context = Context {
+            dialect: dialect::from_name("mysql").unwrap(),
+            ..Default::default()
+        };
+        context.placeholder = Some(context.dialect.placeholder());
+        assert_eq!(
+            program.run_with(&mut context).unwrap(),
+            "SELECT name FROM users WHERE id = ? LIMIT 10;"
+        );
+    }
+
+    #[test]
+    fn test_run_reserved_words() {
+        assert_eq!(
+            run(".order { group, name } order by group"),
+            "SELECT \"group\", name FROM \"order\" ORDER BY \"group\";"
+        );
+        let mut context = Context {
+            dialect: dialect::from_name("mysql").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(".users { key, name as select }", &mut context),
+            "SELECT `key`, name AS `select` FROM users;"
+        );
+    }
+
+    #[test]
+    fn test_run_literals() {
+        assert_eq!(
+            run(r#"+points { x: 1.5, y: -2, label: "a \"b\"" }"#),
+            "INSERT INTO points (x, y, label) VALUES (1.5, -2, 'a \"b\"');"
+        );
+    }
+
+    #[test]
+    fn test_run_unterminated_string() {
+        assert_eq!(
+            run_err(r#"+users { name: "Ada }"#),
+            "LEX ERROR: Unterminated string"
+        );
+    }
+
+    #[test]
+    fn test_run_where() {
+        assert_eq!(
+            run(".users where { age >= 18 and (active or admin) } { name }"),
+            "SELECT name FROM users WHERE age >= 18 AND (active OR admin);"
+        );
+        assert_eq!(
+            run(".users where { a or b and c } {}"),
+            "SELECT * FROM users WHERE a OR b AND c;"
+        );
+        assert_eq!(
+            run(".users where { not (a = 1 or b != 2) } {}"),
+            "SELECT * FROM users WHERE NOT (a = 1 OR b <> 2);"
+        );
+        assert_eq!(
+            run(".users(org: 1) where { a or b } {}"),
+            "SELECT * FROM users WHERE org = 1 AND (a OR b);"
+        );
+    }
+
+    #[test]
+    fn test_prepare() {
+        let mut context = Context {
+            placeholder: Some(Placeholder::Dollar),
+            ..Default::default()
+        };
+        let program = Program::new(
+            r#".users(org: 2) where { age > 18 and name = "Ada" } { id } -users(id: 1)"#,
+        )
+        .unwrap();
+        let prepared = program.prepare(&mut context).unwrap();
+        assert_eq!(
+            prepared[0].sql,
+            "SELECT id FROM users WHERE org = $1 AND age > $2 AND name = $3;"
+        );
+        let parameters: Vec<(&str, &str)> = prepared[0]
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.name.as_str(), parameter.kind))
+            .collect();
+        assert_eq!(
+            parameters,
+            [("org", "number"), ("age", "number"), ("name", "string")]
+        );
+        assert_eq!(prepared[1].sql, "DELETE FROM users WHERE id = $1;");
+        assert_eq!(prepared[1].parameters[0].value, "1");
+    }
+
+    #[test]
+    fn test_reparse() {
+        let input = ".users { id }\n.orders { total }\n-users(id: 1)";
+        let mut program = Program::new(input).unwrap();
+        let edit = TextEdit {
+            range: Span { start: 24, end: 29 },
+            text: "total, paid".to_string(),
+        };
+        assert!(program.reparse(edit).is_empty());
+        let expected =
+            Program::new(".users { id }\n.orders { total, paid }\n-users(id: 1)").unwrap();
+        assert_eq!(program, expected);
+        let mut context = Context {
+            schema: Some(Schema::from_toml("[users]\ncolumns = [\"name\"]\n").unwrap()),
+            ..Default::default()
+        };
+        let spans: Vec<Option<Span>> = program
+            .diagnose(&mut context)
+            .iter()
+            .map(|error| error.span)
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                Some(Span { start: 9, end: 11 }),
+                Some(Span { start: 15, end: 21 }),
+                Some(Span { start: 45, end: 47 }),
+            ]
+        );
+        let edit = TextEdit {
+            range: Span { start: 0, end: 0 },
+            text: "fragment person { name }\n".to_string(),
+        };
+        assert!(program.reparse(edit).is_empty());
+        assert_eq!(program.spans()[0], Span { start: 25, end: 38 });
+        let edit = TextEdit {
+            range: Span { start: 39, end: 40 },
+            text: "{".to_string(),
+        };
+        assert_eq!(program.reparse(edit).len(), 1);
+    }
+
+    #[test]
+    fn test_reparse_splices() {
+        let mut program = Program::new(".users { id }\n.orders { total }\n-users(id: 1)").unwrap();
+        let edits = [
+            (
+                7,
+                7,
+                "where { age > 1 } ",
+                ".users where { age > 1 } { id }\n.orders { total }\n-users(id: 1)",
+            ),
+            (32, 50, "", ".users where { age > 1 } { id }\n-users(id: 1)"),
+            (
+                0,
+                0,
+                ".posts(id: 2) { title }\n",
+                ".posts(id: 2) { title }\n.users where { age > 1 } { id }\n-users(id: 1)",
+            ),
+        ];
+        for (start, end, text, expected) in edits {
+            let edit = TextEdit {
+                range: Span { start, end },
+                text: text.to_string(),
+            };
+            assert!(program.reparse(edit).is_empty());
+            assert_eq!(program, Program::new(expected).unwrap());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let program = Program::new(".users(id: 1) where { age > 18 } { name, email }").unwrap();
+        let json = serde_json::to_string(&program).unwrap();
+        let parsed: Program = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, program);
+        let kind: TokenKind = serde_json::from_str("\"Ident\"").unwrap();
+        assert_eq!(kind, TokenKind::Ident);
+    }
+
+    #[test]
+    fn test_run_where_parameters() {
+        let mut context = Context {
+            placeholder: Some(Placeholder::Colon),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                r#".users where { age > 18 and name = "Ada" } {}"#,
+                &mut context
+            ),
+            "SELECT * FROM users WHERE age > :age AND name = :name;"
+        );
+        assert_eq!(context.parameters, vec!["18", "Ada"]);
+    }
+
+    #[test]
+    fn test_run_comments() {
+        assert_eq!(
+            run("// active users
+                .users where { active } { # just the name
+                    name /* and nothing else */
+                }"),
+            "SELECT name FROM users WHERE active;"
+        );
+    }
+
+    #[test]
+    fn test_run_unterminated_comment() {
+        assert_eq!(
+            run_err(".users { /* name }"),
+            "LEX ERROR: Unterminated comment"
+        );
+    }
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete(".users { name }"));
+        assert!(is_complete(""));
+        assert!(!is_complete(".users {"));
+        assert!(!is_complete(".users(id: 1,"));
+        assert!(!is_complete("+users { name: \"Ada"));
+        assert!(!is_complete(".users { /* name"));
+    }
+
+    #[test]
+    fn test_run_pretty() {
+        let mut context = Context {
+            pretty: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                ".users(org: 2) where { age > 18 } { name, email } order by name limit 10",
+                &mut context
+            ),
+            "SELECT\n  name,\n  email\nFROM users\nWHERE org = 2\n  AND age > 18\nORDER BY name\nLIMIT 10;"
+        );
+        assert_eq!(
+            run_with(r#"~users(id: 1) { name: "Ada" } +users { name: "Ada" }"#, &mut context),
+            "UPDATE users\nSET name = 'Ada'\nWHERE id = 1;\n\nINSERT INTO users (name)\nVALUES ('Ada');"
+        );
+    }
+
+    #[test]
+    fn test_run_transaction() {
+        let mut context = Context {
+            newlines: true,
+            transaction: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(r#"~users(id: 1) { name: "Ada" } -users(id: 2)"#, &mut context),
+            "BEGIN;\nUPDATE users SET name = 'Ada' WHERE id = 1;\nDELETE FROM users WHERE id = 2;\nCOMMIT;"
+        );
+        context.newlines = false;
+        context.dialect = Box::new(dialect::MsSql);
+        assert_eq!(
+            run_with("-users(id: 2)", &mut context),
+            "BEGIN TRANSACTION; DELETE FROM users WHERE id = 2; COMMIT;"
+        );
+    }
+
+    #[test]
+    fn test_run_explain() {
+        let mut context = Context {
+            explain: Some(Explain::Plan),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(".users { name } -users(id: 1)", &mut context),
+            "EXPLAIN SELECT name FROM users; EXPLAIN DELETE FROM users WHERE id = 1;"
+        );
+        context.dialect = Box::new(dialect::Sqlite);
+        assert_eq!(
+            run_with(".users { name }", &mut context),
+            "EXPLAIN QUERY PLAN SELECT name FROM users;"
+        );
+        context.explain = Some(Explain::Analyze);
+        assert_eq!(
+            compile(".users { name }", &mut context)
+                .unwrap_err()
+                .to_string(),
+            "EVAL ERROR: EXPLAIN ANALYZE is not supported by this dialect"
+        );
+    }
+
+    #[test]
+    fn test_ast() {
+        let program = Program::new("-users(id: 1) .users { count(id) as total }").unwrap();
+        assert_eq!(
+            program.ast().to_string(),
+            concat!(
+                r#"[{"type":"Delete","table":{"type":"Identifier","name":"users"},"#,
+                r#""filters":[{"type":"Pair","column":{"type":"Identifier","name":"id"},"#,
+                r#""value":{"type":"Literal","kind":"NUMBER","value":"1"}}],"#,
+                r#""condition":null,"returning":null},"#,
+                r#"{"type":"Select","table":{"type":"Identifier","name":"users"},"#,
+                r#""filters":[],"alias":null,"joins":[],"condition":null,"within":null,"distinct":false,"#,
+                r#""distinct_on":[],"block":{"type":"Block","properties":[{"type":"Alias","#,
+                r#""selection":{"type":"Call","function":{"type":"Identifier","name":"count"},"#,
+                r#""arguments":[{"type":"Identifier","name":"id"}],"window":null},"#,
+                r#""alias":{"type":"Identifier","name":"total"}}]},"#,
+                r#""grouping":[],"ordering":[],"limit":null,"offset":null}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_run_schema() {
+        let mut context = Context {
+            schema: Some(
+                Schema::from_toml("[users]\ncolumns = [\"id\", \"name\", \"age\"]\n").unwrap(),
+            ),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                ".users(id: 1) u where { age > 18 } { u.name, count(id) as total }",
+                &mut context
+            ),
+            "SELECT u.name, COUNT(id) AS total FROM users u WHERE id = 1 AND age > 18;"
+        );
+        assert_eq!(
+            compile(".usres { name }", &mut context)
+                .unwrap_err()
+                .to_string(),
+            "SCHEMA ERROR: table 'usres' not found, did you mean 'users'?"
+        );
+        assert_eq!(
+            compile("~users(id: 1) { nmae: \"Ada\" }", &mut context)
+                .unwrap_err()
+                .to_string(),
+            "SCHEMA ERROR: column 'nmae' not found in table 'users', did you mean 'name'?"
+        );
+    }
+
+    #[test]
+    fn test_run_types() {
+        let schema = "[users]\ncolumns = [\"id\", \"name\", \"active\", \"created_at\"]\n\
+            types = { id = \"int\", name = \"text\", active = \"bool\", created_at = \"timestamp\" }\n";
+        let mut context = Context {
+            schema: Some(Schema::from_toml(schema).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                ".users(id: 1) where { active and created_at > \"2024-01-01\" and name like \"A%\" } { count(id) }",
+                &mut context
+            ),
+            "SELECT COUNT(id) FROM users WHERE id = 1 AND active AND created_at > '2024-01-01' AND name LIKE 'A%';"
+        );
+        let error = compile(
+            ".users { id }\n.users where { name = 1 } { id }",
+            &mut context,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "SCHEMA ERROR: cannot compare text column 'name' to int"
+        );
+        assert_eq!(error.span, Some(Span { start: 29, end: 33 }));
+        context.case = Case::Snake;
+        let error = compile(".users { createdAt, lastSeen }", &mut context).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "SCHEMA ERROR: column 'last_seen' not found in table 'users'"
+        );
+        assert_eq!(error.span, Some(Span { start: 20, end: 28 }));
+        assert_eq!(
+            compile(".users { sum(active) }", &mut context)
+                .unwrap_err()
+                .to_string(),
+            "SCHEMA ERROR: sum() needs a number, got bool column 'active'"
+        );
+        assert!(compile("+users { id: \"one\" }", &mut context).is_err());
+    }
+
+    #[test]
+    fn test_keyword_case() {
+        let mut context = Context {
+            keyword_case: KeywordCase::Lower,
+            terminator: String::new(),
+            dialect: Box::new(dialect::MsSql),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                ".order where { name = \"SELECT AND\" } { count(id) } union .users { ID }",
+                &mut context
+            ),
+            "select count(id) from [order] where name = 'SELECT AND' union select [ID] from users"
+        );
+    }
+
+    #[test]
+    fn test_block_commas() {
+        assert_eq!(
+            run(".users {\n  name,\n  count(id) as total,\n}"),
+            "SELECT name, COUNT(id) AS total FROM users;"
+        );
+        let error = Program::new(".users { name id }").unwrap_err();
+        assert_eq!(error.to_string(), "PARSE ERROR: missing comma before id");
+        assert_eq!(error.span, Some(Span { start: 14, end: 16 }));
+        assert_eq!(
+            Program::new(".users { name,, id }")
+                .unwrap_err()
+                .to_string(),
+            "PARSE ERROR: expected a selection, got COMMA"
+        );
+    }
+
+    #[test]
+    fn test_quoted_identifiers() {
+        assert_eq!(
+            run(".`user data` { `first name`, `order` as \"Sort Order\" }"),
+            "SELECT \"first name\", \"order\" AS \"Sort Order\" FROM \"user data\";"
+        );
+        let mut context = Context {
+            dialect: Box::new(dialect::MySql),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                "~\"user data\"(id: 1) { `e-mail`: \"a@b.c\" }",
+                &mut context
+            ),
+            "UPDATE `user data` SET `e-mail` = 'a@b.c' WHERE id = 1;"
+        );
+        assert_eq!(
+            Program::new(".users { `first name }")
+                .unwrap_err()
+                .to_string(),
+            "LEX ERROR: Unterminated quoted identifier"
+        );
+        let errors = diagnose(".users { `` , id }", &mut Context::default());
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<String>>(),
+            ["LEX ERROR: Empty quoted identifier"]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let source = ".users { id }\n.posts { title";
+        let error = Program::new(source).unwrap_err();
+        assert_eq!(
+            error.render("query.ns", source, false),
+            "PARSE ERROR[E0002]: expected RBRACK, got EOF\n --> query.ns:2:15\n  |\n2 | .posts { title\n  |               ^"
+        );
+        let error = error!(Eval, "no span");
+        assert_eq!(
+            error.render("query.ns", source, true),
+            "\x1b[1;31mEVAL ERROR[E0004]\x1b[0m: no span"
+        );
+    }
+
+    #[test]
+    fn test_run_join() {
+        let schema = "[users]\ncolumns = [\"id\", \"name\"]\n\
+            [posts]\ncolumns = [\"id\", \"user_id\", \"title\"]\nforeign_keys = { user_id = \"users.id\" }\n\
+            [comments]\ncolumns = [\"post_id\", \"body\"]\nforeign_keys = { post_id = \"posts.id\" }\n";
+        let mut context = Context {
+            schema: Some(Schema::from_toml(schema).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(".users { name, posts { title } }", &mut context),
+            "SELECT name, posts.title FROM users JOIN posts ON posts.user_id = users.id;"
+        );
+        assert_eq!(
+            run_with(
+                ".users u { name, posts { title, comments { count(body) } } }",
+                &mut context
+            ),
+            "SELECT name, posts.title, COUNT(comments.body) FROM users u \
+            JOIN posts ON posts.user_id = u.id JOIN comments ON comments.post_id = posts.id;"
+        );
+        assert_eq!(
+            run_with(".posts { title, users { name } }", &mut context),
+            "SELECT title, users.name FROM posts JOIN users ON users.id = posts.user_id;"
+        );
+        assert_eq!(
+            compile(".users { posts { titel } }", &mut context)
+                .unwrap_err()
+                .to_string(),
+            "SCHEMA ERROR: column 'titel' not found in table 'posts', did you mean 'title'?"
+        );
+        assert_eq!(
+            run_err(".users { name, posts { title } }"),
+            "EVAL ERROR: joining posts requires a schema with foreign keys"
+        );
+    }
+
+    #[test]
+    fn test_run_explicit_join() {
+        assert_eq!(
+            run(".users left join .orders on { orders.user_id = users.id } { users.name, orders.total }"),
+            "SELECT users.name, orders.total FROM users LEFT JOIN orders ON orders.user_id = users.id;"
+        );
+        assert_eq!(
+            run(".employees e left outer join .employees m on { e.manager_id = m.id } { e.name, m.name as manager }"),
+            "SELECT e.name, m.name AS manager FROM employees e LEFT JOIN employees m ON e.manager_id = m.id;"
+        );
+        assert_eq!(
+            run(".users u join .orders o on { o.user_id = u.id } cross join .regions { u.name }"),
+            "SELECT u.name FROM users u JOIN orders o ON o.user_id = u.id CROSS JOIN regions;"
+        );
+        let schema = "[users]\ncolumns = [\"id\", \"name\"]\n[orders]\ncolumns = [\"id\", \"user_id\", \"total\"]\n";
+        let mut context = Context {
+            schema: Some(Schema::from_toml(schema).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                ".users u join .orders o on { o.user_id = u.id } { name, o.total }",
+                &mut context
+            ),
+            "SELECT name, o.total FROM users u JOIN orders o ON o.user_id = u.id;"
+        );
+        assert_eq!(
+            compile(
+                ".users u join .orders o on { o.user_id = u.id } { o.name }",
+                &mut context
+            )
+            .unwrap_err()
+            .to_string(),
+            "SCHEMA ERROR: column 'name' not found in table 'orders'"
+        );
+    }
+
+    #[test]
+    fn test_run_wildcard() {
+        let schema = "[users]\ncolumns = [\"id\", \"name\", \"password\", \"ssn\"]\n\
+            [posts]\ncolumns = [\"id\", \"user_id\", \"title\"]\nforeign_keys = { user_id = \"users.id\" }\n";
+        let mut context = Context {
+            schema: Some(Schema::from_toml(schema).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(run(".users { * }"), "SELECT * FROM users;");
+        assert_eq!(
+            run_with(".users { *, !password, !ssn }", &mut context),
+            "SELECT id, name FROM users;"
+        );
+        assert_eq!(
+            run_with(".users { name, posts { *, !user_id } }", &mut context),
+            "SELECT name, posts.id, posts.title FROM users JOIN posts ON posts.user_id = users.id;"
+        );
+        assert_eq!(
+            compile(".users { *, !pasword }", &mut context)
+                .unwrap_err()
+                .to_string(),
+            "SCHEMA ERROR: column 'pasword' not found in table 'users', did you mean 'password'?"
+        );
+        assert_eq!(
+            run_err(".users { *, !password }"),
+            "EVAL ERROR: excluding columns from * requires a schema"
+        );
+        assert_eq!(
+            run_err(".users { !password }"),
+            "PARSE ERROR: !password excludes a column from *, but the block does not select *"
+        );
+    }
+
+    #[test]
+    fn test_run_identifiers() {
+        assert_eq!(
+            run(".users2 { user_id, straße } order by user_id"),
+            "SELECT user_id, \"straße\" FROM users2 ORDER BY user_id;"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_columns() {
+        let error = Program::new(".users { id, name, id }").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "PARSE ERROR: column id is selected twice"
+        );
+        assert_eq!(error.span, Some(Span { start: 19, end: 21 }));
+        assert_eq!(
+            run_err(".users { name, email as name }"),
+            "PARSE ERROR: column name is selected twice"
+        );
+        let program = Program::new(".users {}").unwrap();
+        assert_eq!(program.warnings()[0].lint, "empty-block");
+        assert_eq!(program.warnings()[0].span, Some(Span { start: 7, end: 9 }));
+        assert!(Program::new(".users { * }").unwrap().warnings().is_empty());
+    }
+
+    #[test]
+    fn test_run_subquery() {
+        assert_eq!(
+            run(".users { name, (.orders(user: id) { count(id) }) as order_count }"),
+            "SELECT name, (SELECT COUNT(id) FROM orders WHERE \"user\" = users.id) AS order_count FROM users;"
+        );
+        assert_eq!(
+            run(".users u { (.orders(user_id: id, status: \"paid\") { max(total) }) }"),
+            "SELECT (SELECT MAX(total) FROM orders WHERE user_id = u.id AND status = 'paid') FROM users u;"
+        );
+        assert_eq!(
+            run_err(".users(id: id) { name }"),
+            "PARSE ERROR: expected value, got IDENT"
+        );
+    }
+
+    #[test]
+    fn test_run_union() {
+        assert_eq!(
+            run(".admins { email } | .users { email }"),
+            "SELECT email FROM admins UNION SELECT email FROM users;"
+        );
+        assert_eq!(
+            run(".admins { email } union all .users { email } limit 5 | .guests { email }"),
+            "SELECT email FROM admins UNION ALL (SELECT email FROM users LIMIT 5) UNION SELECT email FROM guests;"
+        );
+        let query = ".admins { email } order by email limit 5 | .users { email } order by email";
+        let expected = [
+            (
+                Box::new(dialect::MySql) as Box<dyn Dialect>,
+                Ok("(SELECT email FROM admins ORDER BY email LIMIT 5) UNION (SELECT email FROM users ORDER BY email);"),
+            ),
+            (
+                Box::new(dialect::Sqlite),
+                Ok("SELECT * FROM (SELECT email FROM admins ORDER BY email LIMIT 5) UNION SELECT * FROM (SELECT email FROM users ORDER BY email);"),
+            ),
+            (
+                Box::new(dialect::MsSql),
+                Err("EVAL ERROR: ORDER BY in a union member needs a limit on this dialect"),
+            ),
+        ];
+        for (dialect, sql) in expected {
+            let mut context = Context {
+                dialect,
+                ..Default::default()
+            };
+            let result = compile(query, &mut context).map_err(|error| error.to_string());
+            assert_eq!(result, sql.map(String::from).map_err(String::from));
+        }
+        let mut context = Context {
+            dialect: Box::new(dialect::MsSql),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile(".admins { email } order by email limit 5 | .users { email }", &mut context).unwrap(),
+            "SELECT * FROM (SELECT email FROM admins ORDER BY email OFFSET 0 ROWS FETCH NEXT 5 ROWS ONLY) AS member UNION SELECT email FROM users;"
+        );
+        assert_eq!(
+            run_err(".admins { email } | +users { email: \"a\" }"),
+            "PARSE ERROR: expected DOT, got PLUS"
+        );
+    }
+
+    #[test]
+    fn test_run_with() {
+        assert_eq!(
+            run("let actives = .users where { active } { id }; .actives {}"),
+            "WITH actives AS (SELECT id FROM users WHERE active) SELECT * FROM actives;"
+        );
+        assert_eq!(
+            run("let a = .users { id } let b = .a { id } let c = .users {} .b {} .users {}"),
+            "WITH a AS (SELECT id FROM users), b AS (SELECT id FROM a) SELECT * FROM b; \
+            SELECT * FROM users;"
+        );
+        let schema = "[users]\ncolumns = [\"id\", \"active\"]\n\
+            [orders]\ncolumns = [\"user_id\", \"total\"]\nforeign_keys = { user_id = \"users.id\" }\n";
+        let mut context = Context {
+            schema: Some(Schema::from_toml(schema).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                "let actives = .users where { active } { id }; .orders in actives { total }",
+                &mut context
+            ),
+            "WITH actives AS (SELECT id FROM users WHERE active) \
+            SELECT total FROM orders WHERE user_id IN (SELECT id FROM actives);"
+        );
+        assert_eq!(
+            compile("let a = .users { id } .a { active }", &mut context)
+                .unwrap_err()
+                .to_string(),
+            "SCHEMA ERROR: column 'active' not found in table 'a'"
+        );
+        assert_eq!(
+            run_err(".orders in actives { total }"),
+            "PARSE ERROR: actives is not defined with let"
+        );
+    }
+
+    #[test]
+    fn test_run_window() {
+        assert_eq!(
+            run(".sales { region, rank() over(partition by region order by total desc) }"),
+            "SELECT region, RANK() OVER (PARTITION BY region ORDER BY total DESC) FROM sales;"
+        );
+        assert_eq!(
+            run(".sales { sum(total) over() as grand_total, ntile(4) over(order by total) }"),
+            "SELECT SUM(total) OVER () AS grand_total, NTILE(4) OVER (ORDER BY total) FROM sales;"
+        );
+        assert_eq!(
+            run_err(".sales { rank() }"),
+            "PARSE ERROR: rank requires an over clause"
+        );
+    }
+
+    #[test]
+    fn test_run_case() {
+        assert_eq!(
+            run(".users { name, case { age >= 18 => \"adult\", _ => \"minor\" } as bracket }"),
+            "SELECT name, CASE WHEN age >= 18 THEN 'adult' ELSE 'minor' END AS bracket FROM users;"
+        );
+        assert_eq!(
+            run(".users where { case { admin => 1, _ => 0 } = 1 } { case { a and b => c } }"),
+            "SELECT CASE WHEN a AND b THEN c END FROM users WHERE CASE WHEN admin THEN 1 ELSE 0 END = 1;"
+        );
+        assert_eq!(
+            run_err(".users { case { _ => 1, a => 2 } }"),
+            "PARSE ERROR: _ must be the last case branch"
+        );
+    }
+
+    #[test]
+    fn test_run_is_null() {
+        assert_eq!(
+            run(".users where { deleted_at is null } { name }"),
+            "SELECT name FROM users WHERE deleted_at IS NULL;"
+        );
+        assert_eq!(
+            run(".users where { not email is not null or a = 1 } { name }"),
+            "SELECT name FROM users WHERE NOT email IS NOT NULL OR a = 1;"
+        );
+        assert_eq!(
+            run_err(".users where { email is 1 } { name }"),
+            "PARSE ERROR: expected NULL, got NUMBER"
+        );
+    }
+
+    #[test]
+    fn test_run_in() {
+        assert_eq!(
+            run(".users where { id in (1, 2, 3) } { name }"),
+            "SELECT name FROM users WHERE id IN (1, 2, 3);"
+        );
+        let mut context = Context {
+            placeholder: Some(Placeholder::Dollar),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                ".users(org: 1) where { status in (\"active\", \"trial\") } { name }",
+                &mut context
+            ),
+            "SELECT name FROM users WHERE org = $1 AND status IN ($2, $3);"
+        );
+        assert_eq!(context.parameters, ["1", "active", "trial"]);
+        assert_eq!(
+            run_err(".users where { id in () } { name }"),
+            "PARSE ERROR: in list is empty"
+        );
+    }
+
+    #[test]
+    fn test_run_between() {
+        assert_eq!(
+            run(".users where { age between 18 and 65 and active } { name }"),
+            "SELECT name FROM users WHERE age BETWEEN 18 AND 65 AND active;"
+        );
+        assert_eq!(
+            run_err(".users where { age between 18 and \"x\" } { name }"),
+            "PARSE ERROR: between bounds must have the same type, got NUMBER and STRING"
+        );
+        let mut context = Context {
+            schema: Some(Schema::from_toml("[users]\ncolumns = [\"name\"]\n").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile(
+                ".users where { agee between 1 and 2 } { name }",
+                &mut context
+            )
+            .unwrap_err()
+            .to_string(),
+            "SCHEMA ERROR: column 'agee' not found in table 'users'"
+        );
+    }
+
+    #[test]
+    fn test_run_like() {
+        assert_eq!(
+            run(".users where { name like \"A%\" } { name }"),
+            "SELECT name FROM users WHERE name LIKE 'A%';"
+        );
+        assert_eq!(
+            run(".users where { name ilike \"a%\" } { name }"),
+            "SELECT name FROM users WHERE name ILIKE 'a%';"
+        );
+        let mut context = Context {
+            dialect: Box::new(dialect::MySql),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(".users where { name ilike \"a%\" } { name }", &mut context),
+            "SELECT name FROM users WHERE LOWER(name) LIKE LOWER('a%');"
+        );
+    }
+
+    #[test]
+    fn test_run_fragment() {
+        assert_eq!(
+            run(".users { ...person, id } .admins { ...person } fragment person { name, ...contact } fragment contact { email }"),
+            "SELECT name, email, id FROM users; SELECT name, email FROM admins;"
+        );
+        assert_eq!(
+            run_err(".users { ...person }"),
+            "PARSE ERROR: fragment person is not defined"
+        );
+        assert_eq!(
+            run_err("fragment a { ...b } fragment b { x, ...a } .users { ...a }"),
+            "PARSE ERROR: fragment a is cyclic: a -> b -> a"
+        );
+    }
+
+    #[test]
+    fn test_run_variables() {
+        let mut context = Context {
+            placeholder: Some(Placeholder::Dollar),
+            variables: HashMap::from([
+                ("userId".to_string(), Json::Number("42".to_string())),
+                ("name".to_string(), Json::String("Ada".to_string())),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(
+                ".users(id: $userId) where { name = $name } { name }",
+                &mut context
+            ),
+            "SELECT name FROM users WHERE id = $1 AND name = $2;"
+        );
+        assert_eq!(context.parameters, ["42", "Ada"]);
+        assert_eq!(
+            run_err(".users(id: $userId, org: $org) where { a = $userId } { name }"),
+            "EVAL ERROR: unbound variables: $userId, $org"
+        );
+    }
+
+    #[test]
+    fn test_run_directives() {
+        assert_eq!(
+            run(".users @limit(10) @order(name: desc, id) { name }"),
+            run(".users { name } order by name desc, id limit 10")
+        );
+        assert_eq!(
+            run(".users @distinct @offset(5) { name }"),
+            "SELECT DISTINCT name FROM users OFFSET 5;"
+        );
+        assert_eq!(
+            run_err(".users @limit(10) { name } limit 5"),
+            "PARSE ERROR: LIMIT is given twice"
+        );
+        assert_eq!(
+            run_err(".users @sort(name) { name }"),
+            "PARSE ERROR: unknown directive @sort"
+        );
+    }
+
+    #[test]
+    fn test_run_order_directive() {
+        assert_eq!(
+            run(".users @order(name: desc) { name }"),
+            "SELECT name FROM users ORDER BY name DESC;"
+        );
+        assert_eq!(
+            run(".users @order(name, desc, id) { name }"),
+            "SELECT name FROM users ORDER BY name DESC, id;"
+        );
+        assert_eq!(
+            run_err(".users @order(desc) { name }"),
+            "PARSE ERROR: expected a column before DESC"
+        );
+        assert_eq!(
+            run_err(".users @order(name: desc, asc) { name }"),
+            "PARSE ERROR: expected a column before ASC"
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut context = Context {
+            placeholder: Some(Placeholder::Dollar),
+            ..Default::default()
+        };
+        let program = Program::new(".users(id: 1) { name } -users(id: 2)").unwrap();
+        let parameter = |value: &str| Parameter {
+            name: "id".to_string(),
+            kind: "number",
+            value: value.to_string(),
+        };
+        assert_eq!(
+            program.queries(&mut context).unwrap(),
+            [
+                (
+                    "SELECT name FROM users WHERE id = $1;".to_string(),
+                    vec![parameter("1")]
+                ),
+                (
+                    "DELETE FROM users WHERE id = $1;".to_string(),
+                    vec![parameter("2")]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_empty() {
+        assert_eq!(run(""), "");
+    }
+
+    #[test]
+    fn test_untrusted_input() {
+        let compile = |input: &[u8]| {
+            try_compile(input, &mut Context::default()).map_err(|error| error.to_string())
+        };
+        assert_eq!(compile(b"").unwrap(), "");
+        assert_eq!(compile(b".users { a0 }").unwrap(), "SELECT a0 FROM users;");
+        assert_eq!(
+            compile(b".users { a }\0.posts { b }").unwrap_err(),
+            "LEX ERROR: Unknown token \\0"
+        );
+        assert_eq!(
+            compile(b"+users { name: \"a\0b\" }").unwrap_err(),
+            "EVAL ERROR: strings cannot contain NUL characters"
+        );
+        let error = try_compile(b".users { \xff }", &mut Context::default()).unwrap_err();
+        assert_eq!(error.to_string(), "LEX ERROR: Invalid UTF-8");
+        assert_eq!(error.span, Some(Span { start: 9, end: 10 }));
+        let (program, errors) = parse_lossy(b"\xff\n.users { name }");
+        assert_eq!(program.spans().len(), 1);
+        assert_eq!(errors[0].to_string(), "LEX ERROR: Unknown token \u{fffd}");
+    }
+
+    #[test]
+    fn test_spans() {
+        let program = Program::new(".users { name }\n-users(id: 1)").unwrap();
+        assert_eq!(
+            program.spans(),
+            [Span { start: 0, end: 15 }, Span { start: 16, end: 29 }]
+        );
+        let error =
+            compile(".users { name }\n.orders(id 1) {}", &mut Context::default()).unwrap_err();
+        assert_eq!(error.span, Some(Span { start: 27, end: 28 }));
+        let error = compile(".users {\n  \"name }", &mut Context::default()).unwrap_err();
+        assert_eq!(error.span, Some(Span { start: 11, end: 18 }));
+        let error = compile(".users { name }\n-users", &mut Context::default()).unwrap_err();
+        assert_eq!(error.span, Some(Span { start: 16, end: 22 }));
+    }
+
+    #[test]
+    fn test_query_names() {
+        let program = Program::new("query getUsers: .users { name }\n.posts { title }").unwrap();
+        assert_eq!(program.names(), [Some("getUsers".to_string()), None]);
+        assert_eq!(program.spans()[0], Span { start: 16, end: 31 });
+        let prepared = Program::new("let actives = .users where { active } { id };\nquery totals: .actives u join .orders o on { o.user_id = u.id } { o.total }")
+            .unwrap()
+            .prepare(&mut Context::default())
+            .unwrap();
+        assert_eq!(prepared[0].name.as_deref(), Some("totals"));
+        assert_eq!(prepared[0].tables, ["users", "orders"]);
+        assert_eq!(
+            run_err("query a: .users { id }\nquery a: .posts { id }"),
+            "PARSE ERROR: query a is named twice"
+        );
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let errors = diagnose(
+            ".users { name } .orders(id 1) { total } .posts title }\n-users $ .tags { name } -orders",
+            &mut Context::default(),
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<String>>(),
+            [
+                "PARSE ERROR: expected COLON, got NUMBER",
+                "PARSE ERROR: expected LBRACK, got RBRACK",
+                "LEX ERROR: Unknown token $",
+                "EVAL ERROR: refusing to delete every row from orders, pass --allow-full-delete",
+            ]
+        );
+        let errors = diagnose(r#".users(name: "a\qb\"c") { id }"#, &mut Context::default());
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<String>>(),
+            ["LEX ERROR: Unknown escape \\q"]
+        );
+    }
+
+    #[test]
+    fn test_run_multibyte() {
+        assert_eq!(
+            run(".cafés { naïve } +users { name: \"Zoë\" }"),
+            "SELECT \"naïve\" FROM \"cafés\"; INSERT INTO users (name) VALUES ('Zoë');"
+        );
+    }
+}
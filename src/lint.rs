@@ -0,0 +1,361 @@
+use crate::arena::Arena;
+use crate::visit::{self, Visitor};
+use crate::{
+    BlockStatement, Context, DeleteStatement, DotStatement, IdentifierStatement, InsertStatement,
+    PairStatement, Parser, Result, ReturningStatement, Span, Stmt, UpdateStatement,
+};
+
+pub const NAMES: [&str; 5] = [
+    "duplicate-column",
+    "empty-block",
+    "shadowed-fragment",
+    "unused-variable",
+    "unfiltered-delete",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Warning {
+    pub lint: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Warning {
+    /// The warning rendered like an error, `level` is WARNING or ERROR for a denied lint
+    pub fn render(&self, level: &str, name: &str, source: &str, color: bool) -> String {
+        let tint = if level == "ERROR" { "1;31" } else { "1;33" };
+        let header = format!("{}[{}]", level, self.lint);
+        crate::snippet(&header, tint, &self.message, self.span, name, source, color)
+    }
+}
+
+pub fn lint(input: &str, context: &Context) -> Result<Vec<Warning>> {
+    let mut parser = Parser::new(input);
+    let mut program = parser.parse_program();
+    // spreads are linted as written, expanding a copy only finds the errors a compile would
+    parser.expand(program.clone());
+    if let Some(error) = parser.errors.into_iter().next() {
+        return Err(error);
+    }
+    let mut lints = Lints {
+        fragments: &parser.fragments,
+        span: None,
+        warnings: std::mem::take(&mut program.warnings),
+        variables: vec![],
+    };
+    let arena = &program.arena;
+    for (_, block) in &parser.fragments {
+        lints.block(arena, block);
+    }
+    for table in &parser.definitions {
+        lints.visit_stmt(arena, &table.query);
+    }
+    for (statement, span) in program.statements.iter().zip(&program.spans) {
+        lints.span = Some(*span);
+        lints.visit_stmt(arena, statement);
+    }
+    let mut names: Vec<&String> = context.variables.keys().collect();
+    names.sort();
+    for name in names {
+        if !lints.variables.contains(name) {
+            lints.warnings.push(Warning {
+                lint: "unused-variable",
+                message: format!("variable ${} is bound but never used", name),
+                span: None,
+            });
+        }
+    }
+    Ok(lints.warnings)
+}
+
+struct Lints<'a> {
+    fragments: &'a [(IdentifierStatement, BlockStatement)],
+    span: Option<Span>,
+    warnings: Vec<Warning>,
+    variables: Vec<String>,
+}
+impl Lints<'_> {
+    fn warn(&mut self, lint: &'static str, message: String) {
+        self.warnings.push(Warning {
+            lint,
+            message,
+            span: self.span,
+        });
+    }
+    fn block(&mut self, arena: &Arena, block: &BlockStatement) {
+        let selected: Vec<&str> = arena[block.properties]
+            .iter()
+            .filter_map(|property| match property {
+                Stmt::Identifier(identifier) => Some(identifier.literal.as_str()),
+                Stmt::Alias(alias) => Some(alias.alias.literal.as_str()),
+                _ => None,
+            })
+            .collect();
+        // the parser already rejects a column selected twice by the block itself
+        let mut spreads: Vec<(&str, &str)> = vec![];
+        for property in &arena[block.properties] {
+            let Stmt::Spread(spread) = property else {
+                continue;
+            };
+            let fragment = self
+                .fragments
+                .iter()
+                .find(|(name, _)| *name == spread.fragment);
+            let Some((_, fragment)) = fragment else {
+                continue;
+            };
+            for property in &arena[fragment.properties] {
+                let Stmt::Identifier(identifier) = property else {
+                    continue;
+                };
+                let column = identifier.literal.as_str();
+                let fragment = spread.fragment.literal.as_str();
+                if selected.contains(&column) {
+                    self.warn(
+                        "shadowed-fragment",
+                        format!(
+                            "column {} is selected both here and by ...{}",
+                            column, fragment
+                        ),
+                    );
+                } else if let Some((_, other)) = spreads.iter().find(|(seen, _)| *seen == column) {
+                    let message = format!(
+                        "column {} is selected by both ...{} and ...{}",
+                        column, other, fragment
+                    );
+                    self.warn("duplicate-column", message);
+                }
+                spreads.push((column, fragment));
+            }
+        }
+    }
+    fn duplicates(&mut self, columns: &[&IdentifierStatement], verb: &str) {
+        for (index, column) in columns.iter().enumerate() {
+            if columns[..index].contains(column) {
+                self.warn(
+                    "duplicate-column",
+                    format!("column {} is {} more than once", column.literal, verb),
+                );
+            }
+        }
+    }
+    fn pairs(&mut self, pairs: &[PairStatement]) {
+        let columns: Vec<&IdentifierStatement> = pairs.iter().map(|pair| &pair.ident).collect();
+        self.duplicates(&columns, "set");
+    }
+    fn returning(&mut self, returning: &Option<ReturningStatement>) {
+        if let Some(returning) = returning {
+            let columns: Vec<&IdentifierStatement> = returning.columns.iter().collect();
+            self.duplicates(&columns, "returned");
+        }
+    }
+}
+impl Visitor for Lints<'_> {
+    fn visit_stmt(&mut self, arena: &Arena, statement: &Stmt) {
+        match statement {
+            // let queries are linted once, on their own
+            Stmt::With(with) => self.visit_stmt(arena, &arena[with.statement]),
+            Stmt::Join(join) => {
+                self.block(arena, &join.block);
+                visit::walk_stmt(self, arena, statement);
+            }
+            Stmt::Variable(variable) => self.variables.push(variable.name.clone()),
+            statement => visit::walk_stmt(self, arena, statement),
+        }
+    }
+    fn visit_dot(&mut self, arena: &Arena, dot: &DotStatement) {
+        self.block(arena, &dot.block);
+        visit::walk_dot(self, arena, dot);
+    }
+    fn visit_insert(&mut self, arena: &Arena, insert: &InsertStatement) {
+        self.pairs(&insert.values);
+        if let Some(conflict) = &insert.conflict {
+            let columns: Vec<&IdentifierStatement> = conflict.columns.iter().collect();
+            self.duplicates(&columns, "listed as colliding");
+            let columns: Vec<&IdentifierStatement> = conflict.update.iter().collect();
+            self.duplicates(&columns, "updated on conflict");
+        }
+        self.returning(&insert.returning);
+        insert
+            .values
+            .iter()
+            .for_each(|pair| self.visit_pair(arena, pair));
+    }
+    fn visit_update(&mut self, arena: &Arena, update: &UpdateStatement) {
+        self.pairs(&update.values);
+        self.returning(&update.returning);
+        let pairs = update.filters.iter().chain(&update.values);
+        pairs.for_each(|pair| self.visit_pair(arena, pair));
+    }
+    fn visit_delete(&mut self, arena: &Arena, delete: &DeleteStatement) {
+        if delete.is_unfiltered() {
+            self.warn(
+                "unfiltered-delete",
+                format!("delete from {} has no filter", delete.ident.literal),
+            );
+        }
+        self.returning(&delete.returning);
+        delete
+            .filters
+            .iter()
+            .for_each(|pair| self.visit_pair(arena, pair));
+        if let Some(condition) = delete.condition {
+            self.visit_stmt(arena, &arena[condition]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Json;
+
+    fn lints(input: &str, context: &Context) -> Vec<(&'static str, String)> {
+        lint(input, context)
+            .unwrap()
+            .into_iter()
+            .map(|warning| (warning.lint, warning.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_duplicate_column() {
+        let context = Context::default();
+        assert_eq!(
+            lints(r#"+users { name: "Ada", name: "Grace" }"#, &context),
+            [(
+                "duplicate-column",
+                "column name is set more than once".to_string()
+            )]
+        );
+        let input = "fragment person { name, email }\n\
+            fragment contact { email, phone }\n\
+            .users { id, ...person, ...contact }\n\
+            -users(id: 1) returning { id, id }\n\
+            +users { id: 1 } on conflict (id, id) update { name, name }";
+        assert_eq!(
+            lints(input, &context),
+            [
+                (
+                    "duplicate-column",
+                    "column email is selected by both ...person and ...contact".to_string()
+                ),
+                (
+                    "duplicate-column",
+                    "column id is returned more than once".to_string()
+                ),
+                (
+                    "duplicate-column",
+                    "column id is listed as colliding more than once".to_string()
+                ),
+                (
+                    "duplicate-column",
+                    "column name is updated on conflict more than once".to_string()
+                ),
+            ]
+        );
+        let input = r#"~users(id: 1) { id: 2, name: "Ada" } returning { id, name }"#;
+        assert!(lints(input, &context).is_empty());
+        // selecting a column twice in one block doesn't parse, so there is nothing to lint
+        assert!(lint(".users { id, name, id }", &context).is_err());
+    }
+
+    #[test]
+    fn test_lint_empty_block() {
+        let context = Context::default();
+        assert_eq!(
+            lints(".orders {}\n+orders {}", &context),
+            [
+                (
+                    "empty-block",
+                    "the block of orders is empty, write { * } to select every column".to_string()
+                ),
+                (
+                    "empty-block",
+                    "the insert into orders sets no columns, every column takes its default"
+                        .to_string()
+                ),
+            ]
+        );
+        assert!(lints(".orders { * }\n+orders { id: 1 }", &context).is_empty());
+    }
+
+    #[test]
+    fn test_render_warning() {
+        let source = "# all of them\n.orders {}";
+        let warning = &lint(source, &Context::default()).unwrap()[0];
+        assert_eq!(
+            warning.render("WARNING", "query.ns", source, false),
+            "WARNING[empty-block]: the block of orders is empty, write { * } to select every column\n --> query.ns:2:9\n  |\n2 | .orders {}\n  |         ^^"
+        );
+        assert!(warning
+            .render("ERROR", "query.ns", source, true)
+            .starts_with("\x1b[1;31mERROR[empty-block]\x1b[0m"));
+    }
+
+    #[test]
+    fn test_lint_shadowed_fragment() {
+        let context = Context::default();
+        assert_eq!(
+            lints(
+                "fragment person { name, email }\n.users { id, ...person, email }",
+                &context
+            ),
+            [(
+                "shadowed-fragment",
+                "column email is selected both here and by ...person".to_string()
+            )]
+        );
+        let input = "fragment person { name, email }\n.users { id, ...person }";
+        assert!(lints(input, &context).is_empty());
+    }
+
+    #[test]
+    fn test_lint_unused_variable() {
+        let mut context = Context::default();
+        context
+            .variables
+            .insert("org".to_string(), Json::Number("1".to_string()));
+        assert!(lints(".users(org: $org) { id }", &context).is_empty());
+        context
+            .variables
+            .insert("unused".to_string(), Json::Number("2".to_string()));
+        assert_eq!(
+            lints(".users(org: $org) { id }", &context),
+            [(
+                "unused-variable",
+                "variable $unused is bound but never used".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_lint_unfiltered_delete() {
+        let context = Context::default();
+        assert_eq!(
+            lints("-users", &context),
+            [(
+                "unfiltered-delete",
+                "delete from users has no filter".to_string()
+            )]
+        );
+        assert!(lints("-users(id: 1)", &context).is_empty());
+        assert!(lints(".users delete where { id = 1 }", &context).is_empty());
+    }
+
+    #[test]
+    fn test_lint_errors() {
+        let context = Context::default();
+        for input in [
+            ".users { ...missing }",
+            ".users {",
+            "let a = .users { id }\nlet a = .orders",
+        ] {
+            assert_eq!(
+                lint(input, &context).unwrap_err(),
+                crate::Program::new(input).unwrap_err()
+            );
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use crate::ContextArgs;
+use nonsense::json::Json;
+use nonsense::{tokenize, Category, CompileError, Program, Span, TextEdit, TokenKind};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+pub fn run(args: ContextArgs) -> Result<(), String> {
+    let mut server = Server {
+        args,
+        documents: HashMap::new(),
+    };
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let failed = |error: io::Error| format!("EXEC ERROR: Language server failed: {}", error);
+    while let Some(message) = receive(&mut stdin).map_err(failed)? {
+        let Ok(message) = Json::parse(&message) else {
+            continue;
+        };
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        if method == "exit" {
+            break;
+        }
+        let result = server.handle(method, params)?;
+        if let Some(id) = message.get("id") {
+            send(Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                ("result", result),
+            ]))
+            .map_err(failed)?;
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    args: ContextArgs,
+    documents: HashMap<String, Program>,
+}
+impl Server {
+    fn handle(&mut self, method: &str, params: &Json) -> Result<Json, String> {
+        let document = params.get("textDocument");
+        let uri = document
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+        match method {
+            "initialize" => Ok(Json::object([(
+                "capabilities",
+                Json::object([
+                    ("textDocumentSync", Json::Number("2".to_string())),
+                    ("hoverProvider", true.into()),
+                    ("completionProvider", Json::object([])),
+                    (
+                        "semanticTokensProvider",
+                        Json::object([
+                            (
+                                "legend",
+                                Json::object([
+                                    (
+                                        "tokenTypes",
+                                        Json::Array(TOKEN_TYPES.map(Json::from).to_vec()),
+                                    ),
+                                    ("tokenModifiers", Json::Array(vec![])),
+                                ]),
+                            ),
+                            ("full", true.into()),
+                        ]),
+                    ),
+                ]),
+            )])),
+            "textDocument/didOpen" => {
+                let text = document.and_then(|document| document.get("text"));
+                let Some(text) = text.and_then(Json::as_str) else {
+                    return Ok(Json::Null);
+                };
+                let (program, errors) = Program::parse(text);
+                self.documents.insert(uri.clone(), program);
+                self.publish(uri, errors)
+            }
+            "textDocument/didChange" => {
+                let Some(Json::Array(changes)) = params.get("contentChanges") else {
+                    return Ok(Json::Null);
+                };
+                let mut errors = vec![];
+                for change in changes {
+                    let Some(text) = change.get("text").and_then(Json::as_str) else {
+                        continue;
+                    };
+                    let program = self.documents.get_mut(&uri);
+                    errors = match (program, change.get("range")) {
+                        (Some(program), Some(range)) => {
+                            let source = program.source();
+                            let range = Span {
+                                start: offset(source, range.get("start")),
+                                end: offset(source, range.get("end")),
+                            };
+                            let text = text.to_string();
+                            program.reparse(TextEdit { range, text })
+                        }
+                        _ => {
+                            let (program, errors) = Program::parse(text);
+                            self.documents.insert(uri.clone(), program);
+                            errors
+                        }
+                    };
+                }
+                self.publish(uri, errors)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => self.hover(&uri, params),
+            "textDocument/completion" => self.completion(&uri, params),
+            "textDocument/semanticTokens/full" => {
+                let Some(program) = self.documents.get(&uri) else {
+                    return Ok(Json::Null);
+                };
+                Ok(Json::object([(
+                    "data",
+                    Json::Array(semantic_tokens(program.source())),
+                )]))
+            }
+            _ => Ok(Json::Null),
+        }
+    }
+    fn publish(&self, uri: String, errors: Vec<CompileError>) -> Result<Json, String> {
+        let Some(program) = self.documents.get(&uri) else {
+            return Ok(Json::Null);
+        };
+        let text = program.source();
+        let diagnostic = |span: Option<Span>, severity: &str, message: String| {
+            Json::object([
+                ("range", range(text, span.unwrap_or_default())),
+                ("severity", Json::Number(severity.to_string())),
+                ("source", "nonsense".into()),
+                ("message", message.into()),
+            ])
+        };
+        let mut errors = errors;
+        errors.extend(program.diagnose(&mut self.args.context()?));
+        errors.sort_by_key(|error| error.span.map(|span| span.start));
+        let mut diagnostics: Vec<Json> = errors
+            .into_iter()
+            .map(|error| diagnostic(error.span, "1", error.to_string()))
+            .collect();
+        diagnostics.extend(
+            program
+                .warnings()
+                .iter()
+                .map(|warning| diagnostic(warning.span, "2", warning.message.clone())),
+        );
+        send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+            ),
+        ]))
+        .map_err(|error| format!("EXEC ERROR: Language server failed: {}", error))?;
+        Ok(Json::Null)
+    }
+    fn hover(&self, uri: &str, params: &Json) -> Result<Json, String> {
+        let Some(program) = self.documents.get(uri) else {
+            return Ok(Json::Null);
+        };
+        let text = program.source();
+        let offset = offset(text, params.get("position"));
+        let Ok(queries) = program.queries(&mut self.args.context()?) else {
+            return Ok(Json::Null);
+        };
+        let hovered = program
+            .spans()
+            .iter()
+            .zip(queries)
+            .find(|(span, _)| span.start <= offset && offset <= span.end);
+        Ok(match hovered {
+            Some((span, (sql, _))) => Json::object([
+                (
+                    "contents",
+                    Json::object([
+                        ("kind", "markdown".into()),
+                        ("value", format!("```sql\n{}\n```", sql).into()),
+                    ]),
+                ),
+                ("range", range(text, *span)),
+            ]),
+            None => Json::Null,
+        })
+    }
+    fn completion(&self, uri: &str, params: &Json) -> Result<Json, String> {
+        let context = self.args.context()?;
+        let (Some(program), Some(schema)) = (self.documents.get(uri), &context.schema) else {
+            return Ok(Json::Array(vec![]));
+        };
+        let text = program.source();
+        let prefix: Vec<char> = text[..offset(text, params.get("position"))]
+            .chars()
+            .collect();
+        let mut word = prefix.len();
+        while word > 0 && is_identifier(prefix[word - 1]) {
+            word -= 1;
+        }
+        let (names, kind): (Vec<&str>, &str) = if word > 0 && is_statement(&prefix, word - 1) {
+            let names = schema.tables().iter().map(|table| table.name.as_str());
+            (names.collect(), "7")
+        } else {
+            let table = current_table(&prefix[..word]);
+            let names = schema
+                .tables()
+                .iter()
+                .filter(|candidate| Some(&candidate.name) == table.as_ref())
+                .flat_map(|table| table.columns.iter().map(String::as_str));
+            (names.collect(), "5")
+        };
+        Ok(Json::Array(
+            names
+                .into_iter()
+                .map(|name| {
+                    Json::object([
+                        ("label", name.into()),
+                        ("kind", Json::Number(kind.to_string())),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+}
+
+fn is_identifier(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+fn is_statement(prefix: &[char], index: usize) -> bool {
+    "+~-.".contains(prefix[index])
+        && (index == 0 || prefix[index - 1].is_whitespace() || "})".contains(prefix[index - 1]))
+}
+
+fn current_table(prefix: &[char]) -> Option<String> {
+    (0..prefix.len()).rev().find_map(|index| {
+        if !is_statement(prefix, index) {
+            return None;
+        }
+        let table: String = prefix[index + 1..]
+            .iter()
+            .take_while(|character| is_identifier(**character))
+            .collect();
+        (!table.is_empty()).then_some(table)
+    })
+}
+
+const TOKEN_TYPES: [&str; 6] = [
+    "keyword", "variable", "operator", "string", "number", "comment",
+];
+
+// Relative line, start, length, type and modifiers of every token, split at line breaks
+fn semantic_tokens(text: &str) -> Vec<Json> {
+    let mut data = vec![];
+    let (mut line, mut line_start, mut scanned) = (0, 0, 0);
+    let (mut previous_line, mut previous_start) = (0, 0);
+    for token in tokenize(text) {
+        let kind = match token.kind.category() {
+            Category::Keyword => 0,
+            Category::Ident => 1,
+            Category::Punctuation => 2,
+            Category::Literal if token.kind == TokenKind::String => 3,
+            Category::Literal => 4,
+            Category::Comment => 5,
+            Category::Invalid => continue,
+        };
+        let mut offset = token.span.start;
+        for piece in text[token.span.start..token.span.end].split('\n') {
+            for (index, character) in text[scanned..offset].char_indices() {
+                if character == '\n' {
+                    line += 1;
+                    line_start = scanned + index + 1;
+                }
+            }
+            scanned = offset;
+            let start = text[line_start..offset].chars().count();
+            let length = piece.trim_end_matches('\r').chars().count();
+            if length > 0 {
+                let delta = line - previous_line;
+                let start_delta = if delta == 0 {
+                    start - previous_start
+                } else {
+                    start
+                };
+                for value in [delta, start_delta, length, kind, 0] {
+                    data.push(Json::Number(value.to_string()));
+                }
+                (previous_line, previous_start) = (line, start);
+            }
+            offset += piece.len() + 1;
+        }
+    }
+    data
+}
+
+fn offset(text: &str, position: Option<&Json>) -> usize {
+    let number = |key: &str| {
+        position
+            .and_then(|position| position.get(key))
+            .and_then(|value| match value {
+                Json::Number(number) => number.parse().ok(),
+                _ => None,
+            })
+            .unwrap_or(0)
+    };
+    let (line, character) = (number("line"), number("character"));
+    let start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let line = text[start.min(text.len())..]
+        .split('\n')
+        .next()
+        .unwrap_or("");
+    let column = line
+        .char_indices()
+        .nth(character)
+        .map_or(line.len(), |(index, _)| index);
+    start + column
+}
+
+fn range(text: &str, span: Span) -> Json {
+    let position = |offset: usize| {
+        let before = &text[..offset.min(text.len())];
+        let line = before.matches('\n').count();
+        let character = before.rsplit('\n').next().unwrap_or("").chars().count();
+        Json::object([
+            ("line", Json::Number(line.to_string())),
+            ("character", Json::Number(character.to_string())),
+        ])
+    };
+    Json::object([("start", position(span.start)), ("end", position(span.end))])
+}
+
+fn receive(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+fn send(message: Json) -> io::Result<()> {
+    let body = message.to_string();
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions() {
+        let text = ".users { name }\n.orders {\n  total\n}";
+        let position = Json::object([
+            ("line", Json::Number("2".to_string())),
+            ("character", Json::Number("3".to_string())),
+        ]);
+        assert_eq!(offset(text, Some(&position)), 29);
+        assert_eq!(
+            range(text, Span { start: 16, end: 29 }).to_string(),
+            r#"{"start":{"line":1,"character":0},"end":{"line":2,"character":3}}"#
+        );
+        let prefix: Vec<char> = text[..29].chars().collect();
+        assert_eq!(current_table(&prefix), Some("orders".to_string()));
+        assert!(is_statement(&prefix, 16));
+        assert!(!is_statement(&[' ', 'u', '.'], 2));
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let data: Vec<String> = semantic_tokens(".users /* a\nb */ { id }")
+            .iter()
+            .map(Json::to_string)
+            .collect();
+        assert_eq!(
+            data.join(" "),
+            "0 0 1 2 0 0 1 5 1 0 0 6 4 5 0 1 0 4 5 0 0 5 1 2 0 0 2 2 1 0 0 3 1 2 0"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{build, failure, query_names, read, CompileArgs, Failure};
+use nonsense::json::Json;
+use nonsense::Program;
+use std::path::{Path, PathBuf};
+
+/// Every query of a directory, or of a single input, by name
+pub fn run(args: &CompileArgs) -> Result<String, Failure> {
+    let sources = match &args.input.file {
+        Some(path) if path.is_dir() => {
+            let mut files = Vec::new();
+            build::collect(path, None, &mut files);
+            files.sort();
+            files
+                .into_iter()
+                .map(|file| {
+                    let input = std::fs::read_to_string(&file).map_err(|error| {
+                        format!("EXEC ERROR: Failed to read {}: {}", file.display(), error)
+                    })?;
+                    let relative = file.strip_prefix(path).unwrap_or(&file).to_path_buf();
+                    Ok((relative, file, input))
+                })
+                .collect::<Result<Vec<_>, String>>()?
+        }
+        Some(path) if path.as_os_str() != "-" => {
+            let stem = PathBuf::from(path.file_stem().unwrap_or_default());
+            vec![(stem, path.clone(), read(&args.input)?)]
+        }
+        _ => vec![(
+            PathBuf::from("query"),
+            PathBuf::from("-"),
+            read(&args.input)?,
+        )],
+    };
+    let mut entries: Vec<(String, Json)> = vec![];
+    let mut files: Vec<&Path> = vec![];
+    for (relative, file, input) in &sources {
+        let name = file.display().to_string();
+        let prepare = || {
+            let mut context = args.context.context()?;
+            if context.placeholder.is_none() {
+                context.placeholder = Some(context.dialect.placeholder());
+            }
+            Ok::<_, String>(context)
+        };
+        let mut context = prepare()?;
+        let prepared = Program::new(input)
+            .and_then(|program| program.prepare(&mut context))
+            .map_err(|error| failure(&name, input, error, &mut prepare().unwrap_or_default()))?;
+        let names = query_names(&relative.with_extension(""), &prepared);
+        for (query, query_name) in prepared.into_iter().zip(names) {
+            if let Some(index) = entries.iter().position(|(key, _)| *key == query_name) {
+                return Err(Failure::from(format!(
+                    "EXEC ERROR: query {} is defined in both {} and {}",
+                    query_name,
+                    files[index].display(),
+                    file.display()
+                )));
+            }
+            let parameters = query.parameters.into_iter().map(|parameter| {
+                Json::object([
+                    ("name", parameter.name.into()),
+                    ("type", parameter.kind.into()),
+                ])
+            });
+            let tables = query.tables.into_iter().map(Json::String);
+            let entry = Json::object([
+                ("file", file.display().to_string().into()),
+                ("sql", query.sql.into()),
+                ("parameters", Json::Array(parameters.collect())),
+                ("tables", Json::Array(tables.collect())),
+            ]);
+            entries.push((query_name, entry));
+            files.push(file);
+        }
+    }
+    let manifest = Json::Object(entries);
+    let extension = args.output.as_deref().and_then(Path::extension);
+    match extension.is_some_and(|extension| extension == "toml") {
+        true => Ok(match toml(manifest) {
+            Some(toml::Value::Table(table)) => table.to_string(),
+            _ => String::new(),
+        }),
+        false => Ok(format!("{:#}\n", manifest)),
+    }
+}
+
+// TOML has no null, keys holding one are left out
+fn toml(json: Json) -> Option<toml::Value> {
+    Some(match json {
+        Json::Null => return None,
+        Json::Bool(value) => toml::Value::Boolean(value),
+        Json::Number(number) => match number.parse::<i64>() {
+            Ok(integer) => toml::Value::Integer(integer),
+            Err(_) => toml::Value::Float(number.parse().ok()?),
+        },
+        Json::String(string) => toml::Value::String(string),
+        Json::Array(values) => toml::Value::Array(values.into_iter().filter_map(toml).collect()),
+        Json::Object(fields) => toml::Value::Table(
+            fields
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, toml(value)?)))
+                .collect(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_manifest() {
+        let directory =
+            std::env::temp_dir().join(format!("nonsense-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("users")).unwrap();
+        std::fs::write(
+            directory.join("users/active.ns"),
+            "query getUsers: .users(org: 1) { name }\n.users u left join .orders o on { o.user_id = u.id } { u.name }",
+        )
+        .unwrap();
+        std::fs::write(directory.join("posts.ns"), "+posts { title: \"Hi\" }").unwrap();
+        let compile = |output: &str| {
+            let argv = ["nonsense", "--emit", "manifest"];
+            let mut cli = crate::Cli::parse_from(argv);
+            cli.compile.input.file = Some(directory.clone());
+            cli.compile.output = Some(PathBuf::from(output));
+            run(&cli.compile).map_err(|failure| failure.message)
+        };
+        let manifest = Json::parse(&compile("queries.json").unwrap()).unwrap();
+        let Json::Object(entries) = &manifest else {
+            unreachable!();
+        };
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["posts", "getUsers", "users_active_2"]);
+        let query = manifest.get("getUsers").unwrap();
+        assert_eq!(
+            query.get("sql").unwrap().as_str(),
+            Some("SELECT name FROM users WHERE org = $1;")
+        );
+        assert_eq!(
+            format!("{}", query.get("parameters").unwrap()),
+            r#"[{"name":"org","type":"number"}]"#
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                manifest
+                    .get("users_active_2")
+                    .unwrap()
+                    .get("tables")
+                    .unwrap()
+            ),
+            r#"["users","orders"]"#
+        );
+        let toml = compile("queries.toml").unwrap();
+        assert!(toml.contains("[getUsers]\n"), "{}", toml);
+        std::fs::write(directory.join("copy.ns"), "query getUsers: .users { id }").unwrap();
+        assert!(compile("queries.json")
+            .unwrap_err()
+            .starts_with("EXEC ERROR: query getUsers is defined in both"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,397 @@
+use crate::arena::Arena;
+use crate::json::Json;
+use crate::{
+    Context, DeleteStatement, DotStatement, PairStatement, Program, Result, Stmt, TokenKind,
+    UpdateStatement, WildcardStatement,
+};
+
+pub fn compile(program: &Program, context: &mut Context) -> Result<String> {
+    let program = program.cased(context);
+    program.check(context)?;
+    let commands = program
+        .statements
+        .iter()
+        .zip(&program.spans)
+        .map(|(statement, span)| {
+            command(&program.arena, statement, context).map_err(|error| error.at(*span))
+        })
+        .collect::<Result<Vec<String>>>()?;
+    Ok(commands.join("\n"))
+}
+
+fn command(arena: &Arena, statement: &Stmt, context: &Context) -> Result<String> {
+    match statement {
+        Stmt::Dot(dot) => find(arena, dot, context),
+        Stmt::Insert(insert) if insert.conflict.is_some() || insert.returning.is_some() => {
+            Err(error!(
+                Eval,
+                "on conflict and returning are not supported by the mongodb target"
+            ))
+        }
+        Stmt::Insert(insert) => Ok(format!(
+            "db.{}.insertOne({})",
+            insert.ident.literal,
+            shell(&document(arena, &insert.values, context)?)
+        )),
+        Stmt::Update(UpdateStatement {
+            returning: Some(_), ..
+        })
+        | Stmt::Delete(DeleteStatement {
+            returning: Some(_), ..
+        }) => Err(error!(
+            Eval,
+            "on conflict and returning are not supported by the mongodb target"
+        )),
+        Stmt::Update(update) => Ok(format!(
+            "db.{}.updateMany({}, {})",
+            update.ident.literal,
+            shell(&document(arena, &update.filters, context)?),
+            shell(&Json::Object(vec![(
+                "$set".to_string(),
+                document(arena, &update.values, context)?
+            )]))
+        )),
+        Stmt::Delete(delete) => {
+            if delete.is_unfiltered() && !context.allow_full_delete {
+                return Err(error!(
+                    Eval,
+                    "refusing to delete every row from {}, pass --allow-full-delete",
+                    delete.ident.literal
+                ));
+            }
+            let mut filter = document(arena, &delete.filters, context)?;
+            if let Some(condition) = delete.condition {
+                filter = and(filter, predicate(arena, &arena[condition], context)?);
+            }
+            Ok(format!(
+                "db.{}.deleteMany({})",
+                delete.ident.literal,
+                shell(&filter)
+            ))
+        }
+        statement => Err(unsupported(statement)),
+    }
+}
+
+fn find(arena: &Arena, dot: &DotStatement, context: &Context) -> Result<String> {
+    if dot.alias.is_some()
+        || !dot.joins.is_empty()
+        || dot.within.is_some()
+        || dot.distinct
+        || !dot.grouping.is_empty()
+    {
+        return Err(error!(
+            Eval,
+            "aliases, joins, in, distinct and group by are not supported by the mongodb target"
+        ));
+    }
+    let mut filter = document(arena, &dot.filters, context)?;
+    if let Some(condition) = dot.condition {
+        filter = and(filter, predicate(arena, &arena[condition], context)?);
+    }
+    let mut arguments = vec![shell(&filter)];
+    if let Some(projection) = projection(arena, &arena[dot.block.properties])? {
+        arguments.push(shell(&projection));
+    }
+    let mut command = format!("db.{}.find({})", dot.ident.literal, arguments.join(", "));
+    if !dot.ordering.is_empty() {
+        let sort = dot.ordering.iter().map(|order| {
+            let direction = match order.direction {
+                Some(TokenKind::Desc) => "-1",
+                _ => "1",
+            };
+            (order.ident.literal.to_string(), number(direction))
+        });
+        command.push_str(&format!(".sort({})", shell(&Json::Object(sort.collect()))));
+    }
+    if let Some(offset) = &dot.offset {
+        command.push_str(&format!(".skip({})", offset.literal));
+    }
+    if let Some(limit) = &dot.limit {
+        command.push_str(&format!(".limit({})", limit.literal));
+    }
+    Ok(command)
+}
+
+fn projection(arena: &Arena, properties: &[Stmt]) -> Result<Option<Json>> {
+    let mut fields = vec![];
+    for property in properties {
+        match property {
+            Stmt::Identifier(identifier) => {
+                fields.push((identifier.literal.to_string(), number("1")));
+            }
+            Stmt::Alias(alias) => match &arena[alias.selection] {
+                Stmt::Identifier(identifier) => fields.push((
+                    alias.alias.literal.to_string(),
+                    Json::String(format!("${}", identifier.literal)),
+                )),
+                selection => return Err(unsupported(selection)),
+            },
+            Stmt::Wildcard(WildcardStatement { exclude }) => {
+                let excluded = exclude.iter();
+                fields.extend(excluded.map(|ident| (ident.literal.to_string(), number("0"))));
+            }
+            property => return Err(unsupported(property)),
+        }
+    }
+    let wildcard = properties
+        .iter()
+        .any(|property| matches!(property, Stmt::Wildcard(_)));
+    if wildcard && fields.iter().any(|(_, value)| *value == number("1")) {
+        return Err(error!(
+            Eval,
+            "the mongodb target cannot mix * with other selections"
+        ));
+    }
+    Ok((!fields.is_empty()).then_some(Json::Object(fields)))
+}
+
+fn document(arena: &Arena, pairs: &[PairStatement], context: &Context) -> Result<Json> {
+    let fields = pairs
+        .iter()
+        .map(|pair| {
+            let value = value(&arena[pair.value], context)?;
+            Ok((pair.ident.literal.to_string(), value))
+        })
+        .collect::<Result<Vec<(String, Json)>>>()?;
+    Ok(Json::Object(fields))
+}
+
+fn predicate(arena: &Arena, statement: &Stmt, context: &Context) -> Result<Json> {
+    let field = |name: &str, condition: Json| Json::Object(vec![(name.to_string(), condition)]);
+    match statement {
+        Stmt::Identifier(identifier) => Ok(field(&identifier.literal, Json::Bool(true))),
+        Stmt::Prefix(prefix) if prefix.operator == TokenKind::Not => Ok(field(
+            "$nor",
+            Json::Array(vec![predicate(arena, &arena[prefix.right], context)?]),
+        )),
+        Stmt::Infix(infix) if infix.operator == TokenKind::And => Ok(and(
+            predicate(arena, &arena[infix.left], context)?,
+            predicate(arena, &arena[infix.right], context)?,
+        )),
+        Stmt::Infix(infix) if infix.operator == TokenKind::Or => {
+            let mut branches = vec![];
+            for side in [infix.left, infix.right] {
+                match predicate(arena, &arena[side], context)? {
+                    Json::Object(mut fields) if fields.len() == 1 && fields[0].0 == "$or" => {
+                        if let (_, Json::Array(nested)) = fields.remove(0) {
+                            branches.extend(nested);
+                        }
+                    }
+                    branch => branches.push(branch),
+                }
+            }
+            Ok(field("$or", Json::Array(branches)))
+        }
+        Stmt::Infix(infix) => {
+            let (column, operator, operand) = match (&arena[infix.left], &arena[infix.right]) {
+                (Stmt::Identifier(column), operand) => (column, infix.operator, operand),
+                (operand, Stmt::Identifier(column)) => (column, flip(infix.operator), operand),
+                _ => return Err(unsupported(statement)),
+            };
+            let operator = match operator {
+                TokenKind::Eq => return Ok(field(&column.literal, value(operand, context)?)),
+                TokenKind::NotEq => "$ne",
+                TokenKind::Lt => "$lt",
+                TokenKind::LtEq => "$lte",
+                TokenKind::Gt => "$gt",
+                TokenKind::GtEq => "$gte",
+                _ => return Err(unsupported(statement)),
+            };
+            Ok(field(
+                &column.literal,
+                field(operator, value(operand, context)?),
+            ))
+        }
+        Stmt::IsNull(is_null) => {
+            let Stmt::Identifier(column) = &arena[is_null.operand] else {
+                return Err(unsupported(statement));
+            };
+            Ok(field(
+                &column.literal,
+                match is_null.negated {
+                    true => field("$ne", Json::Null),
+                    false => Json::Null,
+                },
+            ))
+        }
+        Stmt::In(in_expression) => {
+            let Stmt::Identifier(column) = &arena[in_expression.operand] else {
+                return Err(unsupported(statement));
+            };
+            let values = arena[in_expression.values]
+                .iter()
+                .map(|item| value(item, context))
+                .collect::<Result<Vec<Json>>>()?;
+            Ok(field(&column.literal, field("$in", Json::Array(values))))
+        }
+        Stmt::Between(between) => {
+            let Stmt::Identifier(column) = &arena[between.operand] else {
+                return Err(unsupported(statement));
+            };
+            Ok(field(
+                &column.literal,
+                Json::Object(vec![
+                    ("$gte".to_string(), value(&arena[between.low], context)?),
+                    ("$lte".to_string(), value(&arena[between.high], context)?),
+                ]),
+            ))
+        }
+        Stmt::Like(like) => {
+            let (Stmt::Identifier(column), Json::String(pattern)) =
+                (&arena[like.operand], value(&arena[like.pattern], context)?)
+            else {
+                return Err(unsupported(statement));
+            };
+            let mut condition = vec![("$regex".to_string(), Json::String(regex(&pattern)))];
+            if like.insensitive {
+                condition.push(("$options".to_string(), "i".into()));
+            }
+            Ok(field(&column.literal, Json::Object(condition)))
+        }
+        statement => Err(unsupported(statement)),
+    }
+}
+
+fn value(statement: &Stmt, context: &Context) -> Result<Json> {
+    match statement {
+        Stmt::Literal(literal) if literal.kind == TokenKind::Number => Ok(number(&literal.literal)),
+        Stmt::Literal(literal) => Ok(Json::String(literal.literal.clone())),
+        Stmt::Variable(variable) => match context.variables.get(&variable.name) {
+            Some(value @ (Json::Number(_) | Json::String(_))) => Ok(value.clone()),
+            Some(_) => Err(error!(
+                Eval,
+                "variable ${} must be a number or a string", variable.name
+            )),
+            None => Err(error!(Eval, "unbound variable ${}", variable.name)),
+        },
+        Stmt::Env(env) => Ok(Json::String(env.resolve(context)?)),
+        statement => Err(unsupported(statement)),
+    }
+}
+
+fn and(left: Json, right: Json) -> Json {
+    match (left, right) {
+        (Json::Object(left), right) if left.is_empty() => right,
+        (left, Json::Object(right)) if right.is_empty() => left,
+        (left, right) => Json::Object(vec![("$and".to_string(), Json::Array(vec![left, right]))]),
+    }
+}
+
+fn flip(operator: TokenKind) -> TokenKind {
+    match operator {
+        TokenKind::Lt => TokenKind::Gt,
+        TokenKind::LtEq => TokenKind::GtEq,
+        TokenKind::Gt => TokenKind::Lt,
+        TokenKind::GtEq => TokenKind::LtEq,
+        operator => operator,
+    }
+}
+
+fn number(value: &str) -> Json {
+    Json::Number(value.to_string())
+}
+
+fn regex(pattern: &str) -> String {
+    let mut regex = String::from("^");
+    for character in pattern.chars() {
+        match character {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            character if "\\^$.|?*+()[]{}".contains(character) => {
+                regex.push('\\');
+                regex.push(character);
+            }
+            character => regex.push(character),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn unsupported(statement: &Stmt) -> crate::CompileError {
+    error!(
+        Eval,
+        "{} are not supported by the mongodb target",
+        statement.noun()
+    )
+}
+
+// Mongo shell syntax, keys are only quoted when they have to be
+fn shell(json: &Json) -> String {
+    match json {
+        Json::Array(items) => {
+            let items: Vec<String> = items.iter().map(shell).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Json::Object(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, value)| {
+                    let plain = key.chars().enumerate().all(|(index, character)| {
+                        character == '_'
+                            || character == '$'
+                            || character.is_ascii_alphabetic()
+                            || (index > 0 && character.is_ascii_digit())
+                    });
+                    let key = match plain && !key.is_empty() {
+                        true => key.clone(),
+                        false => Json::String(key.clone()).to_string(),
+                    };
+                    format!("{}: {}", key, shell(value))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+        json => json.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mongodb(input: &str) -> String {
+        compile(&Program::new(input).unwrap(), &mut Context::default()).unwrap()
+    }
+
+    #[test]
+    fn test_mongodb() {
+        assert_eq!(
+            mongodb(".users where { age > 18 } { name }"),
+            "db.users.find({age: {$gt: 18}}, {name: 1})"
+        );
+        assert_eq!(
+            mongodb(
+                r#".users(org: 2) where { 65 >= age or name ilike "a%" or deleted_at is null } { *, !ssn } order by name desc limit 10 offset 20"#
+            ),
+            r#"db.users.find({$and: [{org: 2}, {$or: [{age: {$lte: 65}}, {name: {$regex: "^a.*$", $options: "i"}}, {deleted_at: null}]}]}, {ssn: 0}).sort({name: -1}).skip(20).limit(10)"#
+        );
+        assert_eq!(
+            mongodb("+users { name: \"Ada\" } ~users(id: 1) { age: 36 } -users(id: 1)"),
+            "db.users.insertOne({name: \"Ada\"})\ndb.users.updateMany({id: 1}, {$set: {age: 36}})\ndb.users.deleteMany({id: 1})"
+        );
+        assert_eq!(
+            mongodb(".users delete (org: 2) where { age < 18 }"),
+            "db.users.deleteMany({$and: [{org: 2}, {age: {$lt: 18}}]})"
+        );
+    }
+
+    #[test]
+    fn test_mongodb_unsupported() {
+        let program = Program::new(".orders { count(id) }").unwrap();
+        assert_eq!(
+            compile(&program, &mut Context::default())
+                .unwrap_err()
+                .to_string(),
+            "EVAL ERROR: function calls are not supported by the mongodb target"
+        );
+        let program = Program::new(".users(id: now()) { id }").unwrap();
+        assert_eq!(
+            compile(&program, &mut Context::default())
+                .unwrap_err()
+                .to_string(),
+            "EVAL ERROR: dates and times are not supported by the mongodb target"
+        );
+    }
+}
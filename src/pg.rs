@@ -0,0 +1,56 @@
+use crate::{table, Format};
+use postgres::{Client, NoTls, SimpleQueryMessage};
+use std::io::Write;
+
+pub fn run(url: &str, queries: &[String], format: Format) -> Result<(), String> {
+    let mut client = Client::connect(url, NoTls)
+        .map_err(|error| format!("EXEC ERROR: Failed to connect: {}", error))?;
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let written = |error: std::io::Error| format!("EXEC ERROR: Failed to write output: {}", error);
+    for sql in queries {
+        let messages = client
+            .simple_query(sql)
+            .map_err(|error| format!("EXEC ERROR: {}: {}", sql, error))?;
+        let mut columns: Vec<String> = vec![];
+        // A table needs every row to size its columns, the other formats stream
+        let mut rows = vec![];
+        for message in messages {
+            match message {
+                SimpleQueryMessage::RowDescription(description) => {
+                    columns = description
+                        .iter()
+                        .map(|column| column.name().to_string())
+                        .collect();
+                    if let Format::Csv = format {
+                        writeln!(stdout, "{}", table::header(&columns)).map_err(written)?;
+                    }
+                }
+                SimpleQueryMessage::Row(row) => {
+                    let values: Vec<Option<String>> = (0..row.len())
+                        .map(|index| row.get(index).map(str::to_string))
+                        .collect();
+                    match format {
+                        Format::Table => rows.push(values),
+                        Format::Csv => {
+                            writeln!(stdout, "{}", table::csv(&values)).map_err(written)?
+                        }
+                        Format::Json => writeln!(stdout, "{}", table::json(&columns, &values))
+                            .map_err(written)?,
+                    }
+                }
+                SimpleQueryMessage::CommandComplete(_)
+                    if matches!(format, Format::Table) && !columns.is_empty() =>
+                {
+                    write!(stdout, "{}", table::write(format, &columns, &rows)).map_err(written)?;
+                    rows.clear();
+                }
+                SimpleQueryMessage::CommandComplete(count) => {
+                    eprintln!("({} {})", count, if count == 1 { "row" } else { "rows" });
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,460 @@
+use crate::arena::Arena;
+use crate::json::Json;
+use crate::{
+    BlockStatement, Context, DotStatement, IdentifierStatement, PairStatement, Program, Result,
+    ReturningStatement, Stmt, TemporalKind, TokenKind,
+};
+
+/// Describes what each query reads or writes, without committing to a dialect
+pub fn plan(program: &Program, context: &mut Context) -> Result<Json> {
+    let program = program.cased(context);
+    program.check(context)?;
+    let plans = program
+        .statements
+        .iter()
+        .zip(&program.spans)
+        .map(|(statement, span)| {
+            describe(&program.arena, statement, context).map_err(|error| error.at(*span))
+        })
+        .collect::<Result<Vec<Json>>>()?;
+    Ok(Json::Array(plans))
+}
+
+fn describe(arena: &Arena, statement: &Stmt, context: &Context) -> Result<Json> {
+    match statement {
+        Stmt::Dot(dot) => select(arena, dot, context),
+        Stmt::Insert(insert) => Ok(Json::object([
+            ("operation", "insert".into()),
+            ("table", name(&insert.ident)),
+            ("columns", columns(&insert.values)),
+            (
+                "conflict",
+                insert
+                    .conflict
+                    .as_ref()
+                    .map(|conflict| {
+                        Json::object([
+                            (
+                                "columns",
+                                Json::Array(conflict.columns.iter().map(name).collect()),
+                            ),
+                            (
+                                "update",
+                                Json::Array(conflict.update.iter().map(name).collect()),
+                            ),
+                        ])
+                    })
+                    .into(),
+            ),
+            ("returning", returning(&insert.returning)),
+        ])),
+        Stmt::Update(update) => Ok(Json::object([
+            ("operation", "update".into()),
+            ("table", name(&update.ident)),
+            ("columns", columns(&update.values)),
+            ("predicates", filters(arena, &update.filters, context)?),
+            ("returning", returning(&update.returning)),
+        ])),
+        Stmt::Delete(delete) => {
+            let mut predicates = match filters(arena, &delete.filters, context)? {
+                Json::Array(predicates) => predicates,
+                _ => vec![],
+            };
+            if let Some(condition) = delete.condition {
+                conjuncts(arena, &arena[condition], context, &mut predicates)?;
+            }
+            Ok(Json::object([
+                ("operation", "delete".into()),
+                ("table", name(&delete.ident)),
+                ("predicates", Json::Array(predicates)),
+                ("returning", returning(&delete.returning)),
+            ]))
+        }
+        Stmt::Union(union) => {
+            let mut queries = vec![select(arena, &union.right, context)?];
+            let mut left = &arena[union.left];
+            while let Stmt::Union(nested) = left {
+                queries.push(select(arena, &nested.right, context)?);
+                left = &arena[nested.left];
+            }
+            queries.push(describe(arena, left, context)?);
+            queries.reverse();
+            Ok(Json::object([
+                ("operation", "union".into()),
+                ("all", union.all.into()),
+                ("queries", Json::Array(queries)),
+            ]))
+        }
+        Stmt::With(with) => {
+            let tables = with
+                .tables
+                .iter()
+                .map(|table| {
+                    Ok(Json::object([
+                        ("name", name(&table.name)),
+                        ("query", describe(arena, &table.query, context)?),
+                    ]))
+                })
+                .collect::<Result<Vec<Json>>>()?;
+            let mut plan = describe(arena, &arena[with.statement], context)?;
+            if let Json::Object(fields) = &mut plan {
+                fields.push(("with".to_string(), Json::Array(tables)));
+            }
+            Ok(plan)
+        }
+        statement => Err(unsupported(statement)),
+    }
+}
+
+fn select(arena: &Arena, dot: &DotStatement, context: &Context) -> Result<Json> {
+    let mut predicates = match filters(arena, &dot.filters, context)? {
+        Json::Array(predicates) => predicates,
+        _ => vec![],
+    };
+    if let Some(condition) = dot.condition {
+        conjuncts(arena, &arena[condition], context, &mut predicates)?;
+    }
+    if let Some(within) = &dot.within {
+        predicates.push(Json::object([
+            ("operator", "in".into()),
+            ("query", name(&within.name)),
+        ]));
+    }
+    let ordering = dot.ordering.iter().map(|order| {
+        let direction = match order.direction {
+            Some(TokenKind::Desc) => "desc",
+            _ => "asc",
+        };
+        Json::object([
+            ("column", name(&order.ident)),
+            ("direction", direction.into()),
+        ])
+    });
+    let (selections, implicit) = block(arena, &dot.block, context)?;
+    let mut joins = vec![];
+    for join in &dot.joins {
+        let mut predicates = vec![];
+        if let Some(condition) = join.condition {
+            conjuncts(arena, &arena[condition], context, &mut predicates)?;
+        }
+        joins.push(Json::object([
+            ("table", name(&join.table)),
+            ("alias", join.alias.as_ref().map(name).into()),
+            ("kind", join.kind.word().into()),
+            ("predicates", Json::Array(predicates)),
+        ]));
+    }
+    joins.extend(implicit);
+    Ok(Json::object([
+        ("operation", "select".into()),
+        ("table", name(&dot.ident)),
+        ("alias", dot.alias.as_ref().map(name).into()),
+        ("distinct", dot.distinct.into()),
+        (
+            "distinct_on",
+            Json::Array(dot.distinct_on.iter().map(name).collect()),
+        ),
+        ("columns", Json::Array(selections)),
+        ("joins", Json::Array(joins)),
+        ("predicates", Json::Array(predicates)),
+        (
+            "grouping",
+            Json::Array(dot.grouping.iter().map(name).collect()),
+        ),
+        ("ordering", Json::Array(ordering.collect())),
+        (
+            "limit",
+            dot.limit
+                .as_ref()
+                .map(|limit| number(&limit.literal))
+                .into(),
+        ),
+        (
+            "offset",
+            dot.offset
+                .as_ref()
+                .map(|offset| number(&offset.literal))
+                .into(),
+        ),
+    ]))
+}
+
+fn block(
+    arena: &Arena,
+    block: &BlockStatement,
+    context: &Context,
+) -> Result<(Vec<Json>, Vec<Json>)> {
+    let mut selections = vec![];
+    let mut joins = vec![];
+    for property in &arena[block.properties] {
+        match property {
+            Stmt::Join(join) => {
+                let (columns, nested) = self::block(arena, &join.block, context)?;
+                joins.push(Json::object([
+                    ("table", name(&join.ident)),
+                    ("columns", Json::Array(columns)),
+                    ("joins", Json::Array(nested)),
+                ]));
+            }
+            Stmt::Alias(alias) => {
+                let selection = &arena[alias.selection];
+                let Json::Object(mut fields) = operand(arena, selection, context)? else {
+                    return Err(unsupported(selection));
+                };
+                fields.push(("alias".to_string(), name(&alias.alias)));
+                selections.push(Json::Object(fields));
+            }
+            Stmt::Wildcard(wildcard) => selections.push(Json::object([
+                ("wildcard", true.into()),
+                (
+                    "exclude",
+                    Json::Array(wildcard.exclude.iter().map(name).collect()),
+                ),
+            ])),
+            property => selections.push(operand(arena, property, context)?),
+        }
+    }
+    Ok((selections, joins))
+}
+
+fn filters(arena: &Arena, pairs: &[PairStatement], context: &Context) -> Result<Json> {
+    let predicates = pairs
+        .iter()
+        .map(|pair| {
+            Ok(Json::object([
+                ("column", name(&pair.ident)),
+                ("operator", "=".into()),
+                ("value", operand(arena, &arena[pair.value], context)?),
+            ]))
+        })
+        .collect::<Result<Vec<Json>>>()?;
+    Ok(Json::Array(predicates))
+}
+
+fn conjuncts(
+    arena: &Arena,
+    statement: &Stmt,
+    context: &Context,
+    predicates: &mut Vec<Json>,
+) -> Result<()> {
+    match statement {
+        Stmt::Infix(infix) if infix.operator == TokenKind::And => {
+            conjuncts(arena, &arena[infix.left], context, predicates)?;
+            conjuncts(arena, &arena[infix.right], context, predicates)
+        }
+        statement => {
+            predicates.push(predicate(arena, statement, context)?);
+            Ok(())
+        }
+    }
+}
+
+fn predicate(arena: &Arena, statement: &Stmt, context: &Context) -> Result<Json> {
+    let compare = |column: &Stmt, operator: &str, value: Json| -> Result<Json> {
+        Ok(Json::object([
+            ("column", subject(arena, column, context)?),
+            ("operator", operator.into()),
+            ("value", value),
+        ]))
+    };
+    match statement {
+        Stmt::Identifier(identifier) => Ok(Json::object([
+            ("column", name(identifier)),
+            ("operator", "=".into()),
+            ("value", true.into()),
+        ])),
+        Stmt::Prefix(prefix) if prefix.operator == TokenKind::Not => Ok(Json::object([
+            ("operator", "not".into()),
+            (
+                "operands",
+                Json::Array(vec![predicate(arena, &arena[prefix.right], context)?]),
+            ),
+        ])),
+        Stmt::Infix(infix) if matches!(infix.operator, TokenKind::And | TokenKind::Or) => {
+            let operator = match infix.operator {
+                TokenKind::And => "and",
+                _ => "or",
+            };
+            let mut operands = vec![];
+            for side in [&arena[infix.left], &arena[infix.right]] {
+                match side {
+                    Stmt::Infix(nested) if nested.operator == infix.operator => {
+                        if let Json::Object(mut fields) = predicate(arena, side, context)? {
+                            if let Some((_, Json::Array(nested))) = fields.pop() {
+                                operands.extend(nested);
+                            }
+                        }
+                    }
+                    side => operands.push(predicate(arena, side, context)?),
+                }
+            }
+            Ok(Json::object([
+                ("operator", operator.into()),
+                ("operands", Json::Array(operands)),
+            ]))
+        }
+        Stmt::Infix(infix) => {
+            let (column, operator, value) = match (&arena[infix.left], &arena[infix.right]) {
+                (value, column @ Stmt::Identifier(_)) if !matches!(value, Stmt::Identifier(_)) => {
+                    (column, flip(infix.operator), value)
+                }
+                (column, value) => (column, infix.operator, value),
+            };
+            let operator = match operator {
+                TokenKind::Eq => "=",
+                TokenKind::NotEq => "!=",
+                TokenKind::Lt => "<",
+                TokenKind::LtEq => "<=",
+                TokenKind::Gt => ">",
+                TokenKind::GtEq => ">=",
+                _ => return Err(unsupported(statement)),
+            };
+            compare(column, operator, operand(arena, value, context)?)
+        }
+        Stmt::IsNull(is_null) => Ok(Json::object([
+            ("column", subject(arena, &arena[is_null.operand], context)?),
+            (
+                "operator",
+                match is_null.negated {
+                    true => "is not null",
+                    false => "is null",
+                }
+                .into(),
+            ),
+        ])),
+        Stmt::In(in_expression) => {
+            let values = arena[in_expression.values]
+                .iter()
+                .map(|value| operand(arena, value, context))
+                .collect::<Result<Vec<Json>>>()?;
+            compare(&arena[in_expression.operand], "in", Json::Array(values))
+        }
+        Stmt::Between(between) => compare(
+            &arena[between.operand],
+            "between",
+            Json::Array(vec![
+                operand(arena, &arena[between.low], context)?,
+                operand(arena, &arena[between.high], context)?,
+            ]),
+        ),
+        Stmt::Like(like) => compare(
+            &arena[like.operand],
+            match like.insensitive {
+                true => "ilike",
+                false => "like",
+            },
+            operand(arena, &arena[like.pattern], context)?,
+        ),
+        statement => Err(unsupported(statement)),
+    }
+}
+
+fn operand(arena: &Arena, statement: &Stmt, context: &Context) -> Result<Json> {
+    match statement {
+        Stmt::Identifier(identifier) => Ok(Json::object([("column", name(identifier))])),
+        Stmt::Literal(literal) if literal.kind == TokenKind::Number => Ok(number(&literal.literal)),
+        Stmt::Literal(literal) => Ok(literal.literal.as_str().into()),
+        Stmt::Variable(variable) => match context.variables.get(&variable.name) {
+            Some(value) => Ok(value.clone()),
+            None => Err(error!(Eval, "unbound variable ${}", variable.name)),
+        },
+        Stmt::Env(env) => Ok(env.resolve(context)?.into()),
+        Stmt::Call(call) => {
+            let arguments = arena[call.arguments]
+                .iter()
+                .map(|argument| match argument {
+                    Stmt::Wildcard(_) => Ok("*".into()),
+                    argument => operand(arena, argument, context),
+                })
+                .collect::<Result<Vec<Json>>>()?;
+            Ok(Json::object([
+                ("function", call.function.literal.to_lowercase().into()),
+                ("arguments", Json::Array(arguments)),
+            ]))
+        }
+        Stmt::Subquery(subquery) => Ok(Json::object([(
+            "query",
+            select(arena, &subquery.query, context)?,
+        )])),
+        Stmt::Temporal(temporal) => Ok(match temporal.kind {
+            TemporalKind::Date => Json::object([("date", temporal.value.as_str().into())]),
+            TemporalKind::Timestamp => {
+                Json::object([("timestamp", temporal.value.as_str().into())])
+            }
+            TemporalKind::Interval => Json::object([("interval", temporal.value.as_str().into())]),
+            TemporalKind::Now => Json::object([
+                ("function", "now".into()),
+                ("arguments", Json::Array(Vec::new())),
+            ]),
+        }),
+        statement => Err(unsupported(statement)),
+    }
+}
+
+// Plain columns are named directly, anything else is described as an operand
+fn subject(arena: &Arena, statement: &Stmt, context: &Context) -> Result<Json> {
+    match statement {
+        Stmt::Identifier(identifier) => Ok(name(identifier)),
+        statement => operand(arena, statement, context),
+    }
+}
+
+fn flip(operator: TokenKind) -> TokenKind {
+    match operator {
+        TokenKind::Lt => TokenKind::Gt,
+        TokenKind::LtEq => TokenKind::GtEq,
+        TokenKind::Gt => TokenKind::Lt,
+        TokenKind::GtEq => TokenKind::LtEq,
+        operator => operator,
+    }
+}
+
+fn name(identifier: &IdentifierStatement) -> Json {
+    identifier.literal.to_string().into()
+}
+
+fn number(value: &str) -> Json {
+    Json::Number(value.to_string())
+}
+
+fn columns(pairs: &[PairStatement]) -> Json {
+    Json::Array(pairs.iter().map(|pair| name(&pair.ident)).collect())
+}
+
+fn returning(returning: &Option<ReturningStatement>) -> Json {
+    returning
+        .as_ref()
+        .map(|returning| Json::Array(returning.columns.iter().map(name).collect()))
+        .into()
+}
+
+fn unsupported(statement: &Stmt) -> crate::CompileError {
+    let name = match statement {
+        Stmt::Infix(_) => "arithmetic",
+        statement => statement.noun(),
+    };
+    error!(Eval, "{} cannot be described in a plan", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(input: &str) -> String {
+        super::plan(&Program::new(input).unwrap(), &mut Context::default())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_plan() {
+        assert_eq!(
+            plan(".users(org: 2) u where { 18 <= age and (name like \"A%\" or deleted_at is null) } { u.name, count(*) as total } group by u.name order by total desc limit 10"),
+            r#"[{"operation":"select","table":"users","alias":"u","distinct":false,"distinct_on":[],"columns":[{"column":"u.name"},{"function":"count","arguments":["*"],"alias":"total"}],"joins":[],"predicates":[{"column":"org","operator":"=","value":2},{"column":"age","operator":">=","value":18},{"operator":"or","operands":[{"column":"name","operator":"like","value":"A%"},{"column":"deleted_at","operator":"is null"}]}],"grouping":["u.name"],"ordering":[{"column":"total","direction":"desc"}],"limit":10,"offset":null}]"#
+        );
+        assert_eq!(
+            plan("~users(id: 1) { name: \"Ada\" } -posts(id: 3)"),
+            r#"[{"operation":"update","table":"users","columns":["name"],"predicates":[{"column":"id","operator":"=","value":1}],"returning":null},{"operation":"delete","table":"posts","predicates":[{"column":"id","operator":"=","value":3}],"returning":null}]"#
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use crate::arena::Arena;
+use crate::{
+    AliasStatement, BlockStatement, Context, DotStatement, FunctionCall, IdentifierStatement,
+    InfixExpression, IsNullExpression, LikeExpression, LiteralStatement, OrderStatement,
+    PairStatement, PrefixExpression, Program, Result, Stmt, TokenKind,
+};
+
+/// Builds the same select the parser produces for `.table(filters) where { } { columns }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    dot: DotStatement,
+    arena: Arena,
+    // siblings share a slice of the arena, so the block is only allocated once it's complete
+    properties: Vec<Stmt>,
+}
+impl Query {
+    pub fn table(name: &str) -> Self {
+        Self {
+            dot: DotStatement {
+                ident: identifier(name),
+                filters: vec![],
+                alias: None,
+                joins: vec![],
+                condition: None,
+                within: None,
+                distinct: false,
+                distinct_on: vec![],
+                block: BlockStatement::default(),
+                grouping: vec![],
+                ordering: vec![],
+                limit: None,
+                offset: None,
+            },
+            arena: Arena::default(),
+            properties: vec![],
+        }
+    }
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.dot.alias = Some(identifier(alias));
+        self
+    }
+    pub fn select<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, columns: I) -> Self {
+        let columns = columns
+            .into_iter()
+            .map(|column| Stmt::Identifier(identifier(column.as_ref())));
+        self.properties.extend(columns);
+        self
+    }
+    pub fn select_expr(mut self, expression: Expr) -> Self {
+        let selection = expression.into_arena(&mut self.arena);
+        self.properties.push(selection);
+        self
+    }
+    /// Nests a query in the block, joined through the foreign keys of the schema
+    pub fn join(mut self, query: Query) -> Self {
+        let (arena, statement) = query.finish();
+        self.properties
+            .push(arena.copy(&statement, &mut self.arena));
+        self
+    }
+    pub fn filter(mut self, column: &str, value: impl Into<Expr>) -> Self {
+        let value = value.into().into_arena(&mut self.arena);
+        self.dot.filters.push(PairStatement {
+            ident: identifier(column),
+            value: self.arena.alloc(value),
+        });
+        self
+    }
+    /// Conditions given more than once are joined with `and`
+    pub fn condition(mut self, condition: Expr) -> Self {
+        let condition = condition.into_arena(&mut self.arena);
+        let condition = match self.dot.condition.take() {
+            Some(existing) => Stmt::Infix(InfixExpression {
+                left: existing,
+                operator: TokenKind::And,
+                right: self.arena.alloc(condition),
+            }),
+            None => condition,
+        };
+        self.dot.condition = Some(self.arena.alloc(condition));
+        self
+    }
+    pub fn distinct(mut self) -> Self {
+        self.dot.distinct = true;
+        self
+    }
+    /// Postgres only, one row for each distinct value of the column
+    pub fn distinct_on(mut self, column: &str) -> Self {
+        self.dot.distinct = true;
+        self.dot.distinct_on.push(identifier(column));
+        self
+    }
+    pub fn group_by(mut self, column: &str) -> Self {
+        self.dot.grouping.push(identifier(column));
+        self
+    }
+    pub fn order_by(self, column: &str) -> Self {
+        self.order(column, None)
+    }
+    pub fn order_by_desc(self, column: &str) -> Self {
+        self.order(column, Some(TokenKind::Desc))
+    }
+    fn order(mut self, column: &str, direction: Option<TokenKind>) -> Self {
+        self.dot.ordering.push(OrderStatement {
+            ident: identifier(column),
+            direction,
+        });
+        self
+    }
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.dot.limit = Some(number(limit));
+        self
+    }
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.dot.offset = Some(number(offset));
+        self
+    }
+    pub fn build(self) -> Program {
+        let (arena, statement) = self.finish();
+        Program::from_arena(arena, vec![statement])
+    }
+    pub fn compile(self, context: &mut Context) -> Result<String> {
+        self.build().run_with(context)
+    }
+    fn finish(mut self) -> (Arena, Stmt) {
+        self.dot.block.properties = self.arena.alloc_all(self.properties);
+        (self.arena, Stmt::Dot(self.dot))
+    }
+}
+
+/// An expression for conditions and selections, numbers and strings convert into literals
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr(Stmt, Arena);
+impl Expr {
+    pub fn eq(self, other: impl Into<Expr>) -> Self {
+        self.infix(TokenKind::Eq, other.into())
+    }
+    pub fn not_eq(self, other: impl Into<Expr>) -> Self {
+        self.infix(TokenKind::NotEq, other.into())
+    }
+    pub fn lt(self, other: impl Into<Expr>) -> Self {
+        self.infix(TokenKind::Lt, other.into())
+    }
+    pub fn gt(self, other: impl Into<Expr>) -> Self {
+        self.infix(TokenKind::Gt, other.into())
+    }
+    pub fn lt_eq(self, other: impl Into<Expr>) -> Self {
+        self.infix(TokenKind::LtEq, other.into())
+    }
+    pub fn gt_eq(self, other: impl Into<Expr>) -> Self {
+        self.infix(TokenKind::GtEq, other.into())
+    }
+    pub fn and(self, other: Expr) -> Self {
+        self.infix(TokenKind::And, other)
+    }
+    pub fn or(self, other: Expr) -> Self {
+        self.infix(TokenKind::Or, other)
+    }
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        let Expr(right, mut arena) = self;
+        let right = arena.alloc(right);
+        Expr(
+            Stmt::Prefix(PrefixExpression {
+                operator: TokenKind::Not,
+                right,
+            }),
+            arena,
+        )
+    }
+    pub fn is_null(self) -> Self {
+        self.null(false)
+    }
+    pub fn is_not_null(self) -> Self {
+        self.null(true)
+    }
+    pub fn like(self, pattern: &str) -> Self {
+        let Expr(operand, mut arena) = self;
+        let operand = arena.alloc(operand);
+        let pattern = Expr::from(pattern).into_arena(&mut arena);
+        let pattern = arena.alloc(pattern);
+        Expr(
+            Stmt::Like(LikeExpression {
+                operand,
+                pattern,
+                insensitive: false,
+            }),
+            arena,
+        )
+    }
+    pub fn alias(self, alias: &str) -> Self {
+        let Expr(selection, mut arena) = self;
+        let selection = arena.alloc(selection);
+        Expr(
+            Stmt::Alias(AliasStatement {
+                selection,
+                alias: identifier(alias),
+            }),
+            arena,
+        )
+    }
+    fn infix(self, operator: TokenKind, right: Expr) -> Self {
+        let Expr(left, mut arena) = self;
+        let left = arena.alloc(left);
+        let right = right.into_arena(&mut arena);
+        let right = arena.alloc(right);
+        Expr(
+            Stmt::Infix(InfixExpression {
+                left,
+                operator,
+                right,
+            }),
+            arena,
+        )
+    }
+    fn null(self, negated: bool) -> Self {
+        let Expr(operand, mut arena) = self;
+        let operand = arena.alloc(operand);
+        Expr(Stmt::IsNull(IsNullExpression { operand, negated }), arena)
+    }
+    // The expression with its nodes moved over to the arena it's being built into
+    fn into_arena(self, arena: &mut Arena) -> Stmt {
+        self.1.copy(&self.0, arena)
+    }
+    fn leaf(statement: Stmt) -> Self {
+        Expr(statement, Arena::default())
+    }
+}
+impl From<i64> for Expr {
+    fn from(value: i64) -> Self {
+        Expr::leaf(Stmt::Literal(LiteralStatement {
+            kind: TokenKind::Number,
+            literal: value.to_string(),
+        }))
+    }
+}
+impl From<f64> for Expr {
+    fn from(value: f64) -> Self {
+        Expr::leaf(Stmt::Literal(LiteralStatement {
+            kind: TokenKind::Number,
+            literal: value.to_string(),
+        }))
+    }
+}
+impl From<&str> for Expr {
+    fn from(value: &str) -> Self {
+        Expr::leaf(Stmt::Literal(LiteralStatement {
+            kind: TokenKind::String,
+            literal: value.to_string(),
+        }))
+    }
+}
+impl From<String> for Expr {
+    fn from(value: String) -> Self {
+        Expr::from(value.as_str())
+    }
+}
+
+pub fn column(name: &str) -> Expr {
+    Expr::leaf(Stmt::Identifier(identifier(name)))
+}
+
+pub fn call<I: IntoIterator<Item = Expr>>(function: &str, arguments: I) -> Expr {
+    let mut arena = Arena::default();
+    let arguments: Vec<Stmt> = arguments
+        .into_iter()
+        .map(|argument| argument.into_arena(&mut arena))
+        .collect();
+    Expr(
+        Stmt::Call(FunctionCall {
+            function: identifier(function),
+            arguments: arena.alloc_all(arguments),
+            window: None,
+        }),
+        arena,
+    )
+}
+
+fn identifier(name: &str) -> IdentifierStatement {
+    IdentifierStatement {
+        literal: name.into(),
+        span: None,
+    }
+}
+
+fn number(value: u64) -> LiteralStatement {
+    LiteralStatement {
+        kind: TokenKind::Number,
+        literal: value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Postgres;
+
+    #[test]
+    fn test_query() {
+        let query = Query::table("users")
+            .alias("u")
+            .filter("active", 1)
+            .condition(column("age").gt_eq(18).or(column("email").is_not_null()))
+            .select(["u.name"])
+            .select_expr(call("count", [column("u.id")]).alias("total"))
+            .group_by("u.name")
+            .order_by_desc("total")
+            .limit(10);
+        let parsed = Program::new(
+            ".users(active: 1) u where { age >= 18 or email is not null } { u.name, count(u.id) as total } group by u.name order by total desc limit 10",
+        )
+        .unwrap();
+        assert_eq!(query.clone().build().ast(), parsed.ast());
+        assert_eq!(
+            query.compile(&mut Context::default()).unwrap(),
+            "SELECT u.name, COUNT(u.id) AS total FROM users u WHERE active = 1 AND (age >= 18 OR email IS NOT NULL) GROUP BY u.name ORDER BY total DESC LIMIT 10;"
+        );
+    }
+
+    #[test]
+    fn test_query_dialect() {
+        let mut context = Context {
+            dialect: Box::new(Postgres),
+            ..Default::default()
+        };
+        assert_eq!(
+            Query::table("users")
+                .select(["name"])
+                .condition(column("name").like("A%"))
+                .offset(5)
+                .compile(&mut context)
+                .unwrap(),
+            "SELECT name FROM users WHERE name LIKE 'A%' OFFSET 5;"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use crate::parameters;
+use nonsense::{compile, is_complete, Context};
+use std::io::{self, BufRead, Write};
+
+const PROMPT: &str = "> ";
+const CONTINUATION: &str = "... ";
+
+pub fn run(mut context: Context) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut buffer = String::new();
+    loop {
+        prompt(if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION
+        })?;
+        let Some(line) = lines.next().transpose()? else {
+            eprintln!();
+            return Ok(());
+        };
+        if buffer.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if !is_complete(&buffer) {
+            continue;
+        }
+        let input = std::mem::take(&mut buffer);
+        context.parameters.clear();
+        context.parameter_names.clear();
+        context.parameter_kinds.clear();
+        match compile(&input, &mut context) {
+            Ok(sql) => {
+                println!("{}", sql);
+                if context.placeholder.is_some() {
+                    println!("{}", parameters(&context));
+                }
+            }
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+}
+
+// Prompts go to stderr so piping the REPL only captures SQL
+fn prompt(prompt: &str) -> io::Result<()> {
+    let mut stderr = io::stderr();
+    stderr.write_all(prompt.as_bytes())?;
+    stderr.flush()
+}
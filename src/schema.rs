@@ -0,0 +1,514 @@
+use crate::json::Json;
+use crate::Result;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone)]
+pub struct Schema {
+    tables: Vec<Table>,
+}
+impl Schema {
+    pub fn from_json(input: &str) -> std::result::Result<Self, String> {
+        Self::from_value(&Json::parse(input)?)
+    }
+    pub fn from_toml(input: &str) -> std::result::Result<Self, String> {
+        let table: toml::Table = input.parse().map_err(|error| format!("{}", error))?;
+        Self::from_value(&from_toml(toml::Value::Table(table)))
+    }
+    fn from_value(value: &Json) -> std::result::Result<Self, String> {
+        let Json::Object(entries) = value else {
+            return Err("expected a table for each database table".to_string());
+        };
+        let mut tables = vec![];
+        for (name, table) in entries {
+            let columns = match table.get("columns") {
+                Some(Json::Array(columns)) => columns
+                    .iter()
+                    .map(|column| column.as_str().map(str::to_string))
+                    .collect::<Option<Vec<String>>>(),
+                _ => None,
+            }
+            .ok_or_else(|| format!("expected {}.columns to be a list of names", name))?;
+            let foreign_keys = match table.get("foreign_keys") {
+                Some(Json::Object(keys)) => keys
+                    .iter()
+                    .map(|(column, target)| {
+                        let (table, references) = target.as_str()?.split_once('.')?;
+                        Some(ForeignKey {
+                            column: column.clone(),
+                            table: table.to_string(),
+                            references: references.to_string(),
+                        })
+                    })
+                    .collect::<Option<Vec<ForeignKey>>>(),
+                None => Some(vec![]),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                format!(
+                    "expected {}.foreign_keys to map columns to table.column",
+                    name
+                )
+            })?;
+            let types = match table.get("types") {
+                Some(Json::Object(types)) => types
+                    .iter()
+                    .map(|(column, kind)| {
+                        let kind = kind.as_str().and_then(Type::parse);
+                        match kind {
+                            Some(kind) if columns.contains(column) => Ok((column.clone(), kind)),
+                            Some(_) => Err(format!("{}.types.{} is not a column", name, column)),
+                            None => Err(format!(
+                                "expected {}.types.{} to be int, text, bool or timestamp",
+                                name, column
+                            )),
+                        }
+                    })
+                    .collect::<std::result::Result<HashMap<String, Type>, String>>()?,
+                None => HashMap::new(),
+                _ => return Err(format!("expected {}.types to map columns to types", name)),
+            };
+            tables.push(Table {
+                name: name.clone(),
+                columns,
+                foreign_keys,
+                types,
+            });
+        }
+        Ok(Self { tables })
+    }
+    // Object types are tables and their scalar and enum fields are columns
+    pub fn from_graphql(input: &str) -> std::result::Result<Self, String> {
+        let tokens = graphql_tokens(input)?;
+        let mut types: Vec<(String, Vec<(String, String)>)> = vec![];
+        let mut index = 0;
+        while index < tokens.len() {
+            let extend = tokens[index] == "extend";
+            if extend {
+                index += 1;
+            }
+            match tokens.get(index).copied() {
+                Some("type") => {
+                    let name = tokens
+                        .get(index + 1)
+                        .filter(|name| is_graphql_name(name))
+                        .ok_or("expected a name after type")?;
+                    index += 2;
+                    while index < tokens.len() && tokens[index] != "{" {
+                        index = graphql_skip(&tokens, index);
+                    }
+                    let (fields, next) = graphql_fields(&tokens, index + 1)?;
+                    index = next;
+                    match types.iter_mut().find(|(candidate, _)| candidate == name) {
+                        Some((_, existing)) if extend => existing.extend(fields),
+                        Some(_) => return Err(format!("type {} is defined twice", name)),
+                        None => types.push((name.to_string(), fields)),
+                    }
+                }
+                Some(_) => index = graphql_skip(&tokens, index),
+                None => break,
+            }
+        }
+        let objects: Vec<String> = types.iter().map(|(name, _)| name.clone()).collect();
+        let tables = types
+            .into_iter()
+            .filter(|(name, _)| !["Query", "Mutation", "Subscription"].contains(&name.as_str()))
+            .map(|(name, fields)| {
+                let fields = fields
+                    .into_iter()
+                    .filter(|(_, kind)| !objects.contains(kind));
+                let fields: Vec<(String, String)> = fields.collect();
+                Table {
+                    name,
+                    columns: fields.iter().map(|(field, _)| field.clone()).collect(),
+                    foreign_keys: vec![],
+                    types: fields
+                        .into_iter()
+                        .filter_map(|(field, kind)| {
+                            let kind = match kind.as_str() {
+                                "Int" => Type::Int,
+                                "String" => Type::Text,
+                                "Boolean" => Type::Bool,
+                                "DateTime" | "Timestamp" => Type::Timestamp,
+                                _ => return None,
+                            };
+                            Some((field, kind))
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(Self { tables })
+    }
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+    pub fn insert(&mut self, table: Table) {
+        self.tables.retain(|candidate| candidate.name != table.name);
+        self.tables.push(table);
+    }
+    pub fn table(&self, name: &str) -> Result<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.name == name)
+            .ok_or_else(|| {
+                let names = self.tables.iter().map(|table| table.name.as_str());
+                error!(Schema, "table '{}' not found{}", name, suggest(name, names))
+            })
+    }
+    pub fn relation(&self, from: &str, to: &str) -> Result<(String, String)> {
+        let key = |table: &str, target: &str| {
+            let table = self
+                .tables
+                .iter()
+                .find(|candidate| candidate.name == table)?;
+            let key = table.foreign_keys.iter().find(|key| key.table == target)?;
+            Some((key.column.clone(), key.references.clone()))
+        };
+        key(to, from)
+            .or_else(|| key(from, to).map(|(column, references)| (references, column)))
+            .ok_or_else(|| error!(Schema, "no foreign key between '{}' and '{}'", from, to))
+    }
+}
+
+#[derive(Clone)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub foreign_keys: Vec<ForeignKey>,
+    pub types: HashMap<String, Type>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Int,
+    Text,
+    Bool,
+    Timestamp,
+}
+impl Type {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(Type::Int),
+            "text" => Some(Type::Text),
+            "bool" => Some(Type::Bool),
+            "timestamp" => Some(Type::Timestamp),
+            _ => None,
+        }
+    }
+    // Timestamps are written as strings
+    pub fn compatible(self, other: Self) -> bool {
+        self == other
+            || matches!(
+                (self, other),
+                (Type::Timestamp, Type::Text) | (Type::Text, Type::Timestamp)
+            )
+    }
+}
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Int => "int",
+            Type::Text => "text",
+            Type::Bool => "bool",
+            Type::Timestamp => "timestamp",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone)]
+pub struct ForeignKey {
+    pub column: String,
+    pub table: String,
+    pub references: String,
+}
+impl Table {
+    pub fn type_of(&self, column: &str) -> Option<Type> {
+        let column = column.rsplit('.').next().unwrap_or(column);
+        self.types.get(column).copied()
+    }
+    pub fn column(&self, column: &str) -> Result<()> {
+        let column = column.rsplit('.').next().unwrap_or(column);
+        if self.columns.iter().any(|name| name == column) {
+            return Ok(());
+        }
+        let names = self.columns.iter().map(String::as_str);
+        Err(error!(
+            Schema,
+            "column '{}' not found in table '{}'{}",
+            column,
+            self.name,
+            suggest(column, names)
+        ))
+    }
+}
+
+fn graphql_tokens(input: &str) -> std::result::Result<Vec<&str>, String> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    loop {
+        rest = rest
+            .trim_start_matches(|character: char| character.is_whitespace() || character == ',');
+        let Some(character) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+        let end = if character == '#' {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        } else if let Some(block) = rest.strip_prefix("\"\"\"") {
+            let end = block.find("\"\"\"").ok_or("unterminated block string")?;
+            rest = &block[end + 3..];
+            continue;
+        } else if character == '"' {
+            let mut escaped = false;
+            let end = rest[1..]
+                .char_indices()
+                .find(|(_, character)| {
+                    let closes = *character == '"' && !escaped;
+                    escaped = *character == '\\' && !escaped;
+                    closes
+                })
+                .ok_or("unterminated string")?
+                .0;
+            rest = &rest[end + 2..];
+            continue;
+        } else if character == '_' || character.is_ascii_alphanumeric() {
+            rest.find(|character: char| character != '_' && !character.is_ascii_alphanumeric())
+                .unwrap_or(rest.len())
+        } else {
+            character.len_utf8()
+        };
+        tokens.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+}
+
+fn is_graphql_name(token: &str) -> bool {
+    token.starts_with(|character: char| character == '_' || character.is_ascii_alphabetic())
+}
+
+// The index after one token, or after the whole group it opens
+fn graphql_skip(tokens: &[&str], index: usize) -> usize {
+    let close = match tokens[index] {
+        "{" => "}",
+        "(" => ")",
+        "[" => "]",
+        _ => return index + 1,
+    };
+    let mut index = index + 1;
+    while index < tokens.len() && tokens[index] != close {
+        index = graphql_skip(tokens, index);
+    }
+    index + 1
+}
+
+fn graphql_fields(
+    tokens: &[&str],
+    mut index: usize,
+) -> std::result::Result<(Vec<(String, String)>, usize), String> {
+    let mut fields = vec![];
+    loop {
+        match tokens.get(index).copied() {
+            None => return Err("expected } to close the type".to_string()),
+            Some("}") => return Ok((fields, index + 1)),
+            Some(name) if is_graphql_name(name) => {
+                index += 1;
+                if tokens.get(index) == Some(&"(") {
+                    index = graphql_skip(tokens, index);
+                }
+                if tokens.get(index) != Some(&":") {
+                    return Err(format!("expected : after field {}", name));
+                }
+                index += 1;
+                while matches!(tokens.get(index).copied(), Some("[" | "]" | "!")) {
+                    index += 1;
+                }
+                let kind = tokens
+                    .get(index)
+                    .filter(|kind| is_graphql_name(kind))
+                    .ok_or_else(|| format!("expected a type for field {}", name))?;
+                index += 1;
+                while matches!(tokens.get(index).copied(), Some("]" | "!")) {
+                    index += 1;
+                }
+                while tokens.get(index) == Some(&"@") {
+                    index += 2;
+                    if tokens.get(index) == Some(&"(") {
+                        index = graphql_skip(tokens, index);
+                    }
+                }
+                fields.push((name.to_string(), kind.to_string()));
+            }
+            Some(token) => return Err(format!("unexpected {} in a type", token)),
+        }
+    }
+}
+
+fn from_toml(value: toml::Value) -> Json {
+    match value {
+        toml::Value::String(value) => Json::String(value),
+        toml::Value::Integer(value) => Json::Number(value.to_string()),
+        toml::Value::Float(value) => Json::Number(value.to_string()),
+        toml::Value::Boolean(value) => Json::Bool(value),
+        toml::Value::Datetime(value) => Json::String(value.to_string()),
+        toml::Value::Array(items) => Json::Array(items.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Json::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, from_toml(value)))
+                .collect(),
+        ),
+    }
+}
+
+pub(crate) fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> String {
+    closest(name, candidates)
+        .map(|candidate| format!(", did you mean '{}'?", candidate))
+        .unwrap_or_default()
+}
+
+// The candidate with the fewest edits, when it's within a third of the name
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl Iterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn distance(left: &str, right: &str) -> usize {
+    let left: Vec<char> = left.chars().collect();
+    let right: Vec<char> = right.chars().collect();
+    let mut table = vec![vec![0; right.len() + 1]; left.len() + 1];
+    for (i, row) in table.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    table[0] = (0..=right.len()).collect();
+    for i in 1..=left.len() {
+        for j in 1..=right.len() {
+            let cost = usize::from(left[i - 1] != right[j - 1]);
+            table[i][j] = (table[i - 1][j] + 1)
+                .min(table[i][j - 1] + 1)
+                .min(table[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && left[i - 1] == right[j - 2] && left[i - 2] == right[j - 1] {
+                table[i][j] = table[i][j].min(table[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    table[left.len()][right.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let schema = Schema::from_toml("[users]\ncolumns = [\"id\", \"name\"]\n").unwrap();
+        assert_eq!(schema.table("users").unwrap().columns, ["id", "name"]);
+        assert_eq!(
+            schema.table("usres").err().unwrap().to_string(),
+            "SCHEMA ERROR: table 'usres' not found, did you mean 'users'?"
+        );
+        assert!(Schema::from_toml("[users]\ncolumns = 1\n").is_err());
+    }
+
+    #[test]
+    fn test_types() {
+        let schema =
+            Schema::from_toml("[users]\ncolumns = [\"id\", \"name\"]\ntypes = { id = \"int\" }\n")
+                .unwrap();
+        let users = schema.table("users").unwrap();
+        assert_eq!(users.type_of("users.id"), Some(Type::Int));
+        assert_eq!(users.type_of("name"), None);
+        assert_eq!(
+            Schema::from_toml("[users]\ncolumns = [\"id\"]\ntypes = { id = \"uuid\" }\n")
+                .err()
+                .unwrap(),
+            "expected users.types.id to be int, text, bool or timestamp"
+        );
+        assert!(Schema::from_toml("[users]\ncolumns = []\ntypes = { id = \"int\" }\n").is_err());
+    }
+
+    #[test]
+    fn test_relation() {
+        let schema = Schema::from_toml(
+            "[users]\ncolumns = [\"id\"]\n[posts]\ncolumns = [\"user_id\"]\nforeign_keys = { user_id = \"users.id\" }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            schema.relation("users", "posts").unwrap(),
+            ("user_id".to_string(), "id".to_string())
+        );
+        assert_eq!(
+            schema.relation("posts", "users").unwrap(),
+            ("id".to_string(), "user_id".to_string())
+        );
+        assert_eq!(
+            schema.relation("users", "users").unwrap_err().to_string(),
+            "SCHEMA ERROR: no foreign key between 'users' and 'users'"
+        );
+        assert!(
+            Schema::from_toml("[posts]\ncolumns = []\nforeign_keys = { user_id = 1 }\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_from_graphql() {
+        let schema = Schema::from_graphql(
+            r#"
+            """A person"""
+            type User @key(fields: "id") {
+              id: ID!
+              "Display name"
+              name: String
+              posts(first: Int = 10): [Post!]! # a relation, not a column
+              role: Role
+            }
+            enum Role { ADMIN, MEMBER }
+            type Post implements Node { id: ID!, title: String!, author: User }
+            extend type Post { tags: [String] }
+            type Query { users: [User] }
+            "#,
+        )
+        .unwrap();
+        let tables: Vec<(&str, &[String])> = schema
+            .tables()
+            .iter()
+            .map(|table| (table.name.as_str(), table.columns.as_slice()))
+            .collect();
+        assert_eq!(
+            tables,
+            [
+                ("User", &["id", "name", "role"].map(String::from)[..]),
+                ("Post", &["id", "title", "tags"].map(String::from)[..]),
+            ]
+        );
+        assert_eq!(
+            schema.table("Post").unwrap().type_of("title"),
+            Some(Type::Text)
+        );
+        assert_eq!(schema.table("Post").unwrap().type_of("id"), None);
+        assert!(Schema::from_graphql("type User { id }").is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let schema = Schema::from_json(r#"{"users": {"columns": ["id", "name"]}}"#).unwrap();
+        let users = schema.table("users").unwrap();
+        assert!(users.column("u.name").is_ok());
+        assert_eq!(
+            users.column("nmae").unwrap_err().to_string(),
+            "SCHEMA ERROR: column 'nmae' not found in table 'users', did you mean 'name'?"
+        );
+        assert_eq!(
+            users.column("email").unwrap_err().to_string(),
+            "SCHEMA ERROR: column 'email' not found in table 'users'"
+        );
+    }
+}
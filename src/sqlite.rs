@@ -0,0 +1,110 @@
+use crate::{table, Format};
+use nonsense::Parameter;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params_from_iter, Connection};
+use std::path::Path;
+
+pub fn execute(
+    path: &Path,
+    queries: &[(String, Vec<Parameter>)],
+    format: Format,
+) -> Result<String, String> {
+    let connection = Connection::open(path)
+        .map_err(|error| format!("EXEC ERROR: Failed to open {}: {}", path.display(), error))?;
+    let mut output = String::new();
+    for (sql, parameters) in queries {
+        let failed = |error: rusqlite::Error| format!("EXEC ERROR: {}: {}", sql, error);
+        let mut statement = connection.prepare(sql).map_err(failed)?;
+        let parameters = params_from_iter(parameters.iter().map(value));
+        if statement.column_count() == 0 {
+            let changed = statement.execute(parameters).map_err(failed)?;
+            let affected = format!(
+                "({} {} affected)",
+                changed,
+                if changed == 1 { "row" } else { "rows" }
+            );
+            // Only tables are read by people, keep piped rows free of counts
+            match format {
+                Format::Table => output.push_str(&format!("{}\n", affected)),
+                _ => eprintln!("{}", affected),
+            }
+            continue;
+        }
+        let columns: Vec<String> = statement
+            .column_names()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let mut rows = vec![];
+        let mut cursor = statement.query(parameters).map_err(failed)?;
+        while let Some(row) = cursor.next().map_err(failed)? {
+            let values = (0..columns.len())
+                .map(|index| row.get_ref(index).map(display))
+                .collect::<rusqlite::Result<Vec<Option<String>>>>()
+                .map_err(failed)?;
+            rows.push(values);
+        }
+        output.push_str(&table::write(format, &columns, &rows));
+    }
+    Ok(output)
+}
+
+fn value(parameter: &Parameter) -> Value {
+    let literal = &parameter.value;
+    if parameter.kind == "string" {
+        return Value::Text(literal.clone());
+    }
+    if let Ok(integer) = literal.parse() {
+        return Value::Integer(integer);
+    }
+    match literal.parse() {
+        Ok(real) => Value::Real(real),
+        Err(_) => Value::Text(literal.clone()),
+    }
+}
+
+fn display(value: ValueRef) -> Option<String> {
+    Some(match value {
+        ValueRef::Null => return None,
+        ValueRef::Integer(integer) => integer.to_string(),
+        ValueRef::Real(real) => real.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).to_string(),
+        ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let path = std::env::temp_dir().join(format!("nonsense-{}.sqlite", std::process::id()));
+        let parameter = |name: &str, kind, value: &str| Parameter {
+            name: name.to_string(),
+            kind,
+            value: value.to_string(),
+        };
+        let queries = [
+            ("CREATE TABLE users (id INTEGER, name TEXT);", vec![]),
+            (
+                "INSERT INTO users (id, name) VALUES (?, ?);",
+                vec![
+                    parameter("id", "number", "1"),
+                    parameter("name", "string", "O'Brien"),
+                ],
+            ),
+            (
+                "SELECT name FROM users WHERE id = ?;",
+                vec![parameter("id", "number", "1")],
+            ),
+        ]
+        .map(|(sql, parameters)| (sql.to_string(), parameters));
+        let output = execute(&path, &queries, Format::Table);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            output.unwrap(),
+            "(0 rows affected)\n(1 row affected)\nname\n-------\nO'Brien\n(1 row)\n"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use crate::Format;
+use nonsense::json::Json;
+
+/// Every row of a result set, NULL is an empty CSV field and a JSON null
+pub fn write(format: Format, columns: &[String], rows: &[Vec<Option<String>>]) -> String {
+    match format {
+        Format::Table => render(columns, rows),
+        Format::Csv => {
+            let mut output = format!("{}\n", header(columns));
+            for row in rows {
+                output.push_str(&format!("{}\n", csv(row)));
+            }
+            output
+        }
+        Format::Json => rows
+            .iter()
+            .map(|row| format!("{}\n", json(columns, row)))
+            .collect(),
+    }
+}
+
+pub fn header(columns: &[String]) -> String {
+    let names: Vec<Option<String>> = columns.iter().cloned().map(Some).collect();
+    csv(&names)
+}
+
+pub fn csv(values: &[Option<String>]) -> String {
+    values
+        .iter()
+        .map(|value| match value {
+            Some(value) if value.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            }
+            Some(value) => value.clone(),
+            None => String::new(),
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+pub fn json(columns: &[String], values: &[Option<String>]) -> String {
+    let fields = columns
+        .iter()
+        .cloned()
+        .zip(values.iter().map(|value| Json::from(value.as_deref())));
+    Json::Object(fields.collect()).to_string()
+}
+
+pub fn render(columns: &[String], rows: &[Vec<Option<String>>]) -> String {
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| value.clone().unwrap_or_else(|| "NULL".to_string()))
+                .collect()
+        })
+        .collect();
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .map(|column| column.chars().count())
+        .collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let line = |values: &[String]| {
+        let cells: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:width$}", value, width = width))
+            .collect();
+        format!("{}\n", cells.join(" | ").trim_end())
+    };
+    let mut output = line(columns);
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    output.push_str(&format!("{}\n", separator.join("-+-")));
+    for row in &rows {
+        output.push_str(&line(row));
+    }
+    output.push_str(&format!(
+        "({} {})\n",
+        rows.len(),
+        if rows.len() == 1 { "row" } else { "rows" }
+    ));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let columns = ["id".to_string(), "name".to_string()];
+        let rows = [
+            vec![Some("1".to_string()), Some("Ada".to_string())],
+            vec![Some("10".to_string()), Some("Grace Hopper".to_string())],
+            vec![Some("11".to_string()), None],
+        ];
+        assert_eq!(
+            render(&columns, &rows),
+            "id | name\n---+-------------\n1  | Ada\n10 | Grace Hopper\n11 | NULL\n(3 rows)\n"
+        );
+        assert_eq!(
+            write(Format::Json, &columns, &rows[2..]),
+            "{\"id\":\"11\",\"name\":null}\n"
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let values = [
+            Some("1".to_string()),
+            Some("Lovelace, Ada".to_string()),
+            None,
+            Some("say \"hi\"".to_string()),
+        ];
+        assert_eq!(csv(&values), "1,\"Lovelace, Ada\",,\"say \"\"hi\"\"\"");
+    }
+}
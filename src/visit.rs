@@ -0,0 +1,705 @@
+use crate::arena::{Arena, NodeId, Nodes};
+use crate::{
+    BlockStatement, Case, ConflictStatement, DeleteStatement, DotStatement, IdentifierStatement,
+    InfixExpression, InsertStatement, IsNullExpression, LiteralStatement, OrderStatement,
+    PairStatement, ReturningStatement, Stmt, TokenKind, UpdateStatement,
+};
+
+pub trait Visitor {
+    fn visit_stmt(&mut self, arena: &Arena, statement: &Stmt) {
+        walk_stmt(self, arena, statement)
+    }
+    fn visit_dot(&mut self, arena: &Arena, dot: &DotStatement) {
+        walk_dot(self, arena, dot)
+    }
+    fn visit_insert(&mut self, arena: &Arena, insert: &InsertStatement) {
+        self.visit_table(&insert.ident);
+        insert
+            .values
+            .iter()
+            .for_each(|pair| self.visit_pair(arena, pair));
+        if let Some(conflict) = &insert.conflict {
+            let columns = conflict.columns.iter().chain(&conflict.update);
+            columns.for_each(|ident| self.visit_identifier(ident));
+        }
+        if let Some(returning) = &insert.returning {
+            self.visit_returning(returning);
+        }
+    }
+    fn visit_update(&mut self, arena: &Arena, update: &UpdateStatement) {
+        self.visit_table(&update.ident);
+        let pairs = update.filters.iter().chain(&update.values);
+        pairs.for_each(|pair| self.visit_pair(arena, pair));
+        if let Some(returning) = &update.returning {
+            self.visit_returning(returning);
+        }
+    }
+    fn visit_delete(&mut self, arena: &Arena, delete: &DeleteStatement) {
+        self.visit_table(&delete.ident);
+        delete
+            .filters
+            .iter()
+            .for_each(|pair| self.visit_pair(arena, pair));
+        if let Some(condition) = delete.condition {
+            self.visit_stmt(arena, &arena[condition]);
+        }
+        if let Some(returning) = &delete.returning {
+            self.visit_returning(returning);
+        }
+    }
+    fn visit_pair(&mut self, arena: &Arena, pair: &PairStatement) {
+        self.visit_identifier(&pair.ident);
+        self.visit_stmt(arena, &arena[pair.value]);
+    }
+    fn visit_returning(&mut self, returning: &ReturningStatement) {
+        let columns = returning.columns.iter();
+        columns.for_each(|ident| self.visit_identifier(ident));
+    }
+    fn visit_table(&mut self, _table: &IdentifierStatement) {}
+    fn visit_identifier(&mut self, _identifier: &IdentifierStatement) {}
+    fn visit_literal(&mut self, _literal: &LiteralStatement) {}
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, arena: &Arena, statement: &Stmt) {
+    let mut visit = |id: NodeId| visitor.visit_stmt(arena, &arena[id]);
+    match statement {
+        Stmt::Dot(dot) => visitor.visit_dot(arena, dot),
+        Stmt::Insert(insert) => visitor.visit_insert(arena, insert),
+        Stmt::Update(update) => visitor.visit_update(arena, update),
+        Stmt::Delete(delete) => visitor.visit_delete(arena, delete),
+        Stmt::Block(block) => block.properties.ids().for_each(visit),
+        Stmt::Identifier(identifier) => visitor.visit_identifier(identifier),
+        Stmt::Literal(literal) => visitor.visit_literal(literal),
+        Stmt::Prefix(prefix) => visit(prefix.right),
+        Stmt::Infix(infix) => {
+            visit(infix.left);
+            visit(infix.right);
+        }
+        Stmt::IsNull(expression) => visit(expression.operand),
+        Stmt::Between(expression) => {
+            visit(expression.operand);
+            visit(expression.low);
+            visit(expression.high);
+        }
+        Stmt::Like(expression) => {
+            visit(expression.operand);
+            visit(expression.pattern);
+        }
+        Stmt::In(expression) => {
+            visit(expression.operand);
+            expression.values.ids().for_each(visit);
+        }
+        Stmt::Case(case) => {
+            for (condition, result) in &case.branches {
+                visit(*condition);
+                visit(*result);
+            }
+            if let Some(otherwise) = case.otherwise {
+                visit(otherwise);
+            }
+        }
+        Stmt::Call(call) => {
+            call.arguments.ids().for_each(visit);
+            if let Some(window) = &call.window {
+                let partition = window.partition.iter();
+                partition.for_each(|ident| visitor.visit_identifier(ident));
+                let ordering = window.ordering.iter();
+                ordering.for_each(|order| visitor.visit_identifier(&order.ident));
+            }
+        }
+        Stmt::Alias(alias) => visit(alias.selection),
+        Stmt::Spread(_) | Stmt::Variable(_) | Stmt::Env(_) | Stmt::Temporal(_) => {}
+        Stmt::Wildcard(wildcard) => {
+            let exclude = wildcard.exclude.iter();
+            exclude.for_each(|ident| visitor.visit_identifier(ident));
+        }
+        Stmt::Subquery(subquery) => visitor.visit_dot(arena, &subquery.query),
+        Stmt::With(with) => {
+            let tables = with.tables.iter();
+            tables.for_each(|table| visitor.visit_stmt(arena, &table.query));
+            visitor.visit_stmt(arena, &arena[with.statement]);
+        }
+        Stmt::Union(union) => {
+            visitor.visit_stmt(arena, &arena[union.left]);
+            visitor.visit_dot(arena, &union.right);
+        }
+        Stmt::Join(join) => {
+            visitor.visit_table(&join.ident);
+            let properties = join.block.properties.ids();
+            properties.for_each(|property| visitor.visit_stmt(arena, &arena[property]));
+        }
+    }
+}
+
+pub fn walk_dot<V: Visitor + ?Sized>(visitor: &mut V, arena: &Arena, dot: &DotStatement) {
+    visitor.visit_table(&dot.ident);
+    if let Some(within) = &dot.within {
+        visitor.visit_table(&within.name);
+    }
+    for join in &dot.joins {
+        visitor.visit_table(&join.table);
+        if let Some(condition) = join.condition {
+            visitor.visit_stmt(arena, &arena[condition]);
+        }
+    }
+    let filters = dot.filters.iter();
+    filters.for_each(|pair| visitor.visit_pair(arena, pair));
+    if let Some(condition) = dot.condition {
+        visitor.visit_stmt(arena, &arena[condition]);
+    }
+    let distinct_on = dot.distinct_on.iter();
+    distinct_on.for_each(|ident| visitor.visit_identifier(ident));
+    let properties = dot.block.properties.ids();
+    properties.for_each(|property| visitor.visit_stmt(arena, &arena[property]));
+    let grouping = dot.grouping.iter();
+    grouping.for_each(|ident| visitor.visit_identifier(ident));
+    let ordering = dot.ordering.iter();
+    ordering.for_each(|order| visitor.visit_identifier(&order.ident));
+    if let Some(limit) = &dot.limit {
+        visitor.visit_literal(limit);
+    }
+    if let Some(offset) = &dot.offset {
+        visitor.visit_literal(offset);
+    }
+}
+
+/// Rewrites statements. Children are folded in place in the arena, so their ids don't change
+pub trait Fold {
+    fn fold_stmt(&mut self, arena: &mut Arena, statement: Stmt) -> Stmt {
+        fold_stmt(self, arena, statement)
+    }
+    fn fold_dot(&mut self, arena: &mut Arena, dot: DotStatement) -> DotStatement {
+        fold_dot(self, arena, dot)
+    }
+    fn fold_insert(&mut self, arena: &mut Arena, insert: InsertStatement) -> InsertStatement {
+        InsertStatement {
+            ident: self.fold_table(insert.ident),
+            values: fold_pairs(self, arena, insert.values),
+            conflict: insert.conflict.map(|conflict| ConflictStatement {
+                columns: fold_identifiers(self, conflict.columns),
+                update: fold_identifiers(self, conflict.update),
+            }),
+            returning: insert
+                .returning
+                .map(|returning| self.fold_returning(returning)),
+        }
+    }
+    fn fold_update(&mut self, arena: &mut Arena, update: UpdateStatement) -> UpdateStatement {
+        UpdateStatement {
+            ident: self.fold_table(update.ident),
+            filters: fold_pairs(self, arena, update.filters),
+            values: fold_pairs(self, arena, update.values),
+            returning: update
+                .returning
+                .map(|returning| self.fold_returning(returning)),
+        }
+    }
+    fn fold_delete(&mut self, arena: &mut Arena, delete: DeleteStatement) -> DeleteStatement {
+        DeleteStatement {
+            ident: self.fold_table(delete.ident),
+            filters: fold_pairs(self, arena, delete.filters),
+            condition: delete
+                .condition
+                .map(|condition| fold_node(self, arena, condition)),
+            returning: delete
+                .returning
+                .map(|returning| self.fold_returning(returning)),
+        }
+    }
+    fn fold_block(&mut self, arena: &mut Arena, block: BlockStatement) -> BlockStatement {
+        BlockStatement {
+            properties: fold_all(self, arena, block.properties),
+        }
+    }
+    fn fold_pair(&mut self, arena: &mut Arena, pair: PairStatement) -> PairStatement {
+        PairStatement {
+            ident: self.fold_identifier(pair.ident),
+            value: fold_node(self, arena, pair.value),
+        }
+    }
+    fn fold_returning(&mut self, returning: ReturningStatement) -> ReturningStatement {
+        ReturningStatement {
+            columns: fold_identifiers(self, returning.columns),
+        }
+    }
+    fn fold_table(&mut self, table: IdentifierStatement) -> IdentifierStatement {
+        table
+    }
+    fn fold_identifier(&mut self, identifier: IdentifierStatement) -> IdentifierStatement {
+        identifier
+    }
+    fn fold_literal(&mut self, literal: LiteralStatement) -> LiteralStatement {
+        literal
+    }
+}
+
+pub fn fold_stmt<F: Fold + ?Sized>(folder: &mut F, arena: &mut Arena, statement: Stmt) -> Stmt {
+    match statement {
+        Stmt::Dot(dot) => Stmt::Dot(folder.fold_dot(arena, dot)),
+        Stmt::Insert(insert) => Stmt::Insert(folder.fold_insert(arena, insert)),
+        Stmt::Update(update) => Stmt::Update(folder.fold_update(arena, update)),
+        Stmt::Delete(delete) => Stmt::Delete(folder.fold_delete(arena, delete)),
+        Stmt::Block(block) => Stmt::Block(folder.fold_block(arena, block)),
+        Stmt::Identifier(identifier) => Stmt::Identifier(folder.fold_identifier(identifier)),
+        Stmt::Literal(literal) => Stmt::Literal(folder.fold_literal(literal)),
+        Stmt::Prefix(mut prefix) => {
+            prefix.right = fold_node(folder, arena, prefix.right);
+            Stmt::Prefix(prefix)
+        }
+        Stmt::Infix(mut infix) => {
+            infix.left = fold_node(folder, arena, infix.left);
+            infix.right = fold_node(folder, arena, infix.right);
+            Stmt::Infix(infix)
+        }
+        Stmt::IsNull(mut expression) => {
+            expression.operand = fold_node(folder, arena, expression.operand);
+            Stmt::IsNull(expression)
+        }
+        Stmt::Between(mut expression) => {
+            expression.operand = fold_node(folder, arena, expression.operand);
+            expression.low = fold_node(folder, arena, expression.low);
+            expression.high = fold_node(folder, arena, expression.high);
+            Stmt::Between(expression)
+        }
+        Stmt::Like(mut expression) => {
+            expression.operand = fold_node(folder, arena, expression.operand);
+            expression.pattern = fold_node(folder, arena, expression.pattern);
+            Stmt::Like(expression)
+        }
+        Stmt::In(mut expression) => {
+            expression.operand = fold_node(folder, arena, expression.operand);
+            expression.values = fold_all(folder, arena, expression.values);
+            Stmt::In(expression)
+        }
+        Stmt::Case(mut case) => {
+            for (condition, result) in &mut case.branches {
+                *condition = fold_node(folder, arena, *condition);
+                *result = fold_node(folder, arena, *result);
+            }
+            case.otherwise = case
+                .otherwise
+                .map(|otherwise| fold_node(folder, arena, otherwise));
+            Stmt::Case(case)
+        }
+        Stmt::Call(mut call) => {
+            call.arguments = fold_all(folder, arena, call.arguments);
+            call.window = call.window.map(|mut window| {
+                window.partition = fold_identifiers(folder, window.partition);
+                window.ordering = fold_ordering(folder, window.ordering);
+                window
+            });
+            Stmt::Call(call)
+        }
+        Stmt::Spread(spread) => Stmt::Spread(spread),
+        Stmt::Wildcard(mut wildcard) => {
+            wildcard.exclude = fold_identifiers(folder, wildcard.exclude);
+            Stmt::Wildcard(wildcard)
+        }
+        Stmt::Variable(variable) => Stmt::Variable(variable),
+        Stmt::Env(env) => Stmt::Env(env),
+        Stmt::Temporal(temporal) => Stmt::Temporal(temporal),
+        Stmt::Alias(mut alias) => {
+            alias.selection = fold_node(folder, arena, alias.selection);
+            Stmt::Alias(alias)
+        }
+        Stmt::With(mut with) => {
+            with.tables = with
+                .tables
+                .into_iter()
+                .map(|mut table| {
+                    table.query = folder.fold_stmt(arena, table.query);
+                    table
+                })
+                .collect();
+            with.statement = fold_node(folder, arena, with.statement);
+            Stmt::With(with)
+        }
+        Stmt::Union(mut union) => {
+            union.left = fold_node(folder, arena, union.left);
+            union.right = folder.fold_dot(arena, union.right);
+            Stmt::Union(union)
+        }
+        Stmt::Subquery(mut subquery) => {
+            subquery.query = folder.fold_dot(arena, subquery.query);
+            Stmt::Subquery(subquery)
+        }
+        Stmt::Join(mut join) => {
+            join.ident = folder.fold_table(join.ident);
+            join.block = folder.fold_block(arena, join.block);
+            Stmt::Join(join)
+        }
+    }
+}
+
+pub fn fold_dot<F: Fold + ?Sized>(
+    folder: &mut F,
+    arena: &mut Arena,
+    dot: DotStatement,
+) -> DotStatement {
+    DotStatement {
+        ident: folder.fold_table(dot.ident),
+        filters: fold_pairs(folder, arena, dot.filters),
+        alias: dot.alias,
+        joins: dot
+            .joins
+            .into_iter()
+            .map(|mut join| {
+                join.table = folder.fold_table(join.table);
+                join.condition = join
+                    .condition
+                    .map(|condition| fold_node(folder, arena, condition));
+                join
+            })
+            .collect(),
+        within: dot.within.map(|mut within| {
+            within.name = folder.fold_table(within.name);
+            within
+        }),
+        condition: dot
+            .condition
+            .map(|condition| fold_node(folder, arena, condition)),
+        distinct: dot.distinct,
+        distinct_on: fold_identifiers(folder, dot.distinct_on),
+        block: folder.fold_block(arena, dot.block),
+        grouping: fold_identifiers(folder, dot.grouping),
+        ordering: fold_ordering(folder, dot.ordering),
+        limit: dot.limit.map(|limit| folder.fold_literal(limit)),
+        offset: dot.offset.map(|offset| folder.fold_literal(offset)),
+    }
+}
+
+// Takes the node out for the folder to own, then puts the folded one back under the same id
+fn fold_node<F: Fold + ?Sized>(folder: &mut F, arena: &mut Arena, id: NodeId) -> NodeId {
+    let node = arena.take(id);
+    arena[id] = folder.fold_stmt(arena, node);
+    id
+}
+
+pub(crate) fn fold_all<F: Fold + ?Sized>(folder: &mut F, arena: &mut Arena, nodes: Nodes) -> Nodes {
+    for id in nodes.ids() {
+        fold_node(folder, arena, id);
+    }
+    nodes
+}
+
+fn fold_identifiers<F: Fold + ?Sized>(
+    folder: &mut F,
+    identifiers: Vec<IdentifierStatement>,
+) -> Vec<IdentifierStatement> {
+    identifiers
+        .into_iter()
+        .map(|identifier| folder.fold_identifier(identifier))
+        .collect()
+}
+
+fn fold_ordering<F: Fold + ?Sized>(
+    folder: &mut F,
+    ordering: Vec<OrderStatement>,
+) -> Vec<OrderStatement> {
+    ordering
+        .into_iter()
+        .map(|mut order| {
+            order.ident = folder.fold_identifier(order.ident);
+            order
+        })
+        .collect()
+}
+
+fn fold_pairs<F: Fold + ?Sized>(
+    folder: &mut F,
+    arena: &mut Arena,
+    pairs: Vec<PairStatement>,
+) -> Vec<PairStatement> {
+    pairs
+        .into_iter()
+        .map(|pair| folder.fold_pair(arena, pair))
+        .collect()
+}
+
+/// Hides soft deleted rows by requiring `column IS NULL` in every select
+pub struct SoftDelete {
+    pub column: String,
+}
+impl Default for SoftDelete {
+    fn default() -> Self {
+        Self {
+            column: "deleted_at".to_string(),
+        }
+    }
+}
+impl Fold for SoftDelete {
+    fn fold_dot(&mut self, arena: &mut Arena, dot: DotStatement) -> DotStatement {
+        let mut dot = fold_dot(self, arena, dot);
+        let literal = match &dot.alias {
+            Some(alias) => format!("{}.{}", alias.literal, self.column),
+            None => self.column.clone(),
+        };
+        let operand = arena.alloc(Stmt::Identifier(IdentifierStatement {
+            literal: literal.into(),
+            span: None,
+        }));
+        let predicate = Stmt::IsNull(IsNullExpression {
+            operand,
+            negated: false,
+        });
+        let condition = match dot.condition.take() {
+            Some(condition) => Stmt::Infix(InfixExpression {
+                left: condition,
+                operator: TokenKind::And,
+                right: arena.alloc(predicate),
+            }),
+            None => predicate,
+        };
+        dot.condition = Some(arena.alloc(condition));
+        dot
+    }
+}
+
+/// Maps table, column and alias names to another case, like camelCase to snake_case
+pub struct Recase(pub Case);
+impl Recase {
+    fn name(&self, name: &str) -> String {
+        match self.0 {
+            Case::AsIs => name.to_string(),
+            Case::Snake => snake_case(name),
+            Case::Camel => camel_case(name),
+            Case::Pascal => pascal_case(name),
+        }
+    }
+}
+impl Fold for Recase {
+    fn fold_stmt(&mut self, arena: &mut Arena, statement: Stmt) -> Stmt {
+        match fold_stmt(self, arena, statement) {
+            Stmt::Alias(mut alias) => {
+                alias.alias = self.fold_identifier(alias.alias);
+                Stmt::Alias(alias)
+            }
+            Stmt::With(mut with) => {
+                for table in &mut with.tables {
+                    table.name.literal = self.name(&table.name.literal).into();
+                }
+                Stmt::With(with)
+            }
+            statement => statement,
+        }
+    }
+    fn fold_dot(&mut self, arena: &mut Arena, dot: DotStatement) -> DotStatement {
+        let mut dot = fold_dot(self, arena, dot);
+        dot.alias = dot.alias.map(|alias| self.fold_identifier(alias));
+        dot
+    }
+    fn fold_table(&mut self, mut table: IdentifierStatement) -> IdentifierStatement {
+        table.literal = self.name(&table.literal).into();
+        table
+    }
+    fn fold_identifier(&mut self, mut identifier: IdentifierStatement) -> IdentifierStatement {
+        identifier.literal = self.name(&identifier.literal).into();
+        identifier
+    }
+}
+
+pub fn snake_case(name: &str) -> String {
+    let characters: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len());
+    for (index, character) in characters.iter().enumerate() {
+        if character.is_uppercase() && index > 0 {
+            let previous = characters[index - 1];
+            let next = characters.get(index + 1);
+            let boundary = previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase()));
+            if boundary {
+                snake.push('_');
+            }
+        }
+        snake.extend(character.to_lowercase());
+    }
+    snake
+}
+
+pub fn camel_case(name: &str) -> String {
+    let pascal = pascal_case(name);
+    let start = pascal.len() - pascal.trim_start_matches('_').len();
+    let mut characters = pascal[start..].chars();
+    match characters.next() {
+        Some(first) => format!(
+            "{}{}{}",
+            &pascal[..start],
+            first.to_lowercase(),
+            characters.as_str()
+        ),
+        None => pascal,
+    }
+}
+
+pub fn pascal_case(name: &str) -> String {
+    // Leading underscores usually mean something, like a private column, so they are kept
+    let rest = name.trim_start_matches('_');
+    let mut pascal = name[..name.len() - rest.len()].to_string();
+    for word in snake_case(rest).split('_') {
+        let mut characters = word.chars();
+        if let Some(first) = characters.next() {
+            pascal.extend(first.to_uppercase());
+            pascal.push_str(characters.as_str());
+        }
+    }
+    pascal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Program};
+
+    fn fold(input: &str, folder: &mut impl Fold) -> String {
+        let program = Program::new(input).unwrap().fold(folder);
+        program.run_with(&mut Context::default()).unwrap()
+    }
+
+    #[test]
+    fn test_soft_delete() {
+        assert_eq!(
+            fold(".users { name }", &mut SoftDelete::default()),
+            "SELECT name FROM users WHERE deleted_at IS NULL;"
+        );
+        assert_eq!(
+            fold(
+                ".users u where { a = 1 or b = 2 } { name }",
+                &mut SoftDelete::default()
+            ),
+            "SELECT name FROM users u WHERE (a = 1 OR b = 2) AND u.deleted_at IS NULL;"
+        );
+        assert_eq!(
+            fold("-users(id: 1)", &mut SoftDelete::default()),
+            "DELETE FROM users WHERE id = 1;"
+        );
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(
+            fold(
+                ".userProfiles up { firstName, count(postID) as postCount } order by up.firstName",
+                &mut Recase(Case::Snake)
+            ),
+            "SELECT first_name, COUNT(post_id) AS post_count FROM user_profiles up ORDER BY up.first_name;"
+        );
+        assert_eq!(snake_case("HTTPServer2Name"), "http_server2_name");
+        assert_eq!(snake_case("already_snake"), "already_snake");
+        let mut context = Context {
+            case: Case::Snake,
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::compile(
+                "let activeUsers = .users where { isActive } { id }; .activeUsers { id }",
+                &mut context
+            )
+            .unwrap(),
+            "WITH active_users AS (SELECT id FROM users WHERE is_active) SELECT id FROM active_users;"
+        );
+    }
+
+    #[test]
+    fn test_recase_upsert() {
+        assert_eq!(
+            fold(
+                r#"+userProfiles { firstName: "a" } on conflict (userEmail) update { firstName }"#,
+                &mut Recase(Case::Snake)
+            ),
+            "INSERT INTO user_profiles (first_name) VALUES ('a') ON CONFLICT (user_email) DO UPDATE SET first_name = EXCLUDED.first_name;"
+        );
+    }
+
+    #[test]
+    fn test_recase_returning() {
+        assert_eq!(
+            fold(
+                r#"+userProfiles { firstName: "a" } returning { createdAt } ~userProfiles(userId: 1) { firstName: "b" } returning { updatedAt } -userProfiles(userId: 1) returning { deletedAt }"#,
+                &mut Recase(Case::Snake)
+            ),
+            "INSERT INTO user_profiles (first_name) VALUES ('a') RETURNING created_at; UPDATE user_profiles SET first_name = 'b' WHERE user_id = 1 RETURNING updated_at; DELETE FROM user_profiles WHERE user_id = 1 RETURNING deleted_at;"
+        );
+        struct Names(Vec<String>);
+        impl Visitor for Names {
+            fn visit_identifier(&mut self, identifier: &IdentifierStatement) {
+                self.0.push(identifier.literal.to_string());
+            }
+        }
+        let mut names = Names(vec![]);
+        Program::new("-users(id: 1) returning { id, name }")
+            .unwrap()
+            .visit(&mut names);
+        assert_eq!(names.0, ["id", "id", "name"]);
+    }
+
+    #[test]
+    fn test_camel_and_pascal_case() {
+        assert_eq!(
+            fold(
+                ".user_profiles up { first_name, count(post_id) as post_count } order by up.first_name",
+                &mut Recase(Case::Camel)
+            ),
+            r#"SELECT "firstName", COUNT("postId") AS "postCount" FROM "userProfiles" up ORDER BY up."firstName";"#
+        );
+        assert_eq!(
+            fold(".user_profiles { first_name }", &mut Recase(Case::Pascal)),
+            r#"SELECT "FirstName" FROM "UserProfiles";"#
+        );
+        assert_eq!(camel_case("HTTPServer2Name"), "httpServer2Name");
+        assert_eq!(camel_case("_row_id"), "_rowId");
+        assert_eq!(pascal_case("firstName"), "FirstName");
+        assert_eq!(pascal_case("__v"), "__V");
+    }
+
+    #[test]
+    fn test_visit() {
+        struct Names(Vec<String>);
+        impl Visitor for Names {
+            fn visit_table(&mut self, table: &IdentifierStatement) {
+                self.0.push(format!("table {}", table.literal));
+            }
+            fn visit_identifier(&mut self, identifier: &IdentifierStatement) {
+                self.0.push(identifier.literal.to_string());
+            }
+        }
+        let mut names = Names(vec![]);
+        Program::new(
+            ".users(id: 1) where { age > 18 } { count(name) } order by age ~users { x: 1 }",
+        )
+        .unwrap()
+        .visit(&mut names);
+        assert_eq!(
+            names.0,
+            [
+                "table users",
+                "id",
+                "age",
+                "name",
+                "age",
+                "table users",
+                "x"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fold_rename() {
+        struct Rename;
+        impl Fold for Rename {
+            fn fold_identifier(
+                &mut self,
+                mut identifier: IdentifierStatement,
+            ) -> IdentifierStatement {
+                if identifier.literal == "name" {
+                    identifier.literal = "full_name".into();
+                }
+                identifier
+            }
+        }
+        assert_eq!(
+            fold(".users(name: \"a\") { name } order by name", &mut Rename),
+            "SELECT full_name FROM users WHERE full_name = 'a' ORDER BY full_name;"
+        );
+    }
+}
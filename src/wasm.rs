@@ -0,0 +1,8 @@
+use crate::Context;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub fn compile(input: &str) -> Result<String, JsValue> {
+    crate::compile(input, &mut Context::default())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
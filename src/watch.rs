@@ -0,0 +1,95 @@
+use crate::cache::{self, Cache};
+use crate::{failure, render, write, WatchArgs};
+use nonsense::compile;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub fn run(args: WatchArgs) -> Result<(), String> {
+    if !args.path.exists() {
+        return Err(format!(
+            "EXEC ERROR: {} does not exist",
+            args.path.display()
+        ));
+    }
+    // Only a directory written to a directory has enough files for a cache to pay off
+    let cache = match &args.output {
+        Some(output) if args.path.is_dir() && !args.no_cache => {
+            Some(Cache::new(&output.join(".cache"), &args.context))
+        }
+        _ => None,
+    };
+    let mut seen: HashMap<PathBuf, SystemTime> = HashMap::new();
+    loop {
+        let mut files = Vec::new();
+        collect(&args.path, args.output.as_deref(), &mut files);
+        seen.retain(|path, _| files.contains(path));
+        for path in files {
+            let Ok(modified) = path.metadata().and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if seen.insert(path.clone(), modified) == Some(modified) {
+                continue;
+            }
+            if let Err(message) = recompile(&args, cache.as_ref(), &path) {
+                eprintln!("{}", message);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(args.interval));
+    }
+}
+
+fn recompile(args: &WatchArgs, cache: Option<&Cache>, path: &Path) -> Result<(), String> {
+    let input = std::fs::read_to_string(path)
+        .map_err(|error| format!("EXEC ERROR: Failed to read {}: {}", path.display(), error))?;
+    let (output, cached) = cache::compile(cache, &input, || {
+        let mut context = args.context.context()?;
+        let sql = compile(&input, &mut context).map_err(|error| {
+            failure(&path.display().to_string(), &input, error, &mut context).message
+        })?;
+        Ok::<_, String>(render(&sql, &context))
+    })?;
+    let hit = if cached { " (cached)" } else { "" };
+    match &args.output {
+        Some(target) if args.path.is_dir() => {
+            let relative = path.strip_prefix(&args.path).unwrap_or(path);
+            let target = target.join(relative).with_extension("sql");
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).map_err(|error| {
+                    format!(
+                        "EXEC ERROR: Failed to create {}: {}",
+                        parent.display(),
+                        error
+                    )
+                })?;
+            }
+            write(&target, &output)?;
+            eprintln!("{} -> {}{}", path.display(), target.display(), hit);
+        }
+        Some(target) => {
+            write(target, &output)?;
+            eprintln!("{} -> {}", path.display(), target.display());
+        }
+        None => print!("-- {}\n{}", path.display(), output),
+    }
+    Ok(())
+}
+
+fn collect(path: &Path, output: Option<&Path>, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        let generated = path.extension().is_some_and(|extension| extension == "sql");
+        if hidden || generated || Some(path.as_path()) == output {
+            continue;
+        }
+        collect(&path, output, files);
+    }
+}